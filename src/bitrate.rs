@@ -1,66 +1,185 @@
+use crate::series::excess;
 use oze_canopen::interface::CanOpenInfo;
+use socketcan::CanInterface;
 use std::{
     collections::VecDeque,
-    sync::Arc,
-    time::{Duration, Instant},
+    sync::{self, Arc, PoisonError, Weak},
+    time::Duration,
+};
+use tokio::{
+    sync::Mutex,
+    time::{sleep, Instant},
 };
-use tokio::{sync::Mutex, time::sleep};
 
-pub const RATES_LEN: usize = 1024;
+/// Rates kept for the chart, one hour of 10 ms samples.
+pub const RATES_LEN: usize = 360_000;
 pub const RATE_PERIOD: usize = 4;
 
-pub type RatesData = Vec<[f64; 2]>;
+/// Rates not taken by the GUI yet, at most `RATES_LEN`.
+type Pending = sync::Mutex<VecDeque<[f64; 2]>>;
+
+/// Sending half of the rates, used by the bitrate thread.
+#[derive(Debug, Clone)]
+pub struct RatesSender {
+    start: Instant,
+    pending: Weak<Pending>,
+}
+
+impl RatesSender {
+    /// Queues the rate, dropping the oldest one when the GUI did not take them for an hour,
+    /// e.g. while the window is minimized. Returns false when the GUI was closed.
+    fn send(&self, rate: [f64; 2]) -> bool {
+        let Some(pending) = self.pending.upgrade() else {
+            return false;
+        };
+        let mut pending = pending.lock().unwrap_or_else(PoisonError::into_inner);
+        if pending.len() >= RATES_LEN {
+            pending.pop_front();
+        }
+        pending.push_back(rate);
+        true
+    }
+}
+
+/// Bits per second over time, in seconds since `start`.
+///
+/// Owned by the GUI, new rates are taken from the bounded queue without waiting.
+#[derive(Debug)]
+pub struct RatesData {
+    pub start: Instant,
+    rates: Vec<[f64; 2]>,
+    pending: Arc<Pending>,
+}
+
+/// Creates the queue between the bitrate thread and the chart.
+pub fn rates_channel() -> (RatesSender, RatesData) {
+    let start = Instant::now();
+    let pending = Arc::new(Pending::new(VecDeque::new()));
+    (
+        RatesSender {
+            start,
+            pending: Arc::downgrade(&pending),
+        },
+        RatesData {
+            start,
+            rates: Vec::new(),
+            pending,
+        },
+    )
+}
+
+impl RatesData {
+    /// Takes the rates computed since the last update.
+    pub fn update(&mut self) {
+        let pending =
+            std::mem::take(&mut *self.pending.lock().unwrap_or_else(PoisonError::into_inner));
+        self.rates.extend(pending);
+        self.rates.drain(..excess(self.rates.len(), RATES_LEN));
+    }
+
+    pub fn rates(&self) -> &[[f64; 2]] {
+        &self.rates
+    }
+}
+
+/// Index of the first rate at or after `time`.
+fn position(rates: &[[f64; 2]], time: f64) -> usize {
+    rates.partition_point(|[t, _]| *t < time)
+}
+
+/// Average and peak rate over the last `seconds` of the rates.
+#[allow(clippy::cast_precision_loss)]
+pub fn window_stats(rates: &[[f64; 2]], seconds: f64) -> Option<(f64, f64)> {
+    let last = rates.last()?[0];
+    let window = &rates[position(rates, last - seconds)..];
+    let average = window.iter().map(|[_, rate]| rate).sum::<f64>() / window.len() as f64;
+    let peak = window
+        .iter()
+        .map(|[_, rate]| *rate)
+        .fold(f64::MIN, f64::max);
+    Some((average, peak))
+}
+
+/// Nominal bitrate of the interface as configured in the kernel.
+pub fn interface_bitrate(can_name: &str) -> Option<u32> {
+    CanInterface::open(can_name).ok()?.bit_rate().ok()?
+}
 
 #[derive(Clone, Debug)]
 pub struct Bitrate {
-    rates: Arc<Mutex<RatesData>>,
+    rates: RatesSender,
+    /// Last `RATE_PERIOD + 1` samples of the received bits.
     data: VecDeque<(f64, usize)>,
     canopen_info: Arc<Mutex<CanOpenInfo>>,
 }
 
 impl Bitrate {
-    pub fn new(canopen_info: Arc<Mutex<CanOpenInfo>>, output: Arc<Mutex<RatesData>>) -> Self {
+    pub fn new(canopen_info: Arc<Mutex<CanOpenInfo>>, output: RatesSender) -> Self {
         Self {
-            data: VecDeque::new(),
+            data: VecDeque::with_capacity(RATE_PERIOD + 1),
             canopen_info,
             rates: output,
         }
     }
 
-    async fn calculate_rate(&self) {
-        let mut rates = self.rates.lock().await;
-        rates.clear();
-        for (i, &(current_time, current_bits)) in self.data.iter().enumerate() {
-            if i < RATE_PERIOD {
-                continue;
-            }
-
-            let (prev_time, prev_bits) = self.data[i - RATE_PERIOD];
-            let Ok(bits_diff) = i32::try_from(current_bits - prev_bits) else {
-                continue;
-            };
-            let bits_diff = f64::from(bits_diff);
-            let duration_secs = current_time - prev_time;
-            if duration_secs > 0.0 {
-                let rate = bits_diff / duration_secs;
-                rates.push([current_time, rate]);
-            }
-        }
+    /// Rate over the last `RATE_PERIOD` samples.
+    fn calculate_rate(&self) -> Option<[f64; 2]> {
+        let (current_time, current_bits) = *self.data.get(RATE_PERIOD)?;
+        let (prev_time, prev_bits) = self.data[0];
+        let bits_diff = f64::from(i32::try_from(current_bits.checked_sub(prev_bits)?).ok()?);
+        let duration_secs = current_time - prev_time;
+        (duration_secs > 0.0).then(|| [current_time, bits_diff / duration_secs])
     }
 
     pub fn start_thread(mut self) {
         tokio::spawn(async move {
-            let started = Instant::now();
             loop {
                 let b = self.canopen_info.lock().await.rx_bits;
-                if self.data.len() > RATES_LEN + RATE_PERIOD {
+                if self.data.len() > RATE_PERIOD {
                     self.data.pop_front();
                 }
 
-                self.data.push_back((started.elapsed().as_secs_f64(), b));
-                self.calculate_rate().await;
+                self.data
+                    .push_back((self.rates.start.elapsed().as_secs_f64(), b));
+                if let Some(rate) = self.calculate_rate() {
+                    if !self.rates.send(rate) {
+                        // the GUI was closed
+                        break;
+                    }
+                }
                 sleep(Duration::from_millis(10)).await;
             }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{rates_channel, window_stats, RATES_LEN};
+
+    #[test]
+    fn test_rates() {
+        let (sender, mut data) = rates_channel();
+        assert!(sender.send([0.5, 10.0]));
+        assert!(sender.send([1.0, 20.0]));
+        data.update();
+        assert_eq!(data.rates(), [[0.5, 10.0], [1.0, 20.0]]);
+
+        // the oldest rates are dropped when the GUI doesn't take them
+        for n in 0..=RATES_LEN {
+            sender.send([2.0, f64::from(u32::try_from(n).unwrap())]);
+        }
+        let pending = data.pending.lock().unwrap();
+        assert_eq!(pending.len(), RATES_LEN);
+        assert_eq!(pending.front(), Some(&[2.0, 1.0]));
+        drop(pending);
+
+        drop(data);
+        assert!(!sender.send([3.0, 0.0]));
+
+        assert_eq!(window_stats(&[], 1.0), None);
+        let rates = vec![[0.0, 900.0], [1.0, 100.0], [1.5, 300.0], [2.0, 200.0]];
+        assert_eq!(window_stats(&rates, 1.0), Some((200.0, 300.0)));
+        assert_eq!(window_stats(&rates, 10.0), Some((375.0, 900.0)));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::message_cached::MessageCached;
+use oze_canopen::canopen::NodeId;
+use std::collections::{BTreeMap, VecDeque};
+use tokio::time::Instant;
+
+/// Width of one bucket of the load history in seconds.
+pub const BUCKET_SECONDS: f64 = 0.25;
+/// Buckets kept in the history, one minute.
+const MAX_BUCKETS: usize = 240;
+/// Buckets summed for the current load, one second.
+const RECENT_BUCKETS: usize = 4;
+const CRC15_POLYNOMIAL: u16 = 0x4599;
+/// CRC delimiter, ACK slot and delimiter, end of frame and interframe space.
+const TRAILER_BITS: u32 = 1 + 2 + 7 + 3;
+
+/// Appends the `count` least significant bits of `value`, most significant first.
+fn push_bits(bits: &mut Vec<bool>, value: u32, count: u32) {
+    for n in (0..count).rev() {
+        bits.push(value >> n & 1 != 0);
+    }
+}
+
+fn crc15(bits: &[bool]) -> u16 {
+    let mut crc = 0u16;
+    for &bit in bits {
+        let next = bit ^ (crc >> 14 & 1 != 0);
+        crc = (crc << 1) & 0x7FFF;
+        if next {
+            crc ^= CRC15_POLYNOMIAL;
+        }
+    }
+    crc
+}
+
+/// Stuff bits inserted after five equal bits, stuff bits start a new run themselves.
+fn stuff_bits(bits: &[bool]) -> u32 {
+    let mut stuffed = 0;
+    let mut last = None;
+    let mut run = 0;
+    for &bit in bits {
+        if last == Some(bit) {
+            run += 1;
+        } else {
+            last = Some(bit);
+            run = 1;
+        }
+        if run == 5 {
+            stuffed += 1;
+            last = Some(!bit);
+            run = 1;
+        }
+    }
+    stuffed
+}
+
+/// Length of a data frame on the bus including stuff bits and interframe space.
+///
+/// Stuff bits are counted on the reconstructed frame with its CRC, so they are exact as long as
+/// the frame was sent as a data frame with the received identifier and data.
+pub fn frame_bits(id: u32, extended: bool, data: &[u8]) -> u32 {
+    let dlc = u32::try_from(data.len().min(8)).unwrap_or(8);
+    let mut bits = Vec::with_capacity(128);
+    bits.push(false); // SOF
+    if extended {
+        push_bits(&mut bits, id >> 18, 11);
+        bits.push(true); // SRR
+        bits.push(true); // IDE
+        push_bits(&mut bits, id, 18);
+        bits.extend([false, false, false]); // RTR, r1, r0
+    } else {
+        push_bits(&mut bits, id, 11);
+        bits.extend([false, false, false]); // RTR, IDE, r0
+    }
+    push_bits(&mut bits, dlc, 4);
+    for byte in &data[..data.len().min(8)] {
+        push_bits(&mut bits, u32::from(*byte), 8);
+    }
+    let crc = crc15(&bits);
+    push_bits(&mut bits, u32::from(crc), 15);
+
+    let stuffed = stuff_bits(&bits);
+    u32::try_from(bits.len()).unwrap_or(u32::MAX) + stuffed + TRAILER_BITS
+}
+
+/// Frames and bits of one category.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoadTotals {
+    pub frames: u64,
+    pub bits: u64,
+}
+
+impl LoadTotals {
+    fn add(&mut self, bits: u32) {
+        self.frames += 1;
+        self.bits += u64::from(bits);
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Bucket {
+    index: u64,
+    by_type: BTreeMap<String, u64>,
+    by_node: BTreeMap<Option<NodeId>, u64>,
+}
+
+/// Bus load computed from the received frames, by message type and by node.
+#[derive(Debug)]
+pub struct BusLoad {
+    start: Instant,
+    buckets: VecDeque<Bucket>,
+    pub by_type: BTreeMap<String, LoadTotals>,
+    /// Messages without a node ID, e.g. SYNC and NMT, are counted under `None`.
+    pub by_node: BTreeMap<Option<NodeId>, LoadTotals>,
+}
+
+impl Default for BusLoad {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            buckets: VecDeque::new(),
+            by_type: BTreeMap::new(),
+            by_node: BTreeMap::new(),
+        }
+    }
+}
+
+/// Category of the message in the load by type.
+fn message_type(msg: &MessageCached) -> String {
+    if msg.extended {
+        "J1939".to_owned()
+    } else {
+        msg.msg.parsed_type.to_string().to_owned()
+    }
+}
+
+impl BusLoad {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        let bits = frame_bits(
+            msg.can_id,
+            msg.extended,
+            &msg.msg.msg.data[..msg.msg.msg.dlc.min(8)],
+        );
+
+        let kind = message_type(msg);
+        let node_id = msg.msg.parsed_node_id;
+        self.by_type.entry(kind.clone()).or_default().add(bits);
+        self.by_node.entry(node_id).or_default().add(bits);
+
+        let index = self.index(msg.get_timestamp());
+        let bucket = self.bucket(index);
+        *bucket.by_type.entry(kind).or_default() += u64::from(bits);
+        *bucket.by_node.entry(node_id).or_default() += u64::from(bits);
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn index(&self, time: Instant) -> u64 {
+        let seconds = time.saturating_duration_since(self.start).as_secs_f64();
+        (seconds / BUCKET_SECONDS) as u64
+    }
+
+    /// Returns the bucket, adding empty buckets for silent periods.
+    fn bucket(&mut self, index: u64) -> &mut Bucket {
+        let last = self.buckets.back().map(|b| b.index);
+        match last {
+            Some(last) if index <= last => {
+                // late message, counted in the newest bucket
+            }
+            Some(last) => {
+                let first = last + 1 + (index - last - 1).saturating_sub(MAX_BUCKETS as u64);
+                for i in first..=index {
+                    self.buckets.push_back(Bucket {
+                        index: i,
+                        ..Default::default()
+                    });
+                }
+            }
+            None => self.buckets.push_back(Bucket {
+                index,
+                ..Default::default()
+            }),
+        }
+        while self.buckets.len() > MAX_BUCKETS {
+            self.buckets.pop_front();
+        }
+        self.buckets.back_mut().unwrap()
+    }
+
+    /// History of bits per second by message type, as points for a chart.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn history_by_type(&self) -> BTreeMap<String, Vec<[f64; 2]>> {
+        let mut history: BTreeMap<String, Vec<[f64; 2]>> = self
+            .by_type
+            .keys()
+            .map(|k| (k.clone(), Vec::with_capacity(self.buckets.len())))
+            .collect();
+        for bucket in &self.buckets {
+            let time = bucket.index as f64 * BUCKET_SECONDS;
+            for (kind, points) in &mut history {
+                let bits = bucket.by_type.get(kind).copied().unwrap_or_default();
+                points.push([time, bits as f64 / BUCKET_SECONDS]);
+            }
+        }
+        history
+    }
+
+    /// Bits per second over the last complete second before `now`, by message type and by node.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn recent(&self, now: Instant) -> (BTreeMap<String, f64>, BTreeMap<Option<NodeId>, f64>) {
+        let mut by_type = BTreeMap::new();
+        let mut by_node = BTreeMap::new();
+        // the bucket of `now` is still filling
+        let current = self.index(now);
+        let first = current.saturating_sub(RECENT_BUCKETS as u64);
+        let complete = self
+            .buckets
+            .iter()
+            .rev()
+            .skip_while(|b| b.index >= current)
+            .take_while(|b| b.index >= first);
+        for bucket in complete {
+            for (kind, bits) in &bucket.by_type {
+                *by_type.entry(kind.clone()).or_default() += *bits as f64;
+            }
+            for (node, bits) in &bucket.by_node {
+                *by_node.entry(*node).or_default() += *bits as f64;
+            }
+        }
+        (by_type, by_node)
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_bits, stuff_bits, BusLoad};
+    use crate::message_cached::test_message;
+    use std::time::Duration;
+
+    #[test]
+    fn test_frame_bits() {
+        // 11 bit frame: 47 bits without stuffing plus 8 per data byte
+        for (id, data) in [(0x181u32, &[0x12u8, 0x34][..]), (0x000, &[0u8; 8][..])] {
+            let bits = frame_bits(id, false, data);
+            let len = u32::try_from(data.len()).unwrap();
+            let plain = 47 + 8 * len;
+            let worst = (34 + 8 * len - 1) / 4;
+            assert!((plain..=plain + worst).contains(&bits), "{bits}");
+        }
+        assert!(frame_bits(0x000, false, &[0; 8]) > frame_bits(0x555, false, &[0x55; 8]));
+        // 29 bit frame: 67 bits without stuffing
+        assert!(frame_bits(0x18FE_F100, true, &[]) >= 67);
+
+        assert_eq!(stuff_bits(&[false; 5]), 1);
+        // the stuff bit starts a new run of ones
+        assert_eq!(
+            stuff_bits(&[false, false, false, false, false, true, true, true, true]),
+            2
+        );
+    }
+
+    #[test]
+    fn test_recent_load() {
+        let mut load = BusLoad::default();
+        let start = load.start;
+        let at = |ms| start + Duration::from_millis(ms);
+        let (first, filling) = (at(100), at(1100));
+        load.push_data(&test_message(0x181, &[0; 8], first));
+        load.push_data(&test_message(0x181, &[0; 8], filling));
+
+        let bits = frame_bits(0x181, false, &[0; 8]);
+        let (by_type, by_node) = load.recent(at(1200));
+        assert_eq!(by_type.get("PDO"), Some(&f64::from(bits)));
+        assert_eq!(by_node.get(&Some(1)), Some(&f64::from(bits)));
+        // nothing received in the last complete second
+        let (by_type, _) = load.recent(at(5000));
+        assert!(by_type.is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{bus_load::BusLoad, message_cached::MessageCached};
+use egui::RichText;
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+use std::collections::BTreeMap;
+use tokio::time::Instant;
+
+/// Shows which message types and nodes use the bus bandwidth.
+#[derive(Debug, Default)]
+pub struct BusLoadPanel {
+    load: BusLoad,
+}
+
+fn share(part: f64, total: f64) -> String {
+    if total > 0.0 {
+        format!("{:.1}", part / total * 100.0)
+    } else {
+        "0.0".to_owned()
+    }
+}
+
+impl BusLoadPanel {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        self.load.push_data(msg);
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui) {
+        let (by_type, by_node) = self.load.recent(Instant::now());
+        let total: f64 = by_type.values().sum();
+        ui.horizontal(|ui| {
+            ui.label(format!("total={total:.0} bit/s"));
+            if ui
+                .button("🗑")
+                .on_hover_text("Clear bus load statistics")
+                .clicked()
+            {
+                self.load.clear();
+            }
+        });
+        ui.label("Frame lengths include the frame overhead and the stuff bits");
+
+        self.show_chart(ui);
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| self.show_types(ui, &by_type, total));
+            ui.separator();
+            ui.vertical(|ui| self.show_nodes(ui, &by_node, total));
+        });
+    }
+
+    /// Stacked area chart, each line is the sum of its type and all types drawn after it.
+    fn show_chart(&self, ui: &mut egui::Ui) {
+        let history = self.load.history_by_type();
+        let mut kinds: Vec<(&String, f64)> = history
+            .iter()
+            .map(|(kind, points)| (kind, points.iter().map(|p| p[1]).sum()))
+            .collect();
+        // the largest type at the bottom of the stack
+        kinds.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let len = history.values().next().map_or(0, Vec::len);
+        let mut stacked = vec![0.0; len];
+        let mut lines = Vec::with_capacity(kinds.len());
+        for (kind, _) in kinds {
+            let points: Vec<[f64; 2]> = history[kind]
+                .iter()
+                .zip(&mut stacked)
+                .map(|(p, sum)| {
+                    *sum += p[1];
+                    [p[0], *sum]
+                })
+                .collect();
+            lines.push(Line::new(PlotPoints::new(points)).fill(0.0).name(kind));
+        }
+
+        Plot::new("bus_load_plot")
+            .height(150.0)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .legend(Legend::default())
+            .show(ui, |plot_ui| {
+                // upper areas first, lower ones are drawn over them
+                for line in lines.into_iter().rev() {
+                    plot_ui.line(line);
+                }
+            });
+    }
+
+    fn show_types(&self, ui: &mut egui::Ui, recent: &BTreeMap<String, f64>, total: f64) {
+        egui::Grid::new("bus_load_types")
+            .striped(true)
+            .show(ui, |ui| {
+                for header in ["Packet type", "Frames", "bit/s", "%"] {
+                    ui.label(RichText::new(header).strong());
+                }
+                ui.end_row();
+
+                for (kind, totals) in &self.load.by_type {
+                    let rate = recent.get(kind).copied().unwrap_or_default();
+                    ui.label(kind);
+                    ui.label(totals.frames.to_string());
+                    ui.label(format!("{rate:.0}"));
+                    ui.label(share(rate, total));
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn show_nodes(&self, ui: &mut egui::Ui, recent: &BTreeMap<Option<u8>, f64>, total: f64) {
+        egui::ScrollArea::vertical()
+            .id_salt("bus_load_nodes")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("bus_load_nodes_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in ["Node ID", "Frames", "bit/s", "%"] {
+                            ui.label(RichText::new(header).strong());
+                        }
+                        ui.end_row();
+
+                        for (node_id, totals) in &self.load.by_node {
+                            let rate = recent.get(node_id).copied().unwrap_or_default();
+                            ui.label(
+                                node_id.map_or_else(|| "none".to_owned(), |n| format!("{n:3}")),
+                            );
+                            ui.label(totals.frames.to_string());
+                            ui.label(format!("{rate:.0}"));
+                            ui.label(share(rate, total));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
@@ -1,35 +1,372 @@
-use std::sync::Arc;
+use std::time::Duration;
 
-use crate::{bitrate::RatesData, theme::OZON_PINK};
-use egui::Vec2b;
-use egui_plot::{Line, Plot, PlotPoints};
-use tokio::{runtime::Handle, sync::Mutex};
+use crate::{
+    bitrate::{self, RatesData},
+    frame_rate::FrameRate,
+    series,
+    theme::{OZON_BLUE, OZON_PINK},
+};
+use egui::{Color32, DragValue, Vec2b};
+use egui_plot::{HLine, Legend, Line, Plot, PlotBounds, PlotPoints, Polygon, VLine};
+use oze_canopen::interface::Connection;
+use tokio::time::Instant;
+
+/// Interval of reading the bitrate back from the interface while it is unknown.
+const INTERFACE_RETRY_SECONDS: u64 = 5;
+/// Time shown while the chart follows the newest rates.
+const FOLLOW_SECONDS: f64 = 10.0;
+/// Points drawn at most, rates are merged when zoomed out.
+const MAX_POINTS: usize = 2000;
+/// Frames within this many pixels of the cursor are highlighted.
+const CURSOR_PIXELS: f64 = 3.0;
 
 #[derive(Debug)]
 pub struct Chart {
-    channel: Arc<Mutex<RatesData>>,
+    rates: RatesData,
+    /// Bitrate of the connection, or read back from the interface.
+    bitrate: Option<u32>,
+    /// Interface the bitrate was read back from and when.
+    interface: Option<(String, Instant)>,
+    /// Length of the window of the average and peak load in seconds.
+    pub window: f64,
+    /// Load in percent above which the load is shown as a warning.
+    pub warning: f64,
+    /// Highest load since the last reset, in bits per second.
+    peak: f64,
+    /// Time of the first rate, the x axis is in seconds since then.
+    start: Instant,
+    frame_rate: FrameRate,
+    /// Shows the newest rates until the chart is dragged or zoomed.
+    follow: bool,
+    /// Time range set while following, to notice when the user moved the chart.
+    followed: Option<(f64, f64)>,
+    /// Clicked time and its tolerance in seconds.
+    cursor: Option<(f64, f64)>,
+    /// Time range selected by dragging with shift.
+    selection: Option<(f64, f64)>,
+    selecting: Option<(f64, f64)>,
 }
 
 impl Chart {
-    pub fn new(channel: Arc<Mutex<RatesData>>) -> Chart {
-        Chart { channel }
+    pub fn new(rates: RatesData) -> Chart {
+        let start = rates.start;
+        Chart {
+            rates,
+            bitrate: None,
+            interface: None,
+            window: 5.0,
+            warning: 70.0,
+            peak: 0.0,
+            start,
+            frame_rate: FrameRate::new(start),
+            follow: true,
+            followed: None,
+            cursor: None,
+            selection: None,
+            selecting: None,
+        }
+    }
+
+    /// Time of the first rate, timestamps of the message lists should be relative to it.
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+
+    fn instant(&self, seconds: f64) -> Instant {
+        self.start + Duration::from_secs_f64(seconds.max(0.0))
+    }
+
+    /// Counts a received frame in the frame rate.
+    pub fn push_frame(&mut self, time: Instant, passed_filter: bool) {
+        self.frame_rate.push(time, passed_filter);
+    }
+
+    /// Recounts the frame rate of the filtered frames after the filter changed.
+    pub fn refilter_frames(&mut self, oldest: Instant, passed: impl IntoIterator<Item = Instant>) {
+        self.frame_rate.refilter(oldest, passed);
+    }
+
+    /// Frames around the clicked time.
+    pub fn cursor(&self) -> Option<(Instant, Instant)> {
+        self.cursor.map(|(time, tolerance)| {
+            (
+                self.instant(time - tolerance),
+                self.instant(time + tolerance),
+            )
+        })
+    }
+
+    /// Time range selected in the chart.
+    pub fn selection(&self) -> Option<(Instant, Instant)> {
+        self.selection
+            .map(|(from, to)| (self.instant(from), self.instant(to)))
+    }
+
+    /// Uses the bitrate of the connection, if it is not set it is read back from the interface.
+    pub fn set_connection(&mut self, connection: &Connection) {
+        if connection.bitrate.is_some() {
+            self.bitrate = connection.bitrate;
+            self.interface = None;
+            return;
+        }
+
+        let stale = self.interface.as_ref().map_or(true, |(name, time)| {
+            *name != connection.can_name
+                || (self.bitrate.is_none() && time.elapsed().as_secs() >= INTERFACE_RETRY_SECONDS)
+        });
+        if stale {
+            self.bitrate = bitrate::interface_bitrate(&connection.can_name);
+            self.interface = Some((connection.can_name.clone(), Instant::now()));
+        }
     }
 
-    pub fn ui(&mut self, ui: &mut egui::Ui) {
+    fn percent(&self, rate: f64) -> Option<f64> {
+        self.bitrate.map(|b| rate / f64::from(b) * 100.0)
+    }
+
+    /// Rate as text, with the percentage of the bitrate when it is known.
+    fn rate_text(&self, rate: f64) -> String {
+        match self.percent(rate) {
+            Some(percent) => format!("{percent:.1}%"),
+            None => format!("{:.1} kbit/s", rate / 1000.0),
+        }
+    }
+
+    fn show_stats(&mut self, ui: &mut egui::Ui) {
+        let data = self.rates.rates();
+        let stats = bitrate::window_stats(data, self.window);
+        let current = data.last().map_or(0.0, |[_, rate]| *rate);
+        if let Some((_, peak)) = stats {
+            self.peak = self.peak.max(peak);
+        }
+        ui.horizontal(|ui| {
+            match self.bitrate {
+                Some(b) => ui.label(format!("bus load of {} kbit/s", b / 1000)),
+                None => ui.label("bus load, bitrate unknown"),
+            };
+            if let Some((average, peak)) = stats {
+                let text = format!(
+                    "current={} average={} peak={}",
+                    self.rate_text(current),
+                    self.rate_text(average),
+                    self.rate_text(peak)
+                );
+                if self.percent(peak).is_some_and(|p| p > self.warning) {
+                    ui.colored_label(OZON_PINK, text);
+                } else {
+                    ui.label(text);
+                }
+            }
+            ui.label(format!("max={}", self.rate_text(self.peak)));
+            if ui.button("🗑").on_hover_text("Reset max load").clicked() {
+                self.peak = 0.0;
+            }
+            ui.separator();
+            ui.label("window");
+            ui.add(
+                DragValue::new(&mut self.window)
+                    .range(0.1..=10.0)
+                    .speed(0.1)
+                    .suffix(" s"),
+            )
+            .on_hover_text("Window of the average and peak load");
+            ui.label("warning");
+            ui.add(
+                DragValue::new(&mut self.warning)
+                    .range(1.0..=100.0)
+                    .suffix(" %"),
+            )
+            .on_hover_text("Load above which the load is highlighted");
+        });
+    }
+
+    fn show_navigation(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!self.follow, egui::Button::new("⏵ follow"))
+                .on_hover_text("Show the newest load, double click the chart to do the same")
+                .clicked()
+            {
+                self.follow = true;
+            }
+            if let Some((time, _)) = self.cursor {
+                ui.label(format!("cursor={time:.6}"));
+                if ui.button("✖").on_hover_text("Remove cursor").clicked() {
+                    self.cursor = None;
+                }
+            }
+            if let Some((from, to)) = self.selection {
+                ui.label(format!("showing {from:.6} - {to:.6}"));
+                if ui
+                    .button("✖")
+                    .on_hover_text("Show messages of the whole capture")
+                    .clicked()
+                {
+                    self.selection = None;
+                }
+            }
+            ui.label("click to set the cursor, drag with shift to filter a time range")
+                .on_hover_text(
+                    "Drag to pan, scroll or ctrl+scroll to zoom, drag with the right button to zoom to a range",
+                );
+        });
+    }
+
+    /// Shows the chart, returns `true` when the cursor was moved.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let shift = ui.input(|i| i.modifiers.shift);
         let plot = Plot::new("plot")
             .height(150.0)
+            .allow_drag(Vec2b::new(!shift, false))
+            .allow_zoom(Vec2b::new(true, false))
+            .allow_scroll(Vec2b::new(true, false))
+            .allow_double_click_reset(false)
+            .show_axes(Vec2b::new(true, true));
+
+        self.rates.update();
+        self.show_stats(ui);
+        self.show_navigation(ui);
+
+        let bitrate = self.bitrate.map(f64::from);
+        let scale = bitrate.map_or(1.0, |b| 100.0 / b);
+        let last = self.rates.rates().last().map_or(0.0, |[time, _]| *time);
+        let mut clicked = None;
+        let mut range = (0.0, 0.0);
+        let response = plot.show(ui, |plot_ui| {
+            let mut bounds = plot_ui.plot_bounds();
+            if self.follow {
+                let moved = self.followed.is_some_and(|(min, max)| {
+                    (bounds.min()[0] - min).abs() > 1e-9 || (bounds.max()[0] - max).abs() > 1e-9
+                });
+                self.follow = !moved;
+            }
+            let (min, max) = if self.follow || !bounds.is_valid_x() {
+                (last - FOLLOW_SECONDS, last)
+            } else {
+                (bounds.min()[0], bounds.max()[0])
+            };
+            self.followed = self.follow.then_some((min, max));
+            range = (min, max);
+
+            let data: Vec<[f64; 2]> = series::downsample(self.rates.rates(), min..=max, MAX_POINTS)
+                .into_iter()
+                .map(|[time, rate]| [time, rate * scale])
+                .collect();
+            let top = data.iter().map(|[_, y]| *y).fold(0.0, f64::max);
+            let top = if bitrate.is_some() {
+                top.max(100.0)
+            } else {
+                top.max(1.0)
+            } * 1.05;
+            bounds = PlotBounds::from_min_max([min, 0.0], [max, top]);
+            plot_ui.set_plot_bounds(bounds);
+
+            if let Some((from, to)) = self.selecting.or(self.selection) {
+                let area = vec![[from, 0.0], [to, 0.0], [to, top], [from, top]];
+                plot_ui.polygon(
+                    Polygon::new(PlotPoints::new(area))
+                        .fill_color(Color32::from_rgba_unmultiplied(0, 91, 255, 40))
+                        .stroke(egui::Stroke::new(1.0, OZON_BLUE)),
+                );
+            }
+            if bitrate.is_some() {
+                plot_ui.hline(HLine::new(self.warning).color(OZON_BLUE).name("warning"));
+            }
+            if let Some((time, _)) = self.cursor {
+                plot_ui.vline(VLine::new(time).color(OZON_BLUE));
+            }
+            plot_ui.line(Line::new(PlotPoints::new(data)).color(OZON_PINK));
+
+            let response = plot_ui.response();
+            let pointer = plot_ui.pointer_coordinate().map(|p| p.x);
+            if shift && response.drag_started() {
+                self.selecting = pointer.map(|x| (x, x));
+            } else if let (Some((from, _)), Some(x)) = (self.selecting, pointer) {
+                self.selecting = Some((from, x));
+            }
+            if response.clicked() {
+                let seconds_per_pixel = (max - min) / f64::from(response.rect.width());
+                clicked = pointer.map(|x| (x, seconds_per_pixel * CURSOR_PIXELS));
+            }
+            if response.double_clicked() {
+                self.follow = true;
+            }
+        });
+
+        if response.response.drag_stopped() {
+            if let Some((from, to)) = self.selecting.take() {
+                if (to - from).abs() > 0.0 {
+                    self.selection = Some((from.min(to), from.max(to)));
+                }
+            }
+        }
+        let clicked = clicked.or(self.show_frame_rate(ui, range));
+        if clicked.is_some() {
+            self.cursor = clicked;
+        }
+        clicked.is_some()
+    }
+
+    /// Frames per second in the time range of the load chart, returns the clicked cursor.
+    fn show_frame_rate(&mut self, ui: &mut egui::Ui, (min, max): (f64, f64)) -> Option<(f64, f64)> {
+        self.frame_rate.advance(Instant::now());
+        // the newest bucket is still filling
+        let (total, filtered) = (self.frame_rate.total(), self.frame_rate.filtered());
+        let total = &total[..total.len().saturating_sub(1)];
+        let filtered = &filtered[..filtered.len().saturating_sub(1)];
+        let current = |rates: &[[f64; 2]]| rates.last().map_or(0.0, |r| r[1]);
+        let mut text = format!(
+            "frames/s current={:.0} filtered={:.0}",
+            current(total),
+            current(filtered)
+        );
+        if let Some((average, peak)) = bitrate::window_stats(total, self.window) {
+            text = format!("{text} average={average:.0} peak={peak:.0}");
+        }
+        ui.horizontal(|ui| {
+            ui.label(text);
+            if ui.button("🗑").on_hover_text("Clear frame rate").clicked() {
+                self.frame_rate.clear();
+            }
+        });
+
+        let total = series::downsample(self.frame_rate.total(), min..=max, MAX_POINTS);
+        let filtered = series::downsample(self.frame_rate.filtered(), min..=max, MAX_POINTS);
+        let top = total.iter().map(|[_, y]| *y).fold(1.0, f64::max) * 1.05;
+        let cursor = self.cursor;
+        Plot::new("frame_rate_plot")
+            .height(100.0)
             .allow_drag(false)
             .allow_boxed_zoom(false)
             .allow_scroll(false)
             .allow_zoom(false)
-            .show_axes(Vec2b::new(false, true));
+            .allow_double_click_reset(false)
+            .legend(Legend::default())
+            .show(ui, |plot_ui| {
+                plot_ui.set_plot_bounds(PlotBounds::from_min_max([min, 0.0], [max, top]));
+                if let Some((time, _)) = cursor {
+                    plot_ui.vline(VLine::new(time).color(OZON_BLUE));
+                }
+                plot_ui.line(
+                    Line::new(PlotPoints::new(total))
+                        .color(OZON_PINK)
+                        .name("all frames"),
+                );
+                plot_ui.line(
+                    Line::new(PlotPoints::new(filtered))
+                        .color(OZON_BLUE)
+                        .name("filtered"),
+                );
 
-        Handle::current().block_on(async {
-            let data: Vec<[f64; 2]> = self.channel.lock().await.clone();
-            // There is no Borrowed PlotPoints so we need to copy every time
-            plot.show(ui, |plot_ui| {
-                plot_ui.line(Line::new(PlotPoints::new(data)).color(OZON_PINK));
+                let response = plot_ui.response();
+                if !response.clicked() {
+                    return None;
+                }
+                let seconds_per_pixel = (max - min) / f64::from(response.rect.width());
+                plot_ui
+                    .pointer_coordinate()
+                    .map(|p| (p.x, seconds_per_pixel * CURSOR_PIXELS))
             })
-        });
+            .inner
     }
 }
@@ -0,0 +1,271 @@
+use crate::{
+    message_cached::{MessageCached, RxMessageAdditional},
+    sdo_session::{SdoDirection, SdoResult, SdoTransfer},
+};
+use oze_canopen::canopen::{NodeId, RxMessageType};
+use std::collections::{BTreeMap, BTreeSet};
+use tokio::time::Instant;
+
+/// Read input 8-bit, one byte of 8 digital inputs per subindex.
+pub const DIGITAL_INPUTS: u16 = 0x6000;
+/// Write output 8-bit, one byte of 8 digital outputs per subindex.
+pub const DIGITAL_OUTPUTS: u16 = 0x6200;
+/// Read analog input 16-bit.
+pub const ANALOG_INPUTS: u16 = 0x6401;
+/// Write analog output 16-bit.
+pub const ANALOG_OUTPUTS: u16 = 0x6411;
+
+const DEVICE_TYPE: u16 = 0x1000;
+const PROFILE_IO: u32 = 401;
+const FUNCTION_CODE_MASK: u16 = 0x780;
+
+/// Inputs and outputs of one `CiA 401` module, values by subindex.
+#[derive(Debug, Clone)]
+pub struct IoNode {
+    pub node_id: NodeId,
+    pub digital_inputs: BTreeMap<u8, u8>,
+    pub digital_outputs: BTreeMap<u8, u8>,
+    pub analog_inputs: BTreeMap<u8, i16>,
+    pub analog_outputs: BTreeMap<u8, i16>,
+    pub updated: Instant,
+}
+
+/// Collects I/O states of `CiA 401` modules.
+///
+/// Values come from PDOs decoded with learned mappings. PDOs without a learned mapping are decoded
+/// with the default `CiA 401` layout for nodes which reported device profile 401 (object 0x1000)
+/// or were added manually.
+#[derive(Debug, Default)]
+pub struct IoModules {
+    nodes: BTreeMap<NodeId, IoNode>,
+    default_layout: BTreeSet<NodeId>,
+}
+
+/// Object placed into a PDO by the default `CiA 401` mapping.
+struct DefaultLayout {
+    index: u16,
+    first_subindex: u8,
+    bytes: usize,
+}
+
+impl DefaultLayout {
+    fn from_function_code(code: u16) -> Option<Self> {
+        let (index, first_subindex, bytes) = match code {
+            0x180 => (DIGITAL_INPUTS, 1, 1),
+            0x200 => (DIGITAL_OUTPUTS, 1, 1),
+            0x280 => (ANALOG_INPUTS, 1, 2),
+            0x380 => (ANALOG_INPUTS, 5, 2),
+            0x480 => (ANALOG_INPUTS, 9, 2),
+            0x300 => (ANALOG_OUTPUTS, 1, 2),
+            0x400 => (ANALOG_OUTPUTS, 5, 2),
+            0x500 => (ANALOG_OUTPUTS, 9, 2),
+            _ => return None,
+        };
+        Some(Self {
+            index,
+            first_subindex,
+            bytes,
+        })
+    }
+}
+
+impl IoNode {
+    fn new(node_id: NodeId, time: Instant) -> Self {
+        Self {
+            node_id,
+            digital_inputs: BTreeMap::new(),
+            digital_outputs: BTreeMap::new(),
+            analog_inputs: BTreeMap::new(),
+            analog_outputs: BTreeMap::new(),
+            updated: time,
+        }
+    }
+
+    fn set(&mut self, index: u16, subindex: u8, raw: u64) {
+        let [b0, b1, ..] = raw.to_le_bytes();
+        match index {
+            DIGITAL_INPUTS => {
+                self.digital_inputs.insert(subindex, b0);
+            }
+            DIGITAL_OUTPUTS => {
+                self.digital_outputs.insert(subindex, b0);
+            }
+            ANALOG_INPUTS => {
+                self.analog_inputs
+                    .insert(subindex, i16::from_le_bytes([b0, b1]));
+            }
+            ANALOG_OUTPUTS => {
+                self.analog_outputs
+                    .insert(subindex, i16::from_le_bytes([b0, b1]));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_io_object(index: u16, subindex: u8) -> bool {
+    subindex != 0
+        && matches!(
+            index,
+            DIGITAL_INPUTS | DIGITAL_OUTPUTS | ANALOG_INPUTS | ANALOG_OUTPUTS
+        )
+}
+
+impl IoModules {
+    /// Takes I/O values from PDOs.
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        match &msg.additional {
+            RxMessageAdditional::Pdo(pdo) => {
+                for v in &pdo.values {
+                    self.update(
+                        pdo.key.node_id,
+                        v.object.index,
+                        v.object.subindex,
+                        v.raw,
+                        msg.get_timestamp(),
+                    );
+                }
+            }
+            RxMessageAdditional::None if msg.msg.parsed_type == RxMessageType::Pdo => {
+                self.decode_default(msg);
+            }
+            _ => {}
+        }
+    }
+
+    /// Learns the device type and takes I/O values from successful SDO transfers.
+    pub fn push_transfer(&mut self, transfer: &SdoTransfer) {
+        if transfer.result != SdoResult::Success || !(1..=4).contains(&transfer.data.len()) {
+            return;
+        }
+
+        let mut bytes = [0u8; 8];
+        bytes[..transfer.data.len()].copy_from_slice(&transfer.data);
+        let raw = u64::from_le_bytes(bytes);
+
+        if transfer.index == DEVICE_TYPE
+            && transfer.subindex == 0
+            && transfer.direction == SdoDirection::Upload
+            && raw & 0xFFFF == u64::from(PROFILE_IO)
+        {
+            self.default_layout.insert(transfer.node_id);
+            return;
+        }
+
+        let time = transfer.finished.unwrap_or(transfer.started);
+        self.update(
+            transfer.node_id,
+            transfer.index,
+            transfer.subindex,
+            raw,
+            time,
+        );
+    }
+
+    fn decode_default(&mut self, msg: &MessageCached) {
+        let Some(node_id) = msg.msg.parsed_node_id else {
+            return;
+        };
+        if !self.default_layout.contains(&node_id) {
+            return;
+        }
+        let Some(layout) =
+            DefaultLayout::from_function_code(msg.msg.msg.cob_id & FUNCTION_CODE_MASK)
+        else {
+            return;
+        };
+
+        let data = &msg.msg.msg.data[..msg.msg.msg.dlc.min(8)];
+        for (subindex, chunk) in (layout.first_subindex..).zip(data.chunks_exact(layout.bytes)) {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            self.update(
+                node_id,
+                layout.index,
+                subindex,
+                u64::from_le_bytes(bytes),
+                msg.get_timestamp(),
+            );
+        }
+    }
+
+    fn update(&mut self, node_id: NodeId, index: u16, subindex: u8, raw: u64, time: Instant) {
+        if !is_io_object(index, subindex) {
+            return;
+        }
+
+        let node = self
+            .nodes
+            .entry(node_id)
+            .or_insert_with(|| IoNode::new(node_id, time));
+        node.set(index, subindex, raw);
+        node.updated = time;
+    }
+
+    /// Decodes PDOs of the node with the default `CiA 401` layout.
+    pub fn use_default_layout(&mut self, node_id: NodeId) {
+        self.default_layout.insert(node_id);
+    }
+
+    pub fn uses_default_layout(&self, node_id: NodeId) -> bool {
+        self.default_layout.contains(&node_id)
+    }
+
+    /// Nodes ordered by node ID.
+    pub fn iter(&self) -> impl Iterator<Item = &IoNode> {
+        self.nodes.values()
+    }
+
+    pub fn get(&self, node_id: NodeId) -> Option<&IoNode> {
+        self.nodes.get(&node_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Forgets collected values, nodes using the default layout are kept.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IoModules;
+    use crate::message_cached::test_message;
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_default_layout() {
+        let mut io = IoModules::default();
+        io.push_data(&test_message(0x183, &[0x81, 0x02], Instant::now()));
+        assert!(io.is_empty());
+
+        io.use_default_layout(3);
+        io.push_data(&test_message(0x183, &[0x81, 0x02], Instant::now()));
+        io.push_data(&test_message(0x203, &[0xF0], Instant::now()));
+        io.push_data(&test_message(
+            0x383,
+            &[0x10, 0x00, 0xFF, 0xFF, 0x00, 0x80],
+            Instant::now(),
+        ));
+        io.push_data(&test_message(0x185, &[0xFF], Instant::now()));
+
+        let node = io.get(3).unwrap();
+        assert_eq!(io.len(), 1);
+        assert_eq!(
+            node.digital_inputs.iter().collect::<Vec<_>>(),
+            [(&1, &0x81), (&2, &0x02)]
+        );
+        assert_eq!(node.digital_outputs.get(&1), Some(&0xF0));
+        assert_eq!(
+            node.analog_inputs.iter().collect::<Vec<_>>(),
+            [(&5, &16), (&6, &-1), (&7, &i16::MIN)]
+        );
+    }
+}
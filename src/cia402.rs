@@ -0,0 +1,445 @@
+use crate::{
+    message_cached::{MessageCached, RxMessageAdditional},
+    sdo_session::{SdoResult, SdoTransfer},
+};
+use core::fmt;
+use oze_canopen::canopen::NodeId;
+use std::collections::{BTreeMap, VecDeque};
+use tokio::time::Instant;
+
+pub const CONTROLWORD: u16 = 0x6040;
+pub const STATUSWORD: u16 = 0x6041;
+pub const MODES_OF_OPERATION: u16 = 0x6060;
+pub const MODES_OF_OPERATION_DISPLAY: u16 = 0x6061;
+pub const POSITION_ACTUAL: u16 = 0x6064;
+pub const VELOCITY_ACTUAL: u16 = 0x606C;
+pub const TARGET_POSITION: u16 = 0x607A;
+pub const TARGET_VELOCITY: u16 = 0x60FF;
+
+/// Maximum number of state transitions kept per drive.
+const MAX_TRANSITIONS: usize = 64;
+const CONTROLWORD_FAULT_RESET: u16 = 0x0080;
+
+/// State of the `CiA 402` power state machine, decoded from the statusword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveState {
+    NotReadyToSwitchOn,
+    SwitchOnDisabled,
+    ReadyToSwitchOn,
+    SwitchedOn,
+    OperationEnabled,
+    QuickStopActive,
+    FaultReactionActive,
+    Fault,
+}
+
+/// Device control command encoded in the controlword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveCommand {
+    Shutdown,
+    /// Switch on and disable operation share the same bit pattern.
+    SwitchOn,
+    DisableVoltage,
+    QuickStop,
+    EnableOperation,
+    FaultReset,
+}
+
+/// Mode of operation (0x6060/0x6061).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperationMode(pub i8);
+
+/// Change of the power state machine state.
+#[derive(Debug, Clone, Copy)]
+pub struct DriveTransition {
+    pub time: Instant,
+    pub from: Option<DriveState>,
+    pub to: Option<DriveState>,
+}
+
+/// Last known drive profile objects of one node.
+#[derive(Debug, Clone)]
+pub struct Drive {
+    pub node_id: NodeId,
+    pub statusword: Option<u16>,
+    pub controlword: Option<u16>,
+    pub mode: Option<OperationMode>,
+    pub mode_display: Option<OperationMode>,
+    pub position_actual: Option<i32>,
+    pub target_position: Option<i32>,
+    pub velocity_actual: Option<i32>,
+    pub target_velocity: Option<i32>,
+    pub updated: Instant,
+    /// State transitions, most recent first.
+    pub transitions: VecDeque<DriveTransition>,
+}
+
+/// Collects drive profile objects transferred by PDO or SDO and follows the state machine of each drive.
+#[derive(Debug, Default)]
+pub struct Drives {
+    drives: BTreeMap<NodeId, Drive>,
+}
+
+impl DriveState {
+    pub fn from_statusword(sw: u16) -> Option<Self> {
+        if sw & 0x4F == 0x00 {
+            Some(DriveState::NotReadyToSwitchOn)
+        } else if sw & 0x4F == 0x40 {
+            Some(DriveState::SwitchOnDisabled)
+        } else if sw & 0x6F == 0x21 {
+            Some(DriveState::ReadyToSwitchOn)
+        } else if sw & 0x6F == 0x23 {
+            Some(DriveState::SwitchedOn)
+        } else if sw & 0x6F == 0x27 {
+            Some(DriveState::OperationEnabled)
+        } else if sw & 0x6F == 0x07 {
+            Some(DriveState::QuickStopActive)
+        } else if sw & 0x4F == 0x0F {
+            Some(DriveState::FaultReactionActive)
+        } else if sw & 0x4F == 0x08 {
+            Some(DriveState::Fault)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` for the fault and fault reaction states.
+    pub fn is_fault(self) -> bool {
+        matches!(self, DriveState::Fault | DriveState::FaultReactionActive)
+    }
+}
+
+impl fmt::Display for DriveState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            DriveState::NotReadyToSwitchOn => "Not ready to switch on",
+            DriveState::SwitchOnDisabled => "Switch on disabled",
+            DriveState::ReadyToSwitchOn => "Ready to switch on",
+            DriveState::SwitchedOn => "Switched on",
+            DriveState::OperationEnabled => "Operation enabled",
+            DriveState::QuickStopActive => "Quick stop active",
+            DriveState::FaultReactionActive => "Fault reaction active",
+            DriveState::Fault => "Fault",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl DriveCommand {
+    pub fn from_controlword(cw: u16) -> Option<Self> {
+        if cw & CONTROLWORD_FAULT_RESET != 0 {
+            Some(DriveCommand::FaultReset)
+        } else if cw & 0x02 == 0 {
+            Some(DriveCommand::DisableVoltage)
+        } else if cw & 0x06 == 0x02 {
+            Some(DriveCommand::QuickStop)
+        } else if cw & 0x07 == 0x06 {
+            Some(DriveCommand::Shutdown)
+        } else if cw & 0x0F == 0x07 {
+            Some(DriveCommand::SwitchOn)
+        } else if cw & 0x0F == 0x0F {
+            Some(DriveCommand::EnableOperation)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for DriveCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            DriveCommand::Shutdown => "Shutdown",
+            DriveCommand::SwitchOn => "Switch on",
+            DriveCommand::DisableVoltage => "Disable voltage",
+            DriveCommand::QuickStop => "Quick stop",
+            DriveCommand::EnableOperation => "Enable operation",
+            DriveCommand::FaultReset => "Fault reset",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl fmt::Display for OperationMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self.0 {
+            0 => "No mode",
+            1 => "Profile position",
+            2 => "Velocity",
+            3 => "Profile velocity",
+            4 => "Profile torque",
+            6 => "Homing",
+            7 => "Interpolated position",
+            8 => "Cyclic sync position",
+            9 => "Cyclic sync velocity",
+            10 => "Cyclic sync torque",
+            m if m < 0 => return write!(f, "Manufacturer mode {m}"),
+            m => return write!(f, "Reserved mode {m}"),
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Describes the value of a drive profile object, used in tooltips.
+pub fn describe(index: u16, raw: u64) -> Option<String> {
+    match index {
+        STATUSWORD => {
+            let state = DriveState::from_statusword(low_u16(raw))
+                .map_or_else(|| "unknown state".to_owned(), |s| s.to_string());
+            Some(format!("statusword: {state}"))
+        }
+        CONTROLWORD => {
+            let command = DriveCommand::from_controlword(low_u16(raw))
+                .map_or_else(|| "unknown command".to_owned(), |c| c.to_string());
+            Some(format!("controlword: {command}"))
+        }
+        MODES_OF_OPERATION => Some(format!("mode: {}", OperationMode(low_i8(raw)))),
+        MODES_OF_OPERATION_DISPLAY => Some(format!("mode display: {}", OperationMode(low_i8(raw)))),
+        POSITION_ACTUAL => Some(format!("position actual: {}", low_i32(raw))),
+        TARGET_POSITION => Some(format!("target position: {}", low_i32(raw))),
+        VELOCITY_ACTUAL => Some(format!("velocity actual: {}", low_i32(raw))),
+        TARGET_VELOCITY => Some(format!("target velocity: {}", low_i32(raw))),
+        _ => None,
+    }
+}
+
+fn low_u16(raw: u64) -> u16 {
+    let [b0, b1, ..] = raw.to_le_bytes();
+    u16::from_le_bytes([b0, b1])
+}
+
+fn low_i32(raw: u64) -> i32 {
+    let [b0, b1, b2, b3, ..] = raw.to_le_bytes();
+    i32::from_le_bytes([b0, b1, b2, b3])
+}
+
+fn low_i8(raw: u64) -> i8 {
+    i8::from_le_bytes([raw.to_le_bytes()[0]])
+}
+
+impl Drive {
+    fn new(node_id: NodeId, time: Instant) -> Self {
+        Self {
+            node_id,
+            statusword: None,
+            controlword: None,
+            mode: None,
+            mode_display: None,
+            position_actual: None,
+            target_position: None,
+            velocity_actual: None,
+            target_velocity: None,
+            updated: time,
+            transitions: VecDeque::new(),
+        }
+    }
+
+    /// Current state of the power state machine.
+    pub fn state(&self) -> Option<DriveState> {
+        self.statusword.and_then(DriveState::from_statusword)
+    }
+
+    /// Command of the last seen controlword.
+    pub fn command(&self) -> Option<DriveCommand> {
+        self.controlword.and_then(DriveCommand::from_controlword)
+    }
+
+    fn set_statusword(&mut self, sw: u16, time: Instant) {
+        let from = self.state();
+        let to = DriveState::from_statusword(sw);
+        self.statusword = Some(sw);
+        if from != to {
+            self.transitions
+                .push_front(DriveTransition { time, from, to });
+            self.transitions.truncate(MAX_TRANSITIONS);
+        }
+    }
+
+    fn update(&mut self, index: u16, raw: u64, time: Instant) {
+        match index {
+            STATUSWORD => self.set_statusword(low_u16(raw), time),
+            CONTROLWORD => self.controlword = Some(low_u16(raw)),
+            MODES_OF_OPERATION => self.mode = Some(OperationMode(low_i8(raw))),
+            MODES_OF_OPERATION_DISPLAY => self.mode_display = Some(OperationMode(low_i8(raw))),
+            POSITION_ACTUAL => self.position_actual = Some(low_i32(raw)),
+            TARGET_POSITION => self.target_position = Some(low_i32(raw)),
+            VELOCITY_ACTUAL => self.velocity_actual = Some(low_i32(raw)),
+            TARGET_VELOCITY => self.target_velocity = Some(low_i32(raw)),
+            _ => return,
+        }
+        self.updated = time;
+    }
+}
+
+impl fmt::Display for Drive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "node {:3} ", self.node_id)?;
+        match self.state() {
+            Some(state) => write!(f, "{state}")?,
+            None => write!(f, "state unknown")?,
+        }
+        if let Some(mode) = self.mode_display.or(self.mode) {
+            write!(f, ", {mode}")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_drive_object(index: u16) -> bool {
+    matches!(
+        index,
+        CONTROLWORD
+            | STATUSWORD
+            | MODES_OF_OPERATION
+            | MODES_OF_OPERATION_DISPLAY
+            | POSITION_ACTUAL
+            | VELOCITY_ACTUAL
+            | TARGET_POSITION
+            | TARGET_VELOCITY
+    )
+}
+
+impl Drives {
+    /// Takes drive profile objects from decoded PDOs.
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        let RxMessageAdditional::Pdo(pdo) = &msg.additional else {
+            return;
+        };
+
+        for value in &pdo.values {
+            if value.object.subindex == 0 {
+                self.update(
+                    pdo.key.node_id,
+                    value.object.index,
+                    value.raw,
+                    msg.get_timestamp(),
+                );
+            }
+        }
+    }
+
+    /// Takes drive profile objects from successful SDO transfers.
+    pub fn push_transfer(&mut self, transfer: &SdoTransfer) {
+        if transfer.result != SdoResult::Success
+            || transfer.subindex != 0
+            || !(1..=8).contains(&transfer.data.len())
+        {
+            return;
+        }
+
+        let mut bytes = [0u8; 8];
+        bytes[..transfer.data.len()].copy_from_slice(&transfer.data);
+        let time = transfer.finished.unwrap_or(transfer.started);
+        self.update(
+            transfer.node_id,
+            transfer.index,
+            u64::from_le_bytes(bytes),
+            time,
+        );
+    }
+
+    fn update(&mut self, node_id: NodeId, index: u16, raw: u64, time: Instant) {
+        if !is_drive_object(index) {
+            return;
+        }
+
+        self.drives
+            .entry(node_id)
+            .or_insert_with(|| Drive::new(node_id, time))
+            .update(index, raw, time);
+    }
+
+    /// Drives ordered by node ID.
+    pub fn iter(&self) -> impl Iterator<Item = &Drive> {
+        self.drives.values()
+    }
+
+    pub fn get(&self, node_id: NodeId) -> Option<&Drive> {
+        self.drives.get(&node_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.drives.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.drives.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.drives.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DriveCommand, DriveState, Drives, OperationMode, STATUSWORD};
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_statusword() {
+        assert_eq!(
+            DriveState::from_statusword(0x0250),
+            Some(DriveState::SwitchOnDisabled)
+        );
+        assert_eq!(
+            DriveState::from_statusword(0x0231),
+            Some(DriveState::ReadyToSwitchOn)
+        );
+        assert_eq!(
+            DriveState::from_statusword(0x0233),
+            Some(DriveState::SwitchedOn)
+        );
+        assert_eq!(
+            DriveState::from_statusword(0x1237),
+            Some(DriveState::OperationEnabled)
+        );
+        assert_eq!(
+            DriveState::from_statusword(0x0217),
+            Some(DriveState::QuickStopActive)
+        );
+        assert_eq!(DriveState::from_statusword(0x0218), Some(DriveState::Fault));
+        assert_eq!(
+            DriveState::from_statusword(0x021F),
+            Some(DriveState::FaultReactionActive)
+        );
+
+        assert_eq!(
+            DriveCommand::from_controlword(0x0006),
+            Some(DriveCommand::Shutdown)
+        );
+        assert_eq!(
+            DriveCommand::from_controlword(0x000F),
+            Some(DriveCommand::EnableOperation)
+        );
+        assert_eq!(
+            DriveCommand::from_controlword(0x0080),
+            Some(DriveCommand::FaultReset)
+        );
+        assert_eq!(OperationMode(8).to_string(), "Cyclic sync position");
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mut drives = Drives::default();
+        let now = Instant::now();
+        for sw in [0x0250, 0x0231, 0x0231, 0x0233, 0x0237, 0x0218] {
+            drives.update(5, STATUSWORD, sw, now);
+        }
+        drives.update(5, 0x1000, 0x1234, now);
+
+        let drive = drives.get(5).unwrap();
+        assert_eq!(drives.len(), 1);
+        assert_eq!(drive.state(), Some(DriveState::Fault));
+        let path: Vec<_> = drive.transitions.iter().rev().map(|t| t.to).collect();
+        assert_eq!(
+            path,
+            [
+                Some(DriveState::SwitchOnDisabled),
+                Some(DriveState::ReadyToSwitchOn),
+                Some(DriveState::SwitchedOn),
+                Some(DriveState::OperationEnabled),
+                Some(DriveState::Fault),
+            ]
+        );
+    }
+}
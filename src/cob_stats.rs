@@ -0,0 +1,214 @@
+use crate::message_cached::MessageCached;
+use std::{cmp::Ordering, collections::BTreeMap};
+use tokio::time::Instant;
+
+/// Statistics of one COB ID, updated in place by every message.
+#[derive(Debug, Clone)]
+pub struct CobStats {
+    /// Sort key, see [`MessageCached::frame_id`].
+    pub id: (bool, u32),
+    pub last: MessageCached,
+    /// Data of the message before the last one, to highlight changed bytes.
+    pub previous_data: Option<[u8; 8]>,
+    pub count: u64,
+    pub min_period: Option<f64>,
+    pub max_period: Option<f64>,
+    periods: u64,
+    mean_period: f64,
+    /// Sum of squared differences from the mean (Welford's algorithm).
+    m2: f64,
+}
+
+impl CobStats {
+    fn new(id: (bool, u32), msg: &MessageCached) -> Self {
+        Self {
+            id,
+            last: msg.clone(),
+            previous_data: None,
+            count: 1,
+            min_period: None,
+            max_period: None,
+            periods: 0,
+            mean_period: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn push(&mut self, msg: &MessageCached) {
+        let period = msg
+            .get_timestamp()
+            .saturating_duration_since(self.last.get_timestamp())
+            .as_secs_f64();
+        self.count += 1;
+        self.periods += 1;
+        let delta = period - self.mean_period;
+        self.mean_period += delta / self.periods as f64;
+        self.m2 += delta * (period - self.mean_period);
+        self.min_period = Some(self.min_period.map_or(period, |p| p.min(period)));
+        self.max_period = Some(self.max_period.map_or(period, |p| p.max(period)));
+
+        self.previous_data = Some(self.last.msg.msg.data);
+        self.last = msg.clone();
+    }
+
+    /// Mean time between messages in seconds.
+    pub fn mean_period(&self) -> Option<f64> {
+        (self.periods > 0).then_some(self.mean_period)
+    }
+
+    /// Standard deviation of the period in seconds.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn jitter(&self) -> Option<f64> {
+        (self.periods > 1).then(|| (self.m2 / (self.periods - 1) as f64).sqrt())
+    }
+
+    /// Whether the byte differs from the previous message.
+    pub fn changed(&self, byte: usize) -> bool {
+        self.previous_data
+            .is_some_and(|previous| previous[byte] != self.last.msg.msg.data[byte])
+    }
+
+    pub fn last_timestamp(&self) -> Instant {
+        self.last.get_timestamp()
+    }
+}
+
+/// Column the fixed trace is sorted by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortColumn {
+    #[default]
+    CobId,
+    Type,
+    NodeId,
+    Count,
+    Data,
+    MeanPeriod,
+    MinPeriod,
+    MaxPeriod,
+    Jitter,
+    Timestamp,
+    Info,
+}
+
+fn cmp_periods(a: Option<f64>, b: Option<f64>) -> Ordering {
+    a.unwrap_or(f64::INFINITY)
+        .total_cmp(&b.unwrap_or(f64::INFINITY))
+}
+
+impl SortColumn {
+    fn cmp(self, a: &CobStats, b: &CobStats) -> Ordering {
+        match self {
+            SortColumn::CobId => a.id.cmp(&b.id),
+            SortColumn::Type => a
+                .last
+                .msg
+                .parsed_type
+                .to_string()
+                .cmp(b.last.msg.parsed_type.to_string()),
+            SortColumn::NodeId => a.last.msg.parsed_node_id.cmp(&b.last.msg.parsed_node_id),
+            SortColumn::Count => a.count.cmp(&b.count),
+            SortColumn::Data => a.last.hex_str.cmp(&b.last.hex_str),
+            SortColumn::MeanPeriod => cmp_periods(a.mean_period(), b.mean_period()),
+            SortColumn::MinPeriod => cmp_periods(a.min_period, b.min_period),
+            SortColumn::MaxPeriod => cmp_periods(a.max_period, b.max_period),
+            SortColumn::Jitter => cmp_periods(a.jitter(), b.jitter()),
+            SortColumn::Timestamp => a.last_timestamp().cmp(&b.last_timestamp()),
+            SortColumn::Info => a.last.info_str.cmp(&b.last.info_str),
+        }
+        .then(a.id.cmp(&b.id))
+    }
+}
+
+/// Per COB ID statistics for the fixed trace view.
+#[derive(Debug, Default)]
+pub struct CobStatistics {
+    cobs: BTreeMap<(bool, u32), CobStats>,
+}
+
+impl CobStatistics {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        let id = msg.frame_id();
+        self.cobs
+            .entry(id)
+            .and_modify(|s| s.push(msg))
+            .or_insert_with(|| CobStats::new(id, msg));
+    }
+
+    /// Returns the statistics sorted by the column.
+    pub fn sorted(&self, column: SortColumn, descending: bool) -> Vec<&CobStats> {
+        let mut cobs: Vec<_> = self.cobs.values().collect();
+        cobs.sort_by(|a, b| {
+            let ordering = column.cmp(a, b);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        cobs
+    }
+
+    pub fn len(&self) -> usize {
+        self.cobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cobs.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cobs.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CobStatistics, SortColumn};
+    use crate::message_cached::test_message;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_cob_statistics() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut stats = CobStatistics::default();
+        stats.push_data(&test_message(0x181, &[0; 8], at(0)));
+        stats.push_data(&test_message(0x181, &[0; 8], at(10)));
+        stats.push_data(&test_message(0x181, &[0, 1, 0, 0, 0, 0, 0, 0], at(30)));
+        stats.push_data(&test_message(0x701, &[5, 0, 0, 0, 0, 0, 0, 0], at(40)));
+
+        let sorted = stats.sorted(SortColumn::Count, true);
+        assert_eq!(sorted.len(), 2);
+        let pdo = sorted[0];
+        assert_eq!(pdo.id, (false, 0x181));
+        assert_eq!(pdo.count, 3);
+        assert!((pdo.mean_period().unwrap() - 0.015).abs() < 1e-9);
+        assert!((pdo.min_period.unwrap() - 0.010).abs() < 1e-9);
+        assert!((pdo.max_period.unwrap() - 0.020).abs() < 1e-9);
+        assert!((pdo.jitter().unwrap() - 0.005 * 2f64.sqrt()).abs() < 1e-9);
+        assert!(!pdo.changed(0));
+        assert!(pdo.changed(1));
+
+        let heartbeat = sorted[1];
+        assert_eq!(heartbeat.mean_period(), None);
+        assert_eq!(stats.sorted(SortColumn::CobId, false)[0].id, (false, 0x181));
+    }
+
+    #[test]
+    fn test_extended_apart() {
+        let mut stats = CobStatistics::default();
+        let standard = test_message(0x181, &[0; 8], Instant::now());
+        let mut extended = standard.clone();
+        extended.extended = true;
+        stats.push_data(&standard);
+        stats.push_data(&extended);
+        stats.push_data(&extended);
+
+        let sorted = stats.sorted(SortColumn::CobId, false);
+        assert_eq!(sorted.len(), 2);
+        assert_eq!((sorted[0].id, sorted[0].count), ((false, 0x181), 1));
+        assert_eq!((sorted[1].id, sorted[1].count), ((true, 0x181), 2));
+    }
+}
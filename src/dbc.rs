@@ -0,0 +1,446 @@
+use crate::message_cached::{MessageCached, RxMessageAdditional};
+use core::fmt;
+use regex::Regex;
+use std::{collections::HashMap, fs, path::Path, sync::OnceLock};
+
+/// Set in the DBC message ID for 29-bit identifiers.
+const EXTENDED_ID_FLAG: u32 = 0x8000_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Intel, `@1` in DBC.
+    LittleEndian,
+    /// Motorola, `@0` in DBC, start bit is the most significant bit.
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplex {
+    None,
+    /// Signal selecting which multiplexed signals are present (`M`).
+    Multiplexor,
+    /// Signal present only when the multiplexor has this value (`m3`).
+    Multiplexed(u64),
+}
+
+/// Signal definition (`SG_`).
+#[derive(Debug, Clone)]
+pub struct DbcSignal {
+    pub name: String,
+    pub start: u16,
+    pub len: u16,
+    pub order: ByteOrder,
+    pub signed: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub unit: String,
+    pub mux: Multiplex,
+    /// Value descriptions from `VAL_`.
+    pub values: HashMap<i64, String>,
+}
+
+/// Message definition (`BO_`) with its signals.
+#[derive(Debug, Clone)]
+pub struct DbcMessage {
+    pub id: u32,
+    pub extended: bool,
+    pub name: String,
+    pub dlc: u8,
+    pub signals: Vec<DbcSignal>,
+}
+
+/// Decoded value of one signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbcValue {
+    pub name: String,
+    pub raw: i64,
+    pub value: f64,
+    pub unit: String,
+    /// Description of the raw value from the value table.
+    pub label: Option<String>,
+}
+
+/// Frame decoded with a DBC message definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbcDecoded {
+    pub message: String,
+    pub signals: Vec<DbcValue>,
+}
+
+/// Message definitions loaded from one or more DBC files.
+///
+/// Messages with extended identifiers only match 29-bit frames, which are received in the J1939 mode.
+#[derive(Debug, Default, Clone)]
+pub struct DbcDatabase {
+    messages: HashMap<u32, DbcMessage>,
+}
+
+fn message_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^BO_\s+(\d+)\s+(\w+)\s*:\s*(\d+)").unwrap())
+}
+
+fn signal_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r#"^SG_\s+(\w+)\s*(M|m\d+M?)?\s*:\s*(\d+)\|(\d+)@([01])([+-])\s*\(([^,]+),([^)]+)\)\s*\[[^\]]*\]\s*"([^"]*)""#,
+        )
+        .unwrap()
+    })
+}
+
+fn value_table_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?m)^\s*VAL_\s+(\d+)\s+(\w+)\s+([^;]*);").unwrap())
+}
+
+fn value_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(-?\d+)\s+"([^"]*)""#).unwrap())
+}
+
+impl DbcSignal {
+    fn parse(line: &str) -> Option<Self> {
+        let caps = signal_re().captures(line)?;
+        let mux = match caps.get(2).map(|m| m.as_str()) {
+            None => Multiplex::None,
+            Some("M") => Multiplex::Multiplexor,
+            // extended multiplexing (m1M) is treated as a plain multiplexed signal
+            Some(m) => Multiplex::Multiplexed(m.trim_matches(['m', 'M']).parse().ok()?),
+        };
+
+        Some(Self {
+            name: caps[1].to_owned(),
+            start: caps[3].parse().ok()?,
+            len: caps[4].parse().ok()?,
+            order: if &caps[5] == "1" {
+                ByteOrder::LittleEndian
+            } else {
+                ByteOrder::BigEndian
+            },
+            signed: &caps[6] == "-",
+            factor: caps[7].trim().parse().ok()?,
+            offset: caps[8].trim().parse().ok()?,
+            unit: caps[9].to_owned(),
+            mux,
+            values: HashMap::new(),
+        })
+    }
+
+    /// Extracts the raw value, `None` if the signal does not fit into the data.
+    fn extract(&self, data: &[u8]) -> Option<i64> {
+        let len = u32::from(self.len);
+        if len == 0 || len > 64 {
+            return None;
+        }
+
+        let raw = match self.order {
+            ByteOrder::LittleEndian => {
+                let end = usize::from(self.start) + usize::from(self.len);
+                if end > data.len() * 8 {
+                    return None;
+                }
+                let mut bytes = [0u8; 8];
+                bytes[..data.len()].copy_from_slice(data);
+                let value = u64::from_le_bytes(bytes) >> self.start;
+                if len == 64 {
+                    value
+                } else {
+                    value & ((1u64 << len) - 1)
+                }
+            }
+            ByteOrder::BigEndian => {
+                let mut value = 0u64;
+                let mut bit = usize::from(self.start);
+                for _ in 0..len {
+                    let byte = data.get(bit / 8)?;
+                    value = (value << 1) | u64::from((byte >> (bit % 8)) & 1);
+                    bit = if bit % 8 == 0 { bit + 15 } else { bit - 1 };
+                }
+                value
+            }
+        };
+
+        let raw = i64::from_le_bytes(raw.to_le_bytes());
+        if self.signed && len < 64 {
+            // sign extends from the signal width
+            let shift = 64 - len;
+            Some((raw << shift) >> shift)
+        } else {
+            Some(raw)
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn decode(&self, data: &[u8]) -> Option<DbcValue> {
+        let raw = self.extract(data)?;
+        Some(DbcValue {
+            name: self.name.clone(),
+            raw,
+            value: raw as f64 * self.factor + self.offset,
+            unit: self.unit.clone(),
+            label: self.values.get(&raw).cloned(),
+        })
+    }
+}
+
+impl DbcMessage {
+    /// Decodes all signals present in the data, multiplexed signals only for the active multiplexor value.
+    pub fn decode(&self, data: &[u8]) -> DbcDecoded {
+        let mux = self
+            .signals
+            .iter()
+            .find(|s| s.mux == Multiplex::Multiplexor)
+            .and_then(|s| s.extract(data));
+
+        let signals = self
+            .signals
+            .iter()
+            .filter(|s| match s.mux {
+                Multiplex::None | Multiplex::Multiplexor => true,
+                Multiplex::Multiplexed(value) => {
+                    mux.is_some_and(|m| u64::from_le_bytes(m.to_le_bytes()) == value)
+                }
+            })
+            .filter_map(|s| s.decode(data))
+            .collect();
+
+        DbcDecoded {
+            message: self.name.clone(),
+            signals,
+        }
+    }
+}
+
+impl fmt::Display for DbcValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(label) = &self.label {
+            return write!(f, "{}={label}", self.name);
+        }
+
+        write!(f, "{}={}", self.name, self.value)?;
+        if !self.unit.is_empty() {
+            write!(f, " {}", self.unit)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DbcDecoded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for s in &self.signals {
+            write!(f, " {s}")?;
+        }
+        Ok(())
+    }
+}
+
+impl DbcDecoded {
+    pub fn get_tooltip(&self) -> String {
+        let mut lines = vec![self.message.clone()];
+        lines.extend(self.signals.iter().map(|s| {
+            let label = s.label.as_deref().unwrap_or_default();
+            format!(
+                "{} = {} {} {label} (raw {})",
+                s.name, s.value, s.unit, s.raw
+            )
+        }));
+        lines.join("\n")
+    }
+
+    /// Returns the physical value of the signal.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.signals
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| s.value)
+    }
+}
+
+impl DbcDatabase {
+    /// Parses DBC text, only messages, signals and value descriptions are used.
+    ///
+    /// # Errors
+    /// Returns a description with the line number of a malformed message or signal.
+    pub fn parse(&mut self, text: &str) -> Result<(), String> {
+        let mut current: Option<DbcMessage> = None;
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with("BO_ ") {
+                let caps = message_re()
+                    .captures(line)
+                    .ok_or_else(|| format!("line {}: invalid message", line_no + 1))?;
+                let raw_id: u32 = caps[1]
+                    .parse()
+                    .map_err(|e| format!("line {}: {e}", line_no + 1))?;
+                if let Some(msg) = current.take() {
+                    self.insert(msg);
+                }
+                current = Some(DbcMessage {
+                    id: raw_id & !EXTENDED_ID_FLAG,
+                    extended: raw_id & EXTENDED_ID_FLAG != 0,
+                    name: caps[2].to_owned(),
+                    dlc: caps[3].parse().unwrap_or(8),
+                    signals: Vec::new(),
+                });
+            } else if line.starts_with("SG_ ") {
+                let signal = DbcSignal::parse(line)
+                    .ok_or_else(|| format!("line {}: invalid signal", line_no + 1))?;
+                if let Some(msg) = &mut current {
+                    msg.signals.push(signal);
+                }
+            } else if !line.is_empty() {
+                if let Some(msg) = current.take() {
+                    self.insert(msg);
+                }
+            }
+        }
+        if let Some(msg) = current {
+            self.insert(msg);
+        }
+
+        self.parse_value_tables(text);
+        Ok(())
+    }
+
+    fn parse_value_tables(&mut self, text: &str) {
+        for caps in value_table_re().captures_iter(text) {
+            let Ok(raw_id) = caps[1].parse::<u32>() else {
+                continue;
+            };
+            let Some(signal) = self
+                .messages
+                .get_mut(&raw_id)
+                .and_then(|m| m.signals.iter_mut().find(|s| s.name == caps[2]))
+            else {
+                continue;
+            };
+
+            for value in value_re().captures_iter(&caps[3]) {
+                if let Ok(raw) = value[1].parse() {
+                    signal.values.insert(raw, value[2].to_owned());
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, msg: DbcMessage) {
+        let key = if msg.extended {
+            msg.id | EXTENDED_ID_FLAG
+        } else {
+            msg.id
+        };
+        self.messages.insert(key, msg);
+    }
+
+    /// Loads a DBC file, messages already loaded from other files with the same ID are replaced.
+    ///
+    /// # Errors
+    /// Returns a description if the file can't be read or parsed.
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let text = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        // DBC files are often saved in a legacy code page
+        self.parse(&String::from_utf8_lossy(&text))
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn get(&self, id: u32) -> Option<&DbcMessage> {
+        self.messages.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Decodes frames matching a DBC message which no other decoder recognized, the J1939
+    /// decoding of 29-bit frames is only kept when no message matches.
+    pub fn process(&self, msg: &mut MessageCached) {
+        if !matches!(
+            msg.additional,
+            RxMessageAdditional::None | RxMessageAdditional::J1939(_)
+        ) {
+            return;
+        }
+        let key = if msg.extended {
+            msg.can_id | EXTENDED_ID_FLAG
+        } else {
+            msg.can_id
+        };
+        let Some(dbc) = self.messages.get(&key) else {
+            return;
+        };
+
+        let data = &msg.msg.msg.data[..msg.msg.msg.dlc.min(8)];
+        msg.additional = RxMessageAdditional::Dbc(dbc.decode(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DbcDatabase, DbcSignal};
+    use crate::{j1939::J1939Tracker, message_cached::RxMessageAdditional};
+
+    const DBC: &str = r#"
+VERSION ""
+
+BU_: ECU
+
+BO_ 1280 Engine: 8 ECU
+ SG_ Rpm : 0|16@1+ (0.25,0) [0|16383.75] "rpm" Vector__XXX
+ SG_ Temp : 16|8@1- (1,-40) [-40|87] "degC" Vector__XXX
+ SG_ Gear : 24|3@1+ (1,0) [0|7] "" Vector__XXX
+ SG_ Pressure : 39|12@0+ (0.1,0) [0|409.5] "bar" Vector__XXX
+
+BO_ 1281 Muxed: 8 ECU
+ SG_ Page M : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+ SG_ Voltage m1 : 8|16@1+ (0.01,0) [0|655.35] "V" Vector__XXX
+ SG_ Current m2 : 8|16@1- (0.1,0) [-3276.8|3276.7] "A" Vector__XXX
+
+BO_ 2364540158 EEC1: 8 Vector__XXX
+ SG_ EngineSpeed : 24|16@1+ (0.125,0) [0|8031.875] "rpm" Vector__XXX
+
+VAL_ 1280 Gear 0 "Neutral" 1 "Drive"
+ 2 "Reverse" ;
+"#;
+
+    #[test]
+    fn test_dbc_decoding() {
+        let mut db = DbcDatabase::default();
+        db.parse(DBC).unwrap();
+        assert_eq!(db.len(), 3);
+
+        // Pressure: motorola, msb at bit 39 (byte 4 bit 7), 12 bits -> 0xABC
+        let data = [0x70, 0x17, 0x7D, 0x02, 0xAB, 0xC0, 0, 0];
+        let decoded = db.get(1280).unwrap().decode(&data);
+        assert_eq!(
+            decoded.to_string(),
+            "Engine Rpm=1500 rpm Temp=85 degC Gear=Reverse Pressure=274.8 bar"
+        );
+
+        let muxed = db.get(1281).unwrap();
+        let page1 = muxed.decode(&[1, 0xE8, 0x03, 0, 0, 0, 0, 0]);
+        assert_eq!(page1.to_string(), "Muxed Page=1 Voltage=10 V");
+        let page2 = muxed.decode(&[2, 0x9C, 0xFF, 0, 0, 0, 0, 0]);
+        assert_eq!(page2.to_string(), "Muxed Page=2 Current=-10 A");
+        assert!(db.parse("BO_ 1 Broken\n SG_ x : 0|8").is_err());
+
+        // 29-bit frames, J1939 decoding is kept when no message matches
+        let mut j1939 = J1939Tracker::default();
+        let mut eec1 = j1939.message(0, 0x0CF0_04FE, [0, 0, 0, 0x40, 0x1F, 0, 0, 0], 8);
+        db.process(&mut eec1);
+        assert_eq!(eec1.additional.to_string(), "EEC1 EngineSpeed=1000 rpm");
+        let mut other = j1939.message(1, 0x0CF0_04FD, [0; 8], 8);
+        db.process(&mut other);
+        assert!(matches!(other.additional, RxMessageAdditional::J1939(_)));
+
+        let wide = DbcSignal::parse(r#"SG_ Wide : 0|63@1- (1,0) [0|0] """#).unwrap();
+        assert_eq!(wide.extract(&[0xFF; 8]), Some(-1));
+        assert_eq!(wide.extract(&[0, 0, 0, 0, 0, 0, 0, 0x40]), Some(-(1 << 62)));
+    }
+}
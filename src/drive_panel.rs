@@ -0,0 +1,123 @@
+use crate::{
+    cia402::{Drive, DriveState, Drives},
+    message_cached::MessageCached,
+    sdo_session::SdoTransfer,
+    theme::OZON_PINK,
+};
+use egui::{CollapsingHeader, RichText};
+use tokio::time::Instant;
+
+/// Shows `CiA 402` drives with their state machine state and its transitions.
+#[derive(Debug)]
+pub struct DrivePanel {
+    drives: Drives,
+    pub start_time: Instant,
+}
+
+impl Default for DrivePanel {
+    fn default() -> Self {
+        Self {
+            drives: Drives::default(),
+            start_time: Instant::now(),
+        }
+    }
+}
+
+fn show_value<T: ToString>(ui: &mut egui::Ui, name: &str, value: Option<T>) {
+    ui.label(name);
+    ui.label(value.map_or_else(|| "-".to_owned(), |v| v.to_string()));
+    ui.end_row();
+}
+
+impl DrivePanel {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        self.drives.push_data(msg);
+    }
+
+    pub fn push_transfer(&mut self, transfer: &SdoTransfer) {
+        self.drives.push_transfer(transfer);
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("drives={}", self.drives.len()));
+            if ui.button("🗑").on_hover_text("Clear drives").clicked() {
+                self.drives.clear();
+            }
+        });
+
+        egui::ScrollArea::vertical()
+            .id_salt("drives")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for drive in self.drives.iter() {
+                    let text = RichText::new(drive.to_string());
+                    let text = if drive.state().is_some_and(DriveState::is_fault) {
+                        text.color(OZON_PINK)
+                    } else {
+                        text
+                    };
+
+                    CollapsingHeader::new(text)
+                        .id_salt(("drive", drive.node_id))
+                        .show(ui, |ui| {
+                            ui.horizontal_top(|ui| {
+                                Self::show_objects(ui, drive);
+                                ui.separator();
+                                self.show_transitions(ui, drive);
+                            });
+                        });
+                }
+            });
+    }
+
+    fn show_objects(ui: &mut egui::Ui, drive: &Drive) {
+        egui::Grid::new(("drive_objects", drive.node_id))
+            .striped(true)
+            .show(ui, |ui| {
+                show_value(
+                    ui,
+                    "Statusword",
+                    drive.statusword.map(|sw| format!("{sw:04X}")),
+                );
+                show_value(
+                    ui,
+                    "Controlword",
+                    drive.controlword.map(|cw| match drive.command() {
+                        Some(command) => format!("{cw:04X} {command}"),
+                        None => format!("{cw:04X}"),
+                    }),
+                );
+                show_value(ui, "Mode of operation", drive.mode);
+                show_value(ui, "Mode display", drive.mode_display);
+                show_value(ui, "Target position", drive.target_position);
+                show_value(ui, "Position actual", drive.position_actual);
+                show_value(ui, "Target velocity", drive.target_velocity);
+                show_value(ui, "Velocity actual", drive.velocity_actual);
+            });
+    }
+
+    fn show_transitions(&self, ui: &mut egui::Ui, drive: &Drive) {
+        egui::Grid::new(("drive_transitions", drive.node_id))
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("     Timestamp");
+                ui.label("From");
+                ui.label("To");
+                ui.end_row();
+                for t in &drive.transitions {
+                    let time = t.time.duration_since(self.start_time).as_secs_f32();
+                    ui.label(format!("{time:.6}"));
+                    ui.label(t.from.map_or_else(|| "-".to_owned(), |s| s.to_string()));
+                    let to =
+                        RichText::new(t.to.map_or_else(|| "unknown".to_owned(), |s| s.to_string()));
+                    if t.to.is_some_and(DriveState::is_fault) {
+                        ui.label(to.color(OZON_PINK));
+                    } else {
+                        ui.label(to);
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
@@ -1,10 +1,21 @@
-use crate::message_cached::MessageCached;
+use crate::{
+    dbc::DbcDatabase, emcy::EmcyCatalogue, isotp::IsoTpTracker, j1939::J1939Tracker,
+    message_cached::MessageCached, pdo_mapping::PdoMappingTracker, sdo_block::SdoBlockTracker,
+    sync_time::SyncTracker,
+};
+use futures_util::StreamExt;
 use oze_canopen::{
-    canopen::{self, JoinHandles},
+    canopen::{self, JoinHandles, RxMessage},
     interface::{CanOpenInfo, CanOpenInterface, Connection},
 };
-use std::{collections::VecDeque, time::Duration};
-use tokio::{signal::ctrl_c, sync::watch, task::JoinHandle, time::sleep};
+use socketcan::{tokio::CanSocket, CanFrame, EmbeddedFrame, Id};
+use std::{collections::VecDeque, future, time::Duration};
+use tokio::{
+    signal::ctrl_c,
+    sync::watch,
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
 
 /// Enum representing different control commands that can be sent to the driver.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -21,14 +32,32 @@ pub struct State {
     pub bitrate: Option<u32>,
     pub data: VecDeque<MessageCached>,
     pub info: CanOpenInfo,
+    /// Error of the socket for extended frames, it is opened again after `TIMEOUT`.
+    pub extended_error: Option<String>,
     pub exit_signal: bool,
 }
 
+/// Protocol used to interpret the frames of the interface.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    CanOpen,
+    /// `CANopen` for standard frames and J1939 for extended frames.
+    J1939,
+}
+
 /// Struct representing control data including the command and connection details.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Control {
     pub command: ControlCommand,
     pub connection: Connection,
+    pub protocol: Protocol,
+}
+
+/// Frame received from the `CANopen` stack or from the socket for extended frames.
+enum Received {
+    Standard(Result<RxMessage, tokio::sync::broadcast::error::RecvError>),
+    Extended(Result<CanFrame, socketcan::Error>),
 }
 
 /// Struct representing the driver responsible for processing CAN messages and handling control commands.
@@ -37,12 +66,38 @@ pub struct Driver {
     receiver: watch::Receiver<Control>,
     state: State,
     pub co: CanOpenInterface,
+    /// Describes EMCY error codes, may be extended by a vendor table before the driver is started.
+    pub emcy_catalogue: EmcyCatalogue,
+    /// Decodes proprietary frames, loaded before the driver is started.
+    pub dbc: DbcDatabase,
+    /// Reassembles ISO-TP messages on the identifier pairs configured before the driver is started.
+    pub isotp: IsoTpTracker,
     control: Control,
     index: u64,
     handles: JoinHandles,
+    pdo_mapping: PdoMappingTracker,
+    sdo_block: SdoBlockTracker,
+    sync: SyncTracker,
+    j1939: J1939Tracker,
+    /// Socket receiving extended frames in J1939 mode, the `CANopen` stack drops them.
+    extended_socket: Option<(String, CanSocket)>,
+    /// The socket for extended frames failed, it is not opened again before this time.
+    extended_retry: Option<Instant>,
+}
+
+/// Waits for the next frame from the socket, forever if there is no socket.
+async fn next_extended(
+    socket: &mut Option<(String, CanSocket)>,
+) -> Option<Result<CanFrame, socketcan::Error>> {
+    match socket {
+        Some((_, socket)) => socket.next().await,
+        None => future::pending().await,
+    }
 }
 
 const MAX_MESSAGES_IN_STATE: usize = 512;
+/// Longest wait for a frame, also the delay before a failed socket is opened again.
+const TIMEOUT: Duration = Duration::from_millis(100);
 
 impl Driver {
     pub fn new(sender: watch::Sender<State>, receiver: watch::Receiver<Control>) -> Self {
@@ -54,21 +109,32 @@ impl Driver {
         let control = receiver.borrow().clone();
         Driver {
             co,
+            emcy_catalogue: EmcyCatalogue::default(),
+            dbc: DbcDatabase::default(),
+            isotp: IsoTpTracker::default(),
             sender,
             control,
             receiver,
             index: 0,
             state: State::default(),
             handles,
+            pdo_mapping: PdoMappingTracker::default(),
+            sdo_block: SdoBlockTracker::default(),
+            sync: SyncTracker::default(),
+            j1939: J1939Tracker::default(),
+            extended_socket: None,
+            extended_retry: None,
         }
     }
 
     /// Asynchronously processes incoming CAN messages and control commands.
     async fn process(&mut self) {
         // Wait for a message, timeout, or ctrl_c signal.
+        let extended_socket = &mut self.extended_socket;
         let rcv = tokio::select! {
-            rcv = self.co.rx.recv() => Some(rcv),
-            () = sleep(Duration::from_millis(100)) => None,
+            rcv = self.co.rx.recv() => Some(Received::Standard(rcv)),
+            Some(frame) = next_extended(extended_socket) => Some(Received::Extended(frame)),
+            () = sleep(TIMEOUT) => None,
             _ = ctrl_c() => {
                 self.control.command = ControlCommand::Kill;
                 return;
@@ -85,6 +151,7 @@ impl Driver {
                 .await
                 .clone_from(&self.control.connection);
         }
+        self.update_extended_socket();
 
         // Set information from the CANopen stack to the state.
         let info = self.co.info.lock().await.clone();
@@ -99,21 +166,116 @@ impl Driver {
         }
 
         // If no message has been received, return.
-        let Some(Ok(d)) = rcv else {
-            return;
+        let d = match rcv {
+            Some(Received::Standard(Ok(d))) => d,
+            Some(Received::Extended(Ok(frame))) => {
+                self.push_extended(&frame);
+                return;
+            }
+            Some(Received::Extended(Err(e))) => {
+                self.extended_failed(e.to_string());
+                return;
+            }
+            _ => return,
         };
 
         // Parse and cache the received message.
-        let d = MessageCached::new(self.index, d);
+        let mut d = MessageCached::new(self.index, d);
         self.index += 1;
 
-        // Add the new message to the state, ensuring the state does not exceed the max size.
+        // Block transfer segments can only be recognized by following the transfer.
+        self.sdo_block.process(&mut d);
+
+        // Learn PDO layouts from SDO traffic and decode PDOs with them.
+        self.pdo_mapping.process(&mut d);
+
+        // Detect lost SYNC messages by their counter.
+        self.sync.process(&mut d);
+
+        // Describe EMCY codes from the vendor table.
+        self.emcy_catalogue.process(&mut d);
+
+        // Reassemble ISO-TP messages and decode them as UDS.
+        self.isotp.process(&mut d);
+
+        // Decode remaining frames with the DBC definitions.
+        self.dbc.process(&mut d);
+
+        self.push_message(d);
+    }
+
+    /// Adds the new message to the state, ensuring the state does not exceed the max size.
+    fn push_message(&mut self, mut d: MessageCached) {
+        d.cache_info();
         while self.state.data.len() > MAX_MESSAGES_IN_STATE {
             self.state.data.pop_front();
         }
         self.state.data.push_back(d);
     }
 
+    /// Decodes an extended frame as J1939, standard frames are received by the `CANopen` stack.
+    fn push_extended(&mut self, frame: &CanFrame) {
+        let CanFrame::Data(frame) = frame else {
+            return;
+        };
+        let Id::Extended(id) = frame.id() else {
+            return;
+        };
+
+        let mut data = [0u8; 8];
+        let len = frame.data().len().min(8);
+        data[..len].copy_from_slice(&frame.data()[..len]);
+        let mut d = self
+            .j1939
+            .message(self.index, id.as_raw(), data, frame.dlc());
+        self.index += 1;
+        // J1939 DBC files take precedence over the generic J1939 decoding
+        self.dbc.process(&mut d);
+        self.push_message(d);
+    }
+
+    /// Closes the socket for extended frames, it is opened again after `TIMEOUT`.
+    fn extended_failed(&mut self, error: String) {
+        self.extended_socket = None;
+        self.extended_retry = Some(Instant::now() + TIMEOUT);
+        self.state.extended_error = Some(error);
+    }
+
+    /// Opens the socket for extended frames in J1939 mode and closes it otherwise.
+    fn update_extended_socket(&mut self) {
+        let can_name = &self.control.connection.can_name;
+        if self.control.protocol != Protocol::J1939 || can_name.is_empty() {
+            self.extended_socket = None;
+            self.extended_retry = None;
+            self.state.extended_error = None;
+            return;
+        }
+
+        if self
+            .extended_socket
+            .as_ref()
+            .is_some_and(|(name, _)| name == can_name)
+        {
+            return;
+        }
+        if self
+            .extended_retry
+            .is_some_and(|retry| Instant::now() < retry)
+        {
+            return;
+        }
+
+        self.j1939 = J1939Tracker::default();
+        match CanSocket::open(can_name) {
+            Ok(socket) => {
+                self.extended_socket = Some((can_name.clone(), socket));
+                self.extended_retry = None;
+                self.state.extended_error = None;
+            }
+            Err(e) => self.extended_failed(format!("{can_name}: {e}")),
+        }
+    }
+
     /// Asynchronously runs the driver, continuously processing messages and sending state updates.
     async fn run(&mut self) {
         self.state.data.reserve_exact(MAX_MESSAGES_IN_STATE);
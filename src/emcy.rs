@@ -0,0 +1,403 @@
+use crate::message_cached::{MessageCached, RxMessageAdditional};
+use bitflags::bitflags;
+use core::fmt;
+use std::{collections::HashMap, fs, path::Path};
+
+bitflags! {
+    /// Error register (object 0x1001) sent in every EMCY message.
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ErrorRegister: u8 {
+        const GENERIC = 0b0000_0001;
+        const CURRENT = 0b0000_0010;
+        const VOLTAGE = 0b0000_0100;
+        const TEMPERATURE = 0b0000_1000;
+        const COMMUNICATION = 0b0001_0000;
+        const DEVICE_PROFILE = 0b0010_0000;
+        const RESERVED = 0b0100_0000;
+        const MANUFACTURER = 0b1000_0000;
+    }
+}
+
+/// Decoded EMCY message: error code, error register and manufacturer specific data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmcyInfo {
+    pub code: u16,
+    pub error_register: ErrorRegister,
+    pub vendor_data: [u8; 5],
+    /// Description of the error code from the catalogue.
+    pub description: Option<String>,
+}
+
+/// Catalogue of emergency error codes: `CiA 301` and `CiA 402` codes extended by a vendor table.
+#[derive(Debug, Default, Clone)]
+pub struct EmcyCatalogue {
+    vendor: HashMap<u16, String>,
+}
+
+/// Error codes defined by `CiA 301`.
+const CIA301_CODES: &[(u16, &str)] = &[
+    (0x0000, "Error reset or no error"),
+    (0x1000, "Generic error"),
+    (0x2000, "Current"),
+    (0x2100, "Current, device input side"),
+    (0x2200, "Current inside the device"),
+    (0x2300, "Current, device output side"),
+    (0x3000, "Voltage"),
+    (0x3100, "Mains voltage"),
+    (0x3200, "Voltage inside the device"),
+    (0x3300, "Output voltage"),
+    (0x4000, "Temperature"),
+    (0x4100, "Ambient temperature"),
+    (0x4200, "Device temperature"),
+    (0x5000, "Device hardware"),
+    (0x6000, "Device software"),
+    (0x6100, "Internal software"),
+    (0x6200, "User software"),
+    (0x6300, "Data set"),
+    (0x7000, "Additional modules"),
+    (0x8000, "Monitoring"),
+    (0x8100, "Communication"),
+    (0x8110, "CAN overrun (objects lost)"),
+    (0x8120, "CAN in error passive mode"),
+    (0x8130, "Life guard error or heartbeat error"),
+    (0x8140, "Recovered from bus off"),
+    (0x8150, "CAN-ID collision"),
+    (0x8200, "Protocol error"),
+    (0x8210, "PDO not processed due to length error"),
+    (0x8220, "PDO length exceeded"),
+    (
+        0x8230,
+        "DAM MPDO not processed, destination object not available",
+    ),
+    (0x8240, "Unexpected SYNC data length"),
+    (0x8250, "RPDO timeout"),
+    (0x9000, "External error"),
+    (0xF000, "Additional functions"),
+    (0xFF00, "Device specific"),
+];
+
+/// Drive specific error codes defined by `CiA 402`.
+const CIA402_CODES: &[(u16, &str)] = &[
+    (0x2110, "Short circuit/earth leakage (input)"),
+    (0x2120, "Earth leakage (input)"),
+    (0x2121, "Earth leakage phase L1"),
+    (0x2122, "Earth leakage phase L2"),
+    (0x2123, "Earth leakage phase L3"),
+    (0x2130, "Short circuit (input)"),
+    (0x2131, "Short circuit phases L1-L2"),
+    (0x2132, "Short circuit phases L2-L3"),
+    (0x2133, "Short circuit phases L3-L1"),
+    (0x2211, "Internal current no. 1"),
+    (0x2212, "Internal current no. 2"),
+    (0x2213, "Over-current in ramp function"),
+    (0x2214, "Over-current in the sequence"),
+    (0x2220, "Continuous over-current (device internal)"),
+    (0x2221, "Continuous over-current no. 1"),
+    (0x2222, "Continuous over-current no. 2"),
+    (0x2230, "Short circuit/earth leakage (device internal)"),
+    (0x2240, "Earth leakage (device internal)"),
+    (0x2250, "Short circuit (device internal)"),
+    (0x2310, "Continuous over-current (device output side)"),
+    (0x2311, "Continuous over-current no. 1"),
+    (0x2312, "Continuous over-current no. 2"),
+    (0x2320, "Short circuit/earth leakage (motor-side)"),
+    (0x2330, "Earth leakage (motor-side)"),
+    (0x2331, "Earth leakage phase U"),
+    (0x2332, "Earth leakage phase V"),
+    (0x2333, "Earth leakage phase W"),
+    (0x2340, "Short circuit (motor-side)"),
+    (0x2341, "Short circuit phases U-V"),
+    (0x2342, "Short circuit phases V-W"),
+    (0x2343, "Short circuit phases W-U"),
+    (0x3110, "Mains over-voltage"),
+    (0x3111, "Mains over-voltage phase L1"),
+    (0x3112, "Mains over-voltage phase L2"),
+    (0x3113, "Mains over-voltage phase L3"),
+    (0x3120, "Mains under-voltage"),
+    (0x3121, "Mains under-voltage phase L1"),
+    (0x3122, "Mains under-voltage phase L2"),
+    (0x3123, "Mains under-voltage phase L3"),
+    (0x3130, "Phase failure"),
+    (0x3131, "Phase failure L1"),
+    (0x3132, "Phase failure L2"),
+    (0x3133, "Phase failure L3"),
+    (0x3134, "Phase sequence"),
+    (0x3140, "Mains frequency"),
+    (0x3141, "Mains frequency too great"),
+    (0x3142, "Mains frequency too small"),
+    (0x3210, "DC link over-voltage"),
+    (0x3211, "Over-voltage no. 1"),
+    (0x3212, "Over-voltage no. 2"),
+    (0x3220, "DC link under-voltage"),
+    (0x3221, "Under-voltage no. 1"),
+    (0x3222, "Under-voltage no. 2"),
+    (0x3230, "Load error"),
+    (0x3310, "Output over-voltage"),
+    (0x3311, "Output over-voltage phase U"),
+    (0x3312, "Output over-voltage phase V"),
+    (0x3313, "Output over-voltage phase W"),
+    (0x3320, "Armature circuit"),
+    (0x3321, "Armature circuit interrupted"),
+    (0x3330, "Field circuit"),
+    (0x3331, "Field circuit interrupted"),
+    (0x4110, "Excess ambient temperature"),
+    (0x4120, "Too low ambient temperature"),
+    (0x4130, "Temperature supply air"),
+    (0x4140, "Temperature air outlet"),
+    (0x4210, "Excess temperature device"),
+    (0x4220, "Too low temperature device"),
+    (0x4300, "Temperature drive"),
+    (0x4310, "Excess temperature drive"),
+    (0x4320, "Too low temperature drive"),
+    (0x4400, "Temperature supply"),
+    (0x4410, "Excess temperature supply"),
+    (0x4420, "Too low temperature supply"),
+    (0x5100, "Supply"),
+    (0x5110, "Supply low voltage"),
+    (0x5111, "U1 = supply +/-15V"),
+    (0x5112, "U2 = supply +24V"),
+    (0x5113, "U3 = supply +5V"),
+    (0x5120, "Supply intermediate circuit"),
+    (0x5200, "Control"),
+    (0x5210, "Measurement circuit"),
+    (0x5220, "Computing circuit"),
+    (0x5300, "Operating unit"),
+    (0x5400, "Power section"),
+    (0x5410, "Output stages"),
+    (0x5420, "Chopper"),
+    (0x5430, "Input stages"),
+    (0x5440, "Contacts"),
+    (0x5441, "Contact 1 = manufacturer specific"),
+    (0x5450, "Fuses"),
+    (0x5451, "S1 = l1"),
+    (0x5452, "S2 = l2"),
+    (0x5453, "S3 = l3"),
+    (0x5500, "Data storage"),
+    (0x5510, "Working memory"),
+    (0x5520, "Program memory"),
+    (0x5530, "Non-volatile data memory"),
+    (0x6010, "Software reset (watchdog)"),
+    (0x6301, "Loss of parameters"),
+    (0x6320, "Parameter error"),
+    (0x7100, "Power"),
+    (0x7110, "Brake chopper"),
+    (0x7111, "Failure brake chopper"),
+    (0x7112, "Over current brake chopper"),
+    (0x7113, "Protective circuit brake chopper"),
+    (0x7120, "Motor"),
+    (0x7121, "Motor blocked"),
+    (0x7122, "Motor error or commutation malfunction"),
+    (0x7123, "Motor tilted"),
+    (0x7200, "Measurement circuit"),
+    (0x7300, "Sensor"),
+    (0x7301, "Tacho fault"),
+    (0x7302, "Tacho wrong polarity"),
+    (0x7303, "Resolver 1 fault"),
+    (0x7304, "Resolver 2 fault"),
+    (0x7305, "Incremental sensor 1 fault"),
+    (0x7306, "Incremental sensor 2 fault"),
+    (0x7307, "Incremental sensor 3 fault"),
+    (0x7310, "Speed"),
+    (0x7320, "Position"),
+    (0x7400, "Computation circuit"),
+    (0x7500, "Communication"),
+    (0x7510, "Serial interface no. 1"),
+    (0x7520, "Serial interface no. 2"),
+    (0x7600, "Data storage"),
+    (0x8300, "Torque control"),
+    (0x8311, "Excess torque"),
+    (0x8312, "Difficult start up"),
+    (0x8313, "Standstill torque"),
+    (0x8321, "Insufficient torque"),
+    (0x8331, "Torque fault"),
+    (0x8400, "Velocity speed controller"),
+    (0x8500, "Position controller"),
+    (0x8600, "Positioning controller"),
+    (0x8611, "Following error"),
+    (0x8612, "Reference limit"),
+    (0x8700, "Sync controller"),
+    (0x8800, "Winding controller"),
+    (0x8900, "Process data monitoring"),
+    (0x8A00, "Control"),
+    (0xF001, "Deceleration"),
+    (0xF002, "Sub-synchronous run"),
+    (0xF003, "Stroke operation"),
+    (0xF004, "Control"),
+];
+
+fn find(table: &[(u16, &'static str)], code: u16) -> Option<&'static str> {
+    table.iter().find(|(c, _)| *c == code).map(|(_, d)| *d)
+}
+
+impl ErrorRegister {
+    /// Names of the set bits, e.g. "generic, current".
+    pub fn to_names(self) -> String {
+        if self.is_empty() {
+            return "none".to_owned();
+        }
+
+        self.iter_names()
+            .map(|(name, _)| name.to_lowercase().replace('_', " "))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl EmcyInfo {
+    /// Parses EMCY data: error code (u16), error register and 5 bytes of manufacturer data.
+    pub fn new(data: &[u8]) -> Option<Self> {
+        let [c0, c1, register, vendor @ ..] = data else {
+            return None;
+        };
+
+        let mut vendor_data = [0u8; 5];
+        let len = vendor.len().min(5);
+        vendor_data[..len].copy_from_slice(&vendor[..len]);
+        let code = u16::from_le_bytes([*c0, *c1]);
+        Some(Self {
+            code,
+            error_register: ErrorRegister::from_bits_retain(*register),
+            vendor_data,
+            description: EmcyCatalogue::builtin(code).map(str::to_owned),
+        })
+    }
+
+    pub fn get_tooltip(&self) -> String {
+        format!(
+            "Error code: 0x{:04X} {}\nError register: 0x{:02X} {}\nManufacturer data: {:02X?}",
+            self.code,
+            self.description.as_deref().unwrap_or("unknown"),
+            self.error_register.bits(),
+            self.error_register.to_names(),
+            self.vendor_data
+        )
+    }
+}
+
+impl fmt::Display for EmcyInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "0x{:04X} {} reg [{}] data {:02X?}",
+            self.code,
+            self.description.as_deref().unwrap_or("Unknown error code"),
+            self.error_register.to_names(),
+            self.vendor_data
+        )
+    }
+}
+
+impl EmcyCatalogue {
+    /// Looks up a code in the `CiA 301` and `CiA 402` tables.
+    ///
+    /// Codes missing from the tables are described by their group, e.g. 0x2315 by 0x2300.
+    pub fn builtin(code: u16) -> Option<&'static str> {
+        [0xFFFF, 0xFFF0, 0xFF00, 0xF000]
+            .into_iter()
+            .find_map(|mask| {
+                find(CIA402_CODES, code & mask).or_else(|| find(CIA301_CODES, code & mask))
+            })
+    }
+
+    /// Parses a vendor table, one `code = description` pair per line, `#` starts a comment.
+    ///
+    /// # Errors
+    /// Returns a description of the first malformed line.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut vendor = HashMap::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((code, description)) = line.split_once('=') else {
+                return Err(format!("line {}: expected 'code = description'", n + 1));
+            };
+            let code = code.trim();
+            let code = code
+                .strip_prefix("0x")
+                .or_else(|| code.strip_prefix("0X"))
+                .unwrap_or(code);
+            let code = u16::from_str_radix(code, 16)
+                .map_err(|e| format!("line {}: wrong error code: {e}", n + 1))?;
+            vendor.insert(code, description.trim().to_owned());
+        }
+
+        Ok(Self { vendor })
+    }
+
+    /// Loads a vendor table from a file, see [`EmcyCatalogue::parse`].
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Describes a code, vendor table entries take precedence over the built-in tables.
+    pub fn describe(&self, code: u16) -> Option<&str> {
+        self.vendor
+            .get(&code)
+            .map(String::as_str)
+            .or_else(|| Self::builtin(code))
+    }
+
+    /// Replaces the description of an EMCY message if the vendor table has its code.
+    pub fn process(&self, msg: &mut MessageCached) {
+        if let RxMessageAdditional::Emcy(emcy) = &mut msg.additional {
+            if let Some(description) = self.vendor.get(&emcy.code) {
+                emcy.description = Some(description.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmcyCatalogue, EmcyInfo, ErrorRegister};
+
+    #[test]
+    fn test_emcy_decoding() {
+        let emcy = EmcyInfo::new(&[0x10, 0x23, 0x03, 1, 2, 3, 4, 5]).unwrap();
+        assert_eq!(emcy.code, 0x2310);
+        assert_eq!(
+            emcy.error_register,
+            ErrorRegister::GENERIC | ErrorRegister::CURRENT
+        );
+        assert_eq!(
+            emcy.to_string(),
+            "0x2310 Continuous over-current (device output side) reg [generic, current] data [01, 02, 03, 04, 05]"
+        );
+
+        assert_eq!(
+            EmcyCatalogue::builtin(0x8130),
+            Some("Life guard error or heartbeat error")
+        );
+        assert_eq!(
+            EmcyCatalogue::builtin(0x2315),
+            Some("Continuous over-current (device output side)")
+        );
+        assert_eq!(
+            EmcyCatalogue::builtin(0x2350),
+            Some("Current, device output side")
+        );
+        assert_eq!(EmcyCatalogue::builtin(0x1234), Some("Generic error"));
+        assert!(EmcyInfo::new(&[0x00, 0x10]).is_none());
+    }
+
+    #[test]
+    fn test_vendor_table() {
+        let catalogue = EmcyCatalogue::parse(
+            "# vendor codes\n0xFF01 = Encoder cable broken\n\n2310=Motor phase overload # override\n",
+        )
+        .unwrap();
+        assert_eq!(catalogue.describe(0xFF01), Some("Encoder cable broken"));
+        assert_eq!(catalogue.describe(0x2310), Some("Motor phase overload"));
+        assert_eq!(catalogue.describe(0xFF02), Some("Device specific"));
+
+        assert!(EmcyCatalogue::parse("0xFF01 Encoder").is_err());
+        assert!(EmcyCatalogue::parse("0xFG01 = Encoder").is_err());
+    }
+}
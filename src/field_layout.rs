@@ -0,0 +1,350 @@
+use crate::message_cached::MessageCached;
+use core::fmt;
+use regex::Regex;
+use std::{fs, path::Path};
+
+/// Type of a user defined field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Unsigned(usize),
+    Signed(usize),
+    F32,
+    /// Unsigned bit range of the given width, counted from the least significant bit.
+    Bits(usize),
+}
+
+impl FieldType {
+    fn parse(s: &str) -> Option<(Self, bool)> {
+        let (s, big_endian) = if let Some(s) = s.strip_suffix("be") {
+            (s, true)
+        } else {
+            (s.strip_suffix("le").unwrap_or(s), false)
+        };
+
+        let ty = match s {
+            "u8" => FieldType::Unsigned(1),
+            "u16" => FieldType::Unsigned(2),
+            "u32" => FieldType::Unsigned(4),
+            "u64" => FieldType::Unsigned(8),
+            "i8" => FieldType::Signed(1),
+            "i16" => FieldType::Signed(2),
+            "i32" => FieldType::Signed(4),
+            "i64" => FieldType::Signed(8),
+            "f32" => FieldType::F32,
+            "bit" => FieldType::Bits(1),
+            _ => match s.strip_prefix("bits")?.parse() {
+                Ok(width @ 1..=64) => FieldType::Bits(width),
+                _ => return None,
+            },
+        };
+        Some((ty, big_endian))
+    }
+}
+
+/// Field at a byte (and bit for bit ranges) position of the payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub ty: FieldType,
+    pub byte: usize,
+    pub bit: usize,
+    pub big_endian: bool,
+    pub scale: f64,
+    pub offset: f64,
+}
+
+/// Value of a decoded field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldValue {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+}
+
+impl FieldValue {
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_f64(self) -> f64 {
+        match self {
+            FieldValue::Unsigned(v) => v as f64,
+            FieldValue::Signed(v) => v as f64,
+            FieldValue::Float(v) => v,
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldValue::Unsigned(v) => write!(f, "{v}"),
+            FieldValue::Signed(v) => write!(f, "{v}"),
+            FieldValue::Float(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+impl Field {
+    /// Parses `name:type@byte[.bit][*scale][+offset]`, e.g. `speed:u16be@0*0.1` or `brake:bit@2.3`.
+    ///
+    /// # Errors
+    /// Returns a description of the malformed part.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (name, rest) = s
+            .split_once(':')
+            .ok_or_else(|| format!("'{s}': expected name:type@byte"))?;
+        let (ty, rest) = rest
+            .split_once('@')
+            .ok_or_else(|| format!("'{s}': expected name:type@byte"))?;
+        let (ty, big_endian) =
+            FieldType::parse(ty.trim()).ok_or_else(|| format!("'{s}': unknown type '{ty}'"))?;
+
+        // position ends at the first scale or offset sign
+        let end = rest.find(['*', '+', '-']).unwrap_or(rest.len());
+        let (position, mut modifiers) = rest.split_at(end);
+        let (byte, bit) = position.trim().split_once('.').unwrap_or((position, "0"));
+        let byte = byte
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{s}': wrong byte position"))?;
+        let bit = bit
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{s}': wrong bit position"))?;
+        if byte > 7 {
+            return Err(format!("'{s}': byte position is 0-7"));
+        }
+        if bit > 7 || (bit > 0 && !matches!(ty, FieldType::Bits(_))) {
+            return Err(format!(
+                "'{s}': bit position is only allowed for bit ranges"
+            ));
+        }
+
+        let mut scale = 1.0;
+        let mut offset = 0.0;
+        let number = |v: &str| {
+            v.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("'{s}': wrong number '{}'", v.trim()))
+        };
+        if let Some(m) = modifiers.strip_prefix('*') {
+            // skip the sign of a negative scale
+            let end = m
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| matches!(c, '+' | '-'))
+                .map_or(m.len(), |(i, _)| i);
+            scale = number(&m[..end])?;
+            modifiers = &m[end..];
+        }
+        if let Some(m) = modifiers.strip_prefix('+') {
+            offset = number(m)?;
+        } else if modifiers.starts_with('-') {
+            offset = number(modifiers)?;
+        } else if !modifiers.trim().is_empty() {
+            return Err(format!("'{s}': unexpected '{modifiers}'"));
+        }
+
+        Ok(Field {
+            name: name.trim().to_owned(),
+            ty,
+            byte,
+            bit,
+            big_endian,
+            scale,
+            offset,
+        })
+    }
+
+    fn read_bytes(&self, data: &[u8], len: usize) -> Option<u64> {
+        let bytes = data.get(self.byte..self.byte + len)?;
+        let fold = |acc, b: &u8| acc << 8 | u64::from(*b);
+        Some(if self.big_endian {
+            bytes.iter().fold(0, fold)
+        } else {
+            bytes.iter().rev().fold(0, fold)
+        })
+    }
+
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_wrap)]
+    pub fn decode(&self, data: &[u8]) -> Option<FieldValue> {
+        let raw = match self.ty {
+            FieldType::Unsigned(len) => FieldValue::Unsigned(self.read_bytes(data, len)?),
+            FieldType::Signed(len) => {
+                let shift = 64 - len * 8;
+                FieldValue::Signed(((self.read_bytes(data, len)? << shift) as i64) >> shift)
+            }
+            FieldType::F32 => {
+                let raw = u32::try_from(self.read_bytes(data, 4)?).ok()?;
+                FieldValue::Float(f64::from(f32::from_bits(raw)))
+            }
+            FieldType::Bits(width) => {
+                let end = self.byte * 8 + self.bit + width;
+                if end > data.len() * 8 {
+                    return None;
+                }
+                let mut v = 0u64;
+                for n in (self.byte * 8 + self.bit..end).rev() {
+                    v = v << 1 | u64::from(data[n / 8] >> (n % 8) & 1);
+                }
+                FieldValue::Unsigned(v)
+            }
+        };
+
+        if (self.scale - 1.0).abs() < f64::EPSILON && self.offset == 0.0 {
+            return Some(raw);
+        }
+        Some(FieldValue::Float(raw.as_f64() * self.scale + self.offset))
+    }
+}
+
+/// Selects the messages a layout applies to.
+#[derive(Debug, Clone)]
+pub enum LayoutMatch {
+    CobId(u32),
+    /// Regex over the COB ID column.
+    Regex(Regex),
+}
+
+impl LayoutMatch {
+    /// Parses a hexadecimal COB ID with optional `0x` prefix or a `/regex/`.
+    ///
+    /// # Errors
+    /// Returns a description of the wrong COB ID or regex.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if let Some(regex) = s.strip_prefix('/').and_then(|m| m.strip_suffix('/')) {
+            return Regex::new(regex)
+                .map(LayoutMatch::Regex)
+                .map_err(|e| e.to_string());
+        }
+        let id = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        u32::from_str_radix(id, 16)
+            .map(LayoutMatch::CobId)
+            .map_err(|e| format!("wrong COB ID: {e}"))
+    }
+
+    pub fn matches(&self, msg: &MessageCached) -> bool {
+        match self {
+            LayoutMatch::CobId(id) => msg.can_id == *id,
+            LayoutMatch::Regex(regex) => regex.is_match(&msg.cob_str),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldLayout {
+    pub matcher: LayoutMatch,
+    pub fields: Vec<Field>,
+}
+
+/// User defined payload layouts, shown in the fields column of the viewer.
+#[derive(Debug, Default, Clone)]
+pub struct FieldLayouts {
+    layouts: Vec<FieldLayout>,
+}
+
+impl FieldLayouts {
+    /// Parses one `cob = field; field; ...` layout per line, where `cob` is a hexadecimal
+    /// COB ID or a `/regex/` over the COB ID column, `#` starts a comment.
+    ///
+    /// # Errors
+    /// Returns a description of the first malformed line.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut layouts = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((matcher, fields)) = line.split_once('=') else {
+                return Err(format!("line {}: expected 'cob = fields'", n + 1));
+            };
+            let matcher =
+                LayoutMatch::parse(matcher).map_err(|e| format!("line {}: {e}", n + 1))?;
+
+            let fields = fields
+                .split(';')
+                .filter(|f| !f.trim().is_empty())
+                .map(Field::parse)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("line {}: {e}", n + 1))?;
+            layouts.push(FieldLayout { matcher, fields });
+        }
+
+        Ok(Self { layouts })
+    }
+
+    /// Reads a layouts file for the editor and checks it, see [`FieldLayouts::parse`].
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load(path: &Path) -> Result<String, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(text)
+    }
+
+    pub fn layouts(&self) -> &[FieldLayout] {
+        &self.layouts
+    }
+
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+
+    /// Decodes the fields of the first layout matching the message.
+    pub fn decode(&self, msg: &MessageCached) -> Option<String> {
+        let layout = self.layouts.iter().find(|l| l.matcher.matches(msg))?;
+        let data = &msg.msg.msg.data[..msg.msg.msg.dlc.min(8)];
+        Some(
+            layout
+                .fields
+                .iter()
+                .map(|f| match f.decode(data) {
+                    Some(v) => format!("{}={v}", f.name),
+                    None => format!("{}=?", f.name),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Field, FieldLayouts, FieldValue};
+
+    #[test]
+    fn test_field_decoding() {
+        let data = [0x10, 0x27, 0x08, 0xFE, 0x00, 0x00, 0x20, 0x41];
+        let field = |s: &str| Field::parse(s).unwrap().decode(&data);
+
+        assert_eq!(field("speed:u16@0"), Some(FieldValue::Unsigned(10000)));
+        assert_eq!(field("speed:u16be@0"), Some(FieldValue::Unsigned(0x1027)));
+        assert_eq!(field("speed:u16@0*0.1"), Some(FieldValue::Float(1000.0)));
+        assert_eq!(field("brake:bit@2.3"), Some(FieldValue::Unsigned(1)));
+        assert_eq!(field("gear:bits3@2.2"), Some(FieldValue::Unsigned(2)));
+        assert_eq!(field("temp:i8@3-40"), Some(FieldValue::Float(-42.0)));
+        assert_eq!(field("temp:i8@3*-1"), Some(FieldValue::Float(2.0)));
+        assert_eq!(field("temp:i16@2"), Some(FieldValue::Signed(-504)));
+        assert_eq!(field("ratio:f32@4"), Some(FieldValue::Float(10.0)));
+        assert_eq!(field("total:u64@1"), None);
+        assert!(Field::parse("x:u16@0.3").is_err());
+        assert!(Field::parse("x:u24@0").is_err());
+        assert!(Field::parse("x:u16@18446744073709551615").is_err());
+        assert!(Field::parse("x:u8@8").is_err());
+
+        let layouts =
+            FieldLayouts::parse("# drive\n0x181 = speed:u16@0; brake:bit@2.3\n/^2../ = x:u8@0\n")
+                .unwrap();
+        assert_eq!(layouts.len(), 2);
+        assert!(FieldLayouts::parse("181 speed:u16@0").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{field_layout::FieldLayouts, theme::OZON_PINK};
+use egui::{Button, TextEdit};
+use std::{fs, path::Path, rc::Rc};
+
+const HINT: &str = "# cob = name:type@byte[.bit][*scale][+offset]; ...
+181 = speed:u16@0*0.1; brake:bit@2.3; gear:bits3@2.4
+/^2.1$/ = torque:i16be@0; temp:u8@2-40";
+
+/// Editor of the user defined field layouts.
+#[derive(Debug)]
+pub struct FieldLayoutPanel {
+    pub layouts: Rc<FieldLayouts>,
+    /// Fields column is shown in the viewer.
+    pub show: bool,
+    /// Editor window is open.
+    pub open: bool,
+    text: String,
+    error: Option<String>,
+    /// File the layouts are saved to, the `--fields` file if given.
+    path: String,
+    save_status: Option<Result<String, String>>,
+}
+
+impl Default for FieldLayoutPanel {
+    fn default() -> Self {
+        Self {
+            layouts: Rc::default(),
+            show: false,
+            open: false,
+            text: String::new(),
+            error: None,
+            path: "fields.txt".to_owned(),
+            save_status: None,
+        }
+    }
+}
+
+impl FieldLayoutPanel {
+    /// Creates the editor with layouts read by [`FieldLayouts::load`] from the path, the fields
+    /// column is shown.
+    pub fn new(text: String, path: &Path) -> Self {
+        let mut panel = Self {
+            show: true,
+            text,
+            path: path.display().to_string(),
+            ..Default::default()
+        };
+        panel.apply();
+        panel
+    }
+
+    /// Keeps the last valid layouts while the text has errors.
+    fn apply(&mut self) {
+        match FieldLayouts::parse(&self.text) {
+            Ok(layouts) => {
+                self.layouts = Rc::new(layouts);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context) {
+        let mut open = self.open;
+        egui::Window::new("Field layouts")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.label("One layout per line: hexadecimal COB ID or /regex/ over the COB ID column, then fields separated by ';'.");
+                ui.label("Types: u8..u64, i8..i64, f32 with optional be/le suffix (little endian by default), bit and bitsN at byte.bit.");
+                if ui
+                    .add(
+                        TextEdit::multiline(&mut self.text)
+                            .code_editor()
+                            .hint_text(HINT)
+                            .desired_rows(8)
+                            .desired_width(f32::INFINITY),
+                    )
+                    .changed()
+                {
+                    self.apply();
+                }
+
+                match &self.error {
+                    Some(e) => ui.colored_label(OZON_PINK, e),
+                    None => ui.label(format!("{} layouts", self.layouts.len())),
+                };
+
+                ui.horizontal(|ui| {
+                    ui.add(TextEdit::singleline(&mut self.path).desired_width(200.0));
+                    if ui
+                        .add_enabled(self.error.is_none(), Button::new("💾 Save"))
+                        .on_hover_text("Edits are lost on exit unless they are saved")
+                        .clicked()
+                    {
+                        self.save_status = Some(
+                            fs::write(&self.path, &self.text)
+                                .map(|()| format!("saved to {}", self.path))
+                                .map_err(|e| format!("{}: {e}", self.path)),
+                        );
+                    }
+                    match &self.save_status {
+                        Some(Ok(status)) => {
+                            ui.label(status);
+                        }
+                        Some(Err(e)) => {
+                            ui.colored_label(OZON_PINK, e);
+                        }
+                        None => {}
+                    }
+                });
+            });
+        self.open = open;
+    }
+}
@@ -1,16 +1,38 @@
-use crate::message_cached::MessageCached;
+use crate::{
+    filter_expr::FilterExpr,
+    heartbeat::NmtState,
+    message_cached::{MessageCached, RxMessageAdditional},
+};
 use bitflags::bitflags;
 use oze_canopen::canopen::{NodeId, RxMessageType};
 use regex::Regex;
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, ops::RangeInclusive, rc::Rc, str::FromStr};
 
-/// Represents a filter for CAN messages based on node id and regular expressions.
+/// Highest node ID of `CANopen`.
+const MAX_NODE_ID: NodeId = 127;
+
+/// Represents a filter for CAN messages based on node id, NMT state and regular expressions.
 #[derive(Default, Debug, Clone)]
 #[allow(clippy::module_name_repetitions)]
 pub struct DataFilter {
     pub regex: Option<Regex>,
-    pub node_id: Option<NodeId>,
+    pub node_id: Option<NodeSet>,
     pub regex_cob: Option<Regex>,
+    /// Pass only heartbeat and node guarding messages reporting this state.
+    pub nmt_state: Option<NmtState>,
+    /// Matched against the decoded information, e.g. DBC message and signal names.
+    pub regex_info: Option<Regex>,
+    /// Passes only messages matching the expression.
+    pub expr: Option<FilterExpr>,
+}
+
+/// Node IDs passed by a filter, parsed from e.g. `1,3,10-20,0x7F` or `!5`.
+///
+/// Messages without a node ID pass only when the set has nothing but exclusions.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct NodeSet {
+    include: Vec<RangeInclusive<NodeId>>,
+    exclude: Vec<RangeInclusive<NodeId>>,
 }
 
 /// Represents a global filter that includes data filters and flag-based type filters.
@@ -19,6 +41,8 @@ pub struct DataFilter {
 pub struct GlobalFilter {
     pub ignore_type: Flags,
     pub data: Rc<RefCell<DataFilter>>,
+    /// Shows every frame of segmented ISO-TP messages, not only the frame completing them.
+    pub isotp_frames: bool,
 }
 
 bitflags! {
@@ -38,13 +62,64 @@ bitflags! {
     }
 }
 
+fn parse_node_id(s: &str) -> Result<NodeId, String> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => NodeId::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .ok()
+    .filter(|node_id| *node_id <= MAX_NODE_ID)
+    .ok_or_else(|| format!("'{s}': expected a node ID 0-127"))
+}
+
+impl NodeSet {
+    pub fn single(node_id: NodeId) -> Self {
+        Self {
+            include: vec![node_id..=node_id],
+            exclude: Vec::new(),
+        }
+    }
+
+    pub fn contains(&self, node_id: Option<NodeId>) -> bool {
+        let Some(node_id) = node_id else {
+            return self.include.is_empty();
+        };
+        (self.include.is_empty() || self.include.iter().any(|r| r.contains(&node_id)))
+            && !self.exclude.iter().any(|r| r.contains(&node_id))
+    }
+}
+
+impl FromStr for NodeSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = Self::default();
+        for item in s.split(',').map(str::trim) {
+            let (list, item) = match item.strip_prefix('!') {
+                Some(item) => (&mut set.exclude, item),
+                None => (&mut set.include, item),
+            };
+            let (from, to) = item.split_once('-').unwrap_or((item, item));
+            let (from, to) = (parse_node_id(from)?, parse_node_id(to)?);
+            if from > to {
+                return Err(format!("'{item}': empty range"));
+            }
+            list.push(from..=to);
+        }
+        Ok(set)
+    }
+}
+
 impl DataFilter {
     /// Filters messages based on node id and regular expressions.
     ///
     /// Returns `true` if the message should be filtered out, `false` otherwise.
     pub fn filter(&self, msg: &MessageCached) -> bool {
-        if self.node_id.is_some() && msg.msg.parsed_node_id != self.node_id {
-            return true;
+        if let Some(node_id) = &self.node_id {
+            if !node_id.contains(msg.msg.parsed_node_id) {
+                return true;
+            }
         }
 
         if let Some(re) = &self.regex_cob {
@@ -59,6 +134,25 @@ impl DataFilter {
             }
         }
 
+        if let Some(re) = &self.regex_info {
+            if !re.is_match(&msg.info_str) {
+                return true;
+            }
+        }
+
+        if let Some(expr) = &self.expr {
+            if !expr.matches(msg) {
+                return true;
+            }
+        }
+
+        if let Some(state) = self.nmt_state {
+            match &msg.additional {
+                RxMessageAdditional::Heartbeat(h) if h.state == state => {}
+                _ => return true,
+            }
+        }
+
         false
     }
 }
@@ -72,6 +166,12 @@ impl GlobalFilter {
             return true;
         }
 
+        if !self.isotp_frames
+            && matches!(&msg.additional, RxMessageAdditional::IsoTp(info) if info.folded)
+        {
+            return true;
+        }
+
         match msg.msg.parsed_type {
             RxMessageType::SdoTx | RxMessageType::SdoRx => self.ignore_type.contains(Flags::SDO),
             RxMessageType::Pdo => self.ignore_type.contains(Flags::PDO),
@@ -87,8 +187,11 @@ impl GlobalFilter {
 
 #[cfg(test)]
 mod tests {
-    use super::{DataFilter, GlobalFilter};
-    use crate::message_cached::MessageCached;
+    use super::{DataFilter, GlobalFilter, NodeSet};
+    use crate::{
+        heartbeat::NmtState,
+        message_cached::{test_message, MessageCached},
+    };
     use oze_canopen::receiver::RxMessage;
     use regex::Regex;
     use std::{cell::RefCell, rc::Rc};
@@ -119,6 +222,9 @@ mod tests {
             regex: Regex::new("^01").ok(),
             node_id: None,
             regex_cob: None,
+            nmt_state: None,
+            regex_info: None,
+            expr: None,
         };
         assert!(!filt.filter(msg183));
         assert!(filt.filter(msg585));
@@ -127,6 +233,9 @@ mod tests {
             regex: Regex::new("^01 02 03$").ok(),
             node_id: None,
             regex_cob: None,
+            nmt_state: None,
+            regex_info: None,
+            expr: None,
         };
         assert!(!filt.filter(msg183));
         assert!(filt.filter(msg585));
@@ -135,46 +244,64 @@ mod tests {
             regex: Regex::new("03").ok(),
             node_id: None,
             regex_cob: None,
+            nmt_state: None,
+            regex_info: None,
+            expr: None,
         };
         assert!(!filt.filter(msg183));
         assert!(!filt.filter(msg585));
 
         let filt = DataFilter {
             regex: Regex::new("03").ok(),
-            node_id: Some(3),
+            node_id: Some(NodeSet::single(3)),
             regex_cob: None,
+            nmt_state: None,
+            regex_info: None,
+            expr: None,
         };
         assert!(!filt.filter(msg183));
         assert!(filt.filter(msg585));
 
         let filt = DataFilter {
             regex: Regex::new("03").ok(),
-            node_id: Some(5),
+            node_id: Some(NodeSet::single(5)),
             regex_cob: None,
+            nmt_state: None,
+            regex_info: None,
+            expr: None,
         };
         assert!(filt.filter(msg183));
         assert!(!filt.filter(msg585));
 
         let filt = DataFilter {
             regex: None,
-            node_id: Some(5),
+            node_id: Some(NodeSet::single(5)),
             regex_cob: Regex::new("^58").ok(),
+            nmt_state: None,
+            regex_info: None,
+            expr: None,
         };
         assert!(filt.filter(msg183));
         assert!(!filt.filter(msg585));
 
         let filt = DataFilter {
             regex: None,
-            node_id: Some(5),
+            node_id: Some(NodeSet::single(5)),
             regex_cob: Regex::new("^18").ok(),
+            nmt_state: None,
+            regex_info: None,
+            expr: None,
         };
         assert!(filt.filter(msg183));
         assert!(filt.filter(msg585));
 
         let filt = DataFilter {
             regex: Regex::new("AB").ok(),
-            node_id: Some(5),
+            node_id: Some(NodeSet::single(5)),
             regex_cob: Regex::new("58").ok(),
+            nmt_state: None,
+            regex_info: None,
+            expr: None,
         };
         assert!(filt.filter(msg183));
         assert!(!filt.filter(msg585));
@@ -215,7 +342,11 @@ mod tests {
                 regex: None,
                 node_id: None,
                 regex_cob: None,
+                nmt_state: None,
+                regex_info: None,
+                expr: None,
             })),
+            isotp_frames: false,
         };
         assert!(!filt.filter(msg183));
         assert!(!filt.filter(msg585));
@@ -225,9 +356,13 @@ mod tests {
             ignore_type: super::Flags::all() & !super::Flags::PDO,
             data: Rc::new(RefCell::new(DataFilter {
                 regex: None,
-                node_id: Some(3),
+                node_id: Some(NodeSet::single(3)),
                 regex_cob: None,
+                nmt_state: None,
+                regex_info: None,
+                expr: None,
             })),
+            isotp_frames: false,
         };
         assert!(!filt.filter(msg183));
         assert!(filt.filter(msg585));
@@ -237,9 +372,13 @@ mod tests {
             ignore_type: super::Flags::all() & !super::Flags::PDO,
             data: Rc::new(RefCell::new(DataFilter {
                 regex: None,
-                node_id: Some(5),
+                node_id: Some(NodeSet::single(5)),
                 regex_cob: None,
+                nmt_state: None,
+                regex_info: None,
+                expr: None,
             })),
+            isotp_frames: false,
         };
         assert!(filt.filter(msg183));
         assert!(filt.filter(msg585));
@@ -251,7 +390,11 @@ mod tests {
                 regex: None,
                 node_id: None,
                 regex_cob: None,
+                nmt_state: None,
+                regex_info: None,
+                expr: None,
             })),
+            isotp_frames: false,
         };
         assert!(!filt.filter(msg183));
         assert!(filt.filter(msg585));
@@ -263,10 +406,105 @@ mod tests {
                 regex: None,
                 node_id: None,
                 regex_cob: None,
+                nmt_state: None,
+                regex_info: None,
+                expr: None,
             })),
+            isotp_frames: false,
         };
         assert!(filt.filter(msg183));
         assert!(filt.filter(msg585));
         assert!(filt.filter(msg80));
     }
+
+    #[test]
+    fn test_nmt_state_filter() {
+        let heartbeat = |state: u8| test_message(0x705, &[state], Instant::now());
+        let operational = &heartbeat(0x05);
+        let guard_preop = &heartbeat(0xFF);
+        let bootup = &heartbeat(0x00);
+
+        let filt = DataFilter {
+            nmt_state: Some(NmtState::Operational),
+            ..DataFilter::default()
+        };
+        assert!(!filt.filter(operational));
+        assert!(filt.filter(guard_preop));
+        assert!(filt.filter(bootup));
+
+        let filt = DataFilter {
+            nmt_state: Some(NmtState::PreOperational),
+            ..DataFilter::default()
+        };
+        assert!(filt.filter(operational));
+        assert!(!filt.filter(guard_preop));
+        assert_eq!(
+            guard_preop.additional.to_string(),
+            "Guard     Pre-operational toggle=1"
+        );
+        assert_eq!(bootup.additional.to_string(), "Boot-up");
+    }
+
+    #[test]
+    fn test_info_filter() {
+        let heartbeat = test_message(0x705, &[0x05], Instant::now());
+
+        let filt = DataFilter {
+            regex_info: Regex::new("Heartbeat Oper").ok(),
+            ..DataFilter::default()
+        };
+        assert!(!filt.filter(&heartbeat));
+
+        let filt = DataFilter {
+            regex_info: Regex::new("^Engine").ok(),
+            ..DataFilter::default()
+        };
+        assert!(filt.filter(&heartbeat));
+    }
+
+    #[test]
+    fn test_node_set() {
+        let msg = |cob_id| test_message(cob_id, &[0; 8], Instant::now());
+        let node = |s: &str| DataFilter {
+            node_id: Some(s.parse().unwrap()),
+            ..DataFilter::default()
+        };
+        let node1 = &msg(0x181);
+        let node3 = &msg(0x703);
+        let node15 = &msg(0x58F);
+        let node127 = &msg(0x1FF);
+        let sync = &msg(0x080);
+
+        let filt = node("1, 3,10-20");
+        assert!(!filt.filter(node1));
+        assert!(!filt.filter(node3));
+        assert!(!filt.filter(node15));
+        assert!(filt.filter(node127));
+        assert!(filt.filter(sync));
+
+        let filt = node("!3,!0x0A-0x0F");
+        assert!(!filt.filter(node1));
+        assert!(filt.filter(node3));
+        assert!(filt.filter(node15));
+        assert!(!filt.filter(node127));
+        assert!(!filt.filter(sync));
+
+        let filt = node("0x7F,1-5,!2-3");
+        assert!(!filt.filter(node1));
+        assert!(filt.filter(node3));
+        assert!(!filt.filter(node127));
+
+        assert_eq!(
+            "5-3".parse::<NodeSet>(),
+            Err("'5-3': empty range".to_owned())
+        );
+        assert_eq!(
+            "1,".parse::<NodeSet>(),
+            Err("'': expected a node ID 0-127".to_owned())
+        );
+        assert!("300".parse::<NodeSet>().is_err());
+        assert!("0x80".parse::<NodeSet>().is_err());
+        assert!("100-128".parse::<NodeSet>().is_err());
+        assert_eq!("7".parse(), Ok(NodeSet::single(7)));
+    }
 }
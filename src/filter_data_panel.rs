@@ -1,5 +1,11 @@
-use crate::filter::DataFilter;
-use egui::TextEdit;
+use crate::{
+    filter::{DataFilter, NodeSet},
+    filter_expr::FilterExpr,
+    heartbeat::NmtState,
+    theme::OZON_PINK,
+};
+use egui::{ComboBox, TextEdit};
+use oze_canopen::canopen::NodeId;
 use regex::Regex;
 use std::{cell::RefCell, rc::Rc};
 
@@ -9,6 +15,89 @@ pub struct FilterDataPanel {
     node_raw: String,
     regex_raw: String,
     regex_cob_raw: String,
+    regex_info_raw: String,
+    node_error: Option<String>,
+    expr_raw: String,
+    expr_error: Option<String>,
+}
+
+/// Node ID field, a parse error is shown next to it and disables the node filter.
+fn update_node(
+    ui: &mut egui::Ui,
+    raw: &mut String,
+    error: &mut Option<String>,
+    data_filter: &mut DataFilter,
+) -> bool {
+    let response = ui
+        .add(
+            TextEdit::singleline(raw)
+                .hint_text("nodeID")
+                .desired_width(80.0),
+        )
+        .on_hover_text(
+            "Node IDs, ranges and exclusions, decimal or hex. For example: '1,3,10-20', '0x7F' or '!5'",
+        );
+    if response.changed() {
+        let parsed = if raw.trim().is_empty() {
+            Ok(None)
+        } else {
+            raw.parse().map(Some)
+        };
+        match parsed {
+            Ok(node_id) => {
+                data_filter.node_id = node_id;
+                *error = None;
+            }
+            Err(e) => {
+                data_filter.node_id = None;
+                *error = Some(e);
+            }
+        }
+    }
+    if let Some(e) = error {
+        ui.colored_label(OZON_PINK, e);
+    }
+    response.changed()
+}
+
+/// Expression field, a parse error is shown next to it and disables the expression.
+fn update_expr(
+    ui: &mut egui::Ui,
+    raw: &mut String,
+    error: &mut Option<String>,
+    data_filter: &mut DataFilter,
+) -> bool {
+    let response = ui
+        .add(
+            TextEdit::singleline(raw)
+                .hint_text("expression")
+                .desired_width(250.0),
+        )
+        .on_hover_text(
+            "Boolean expression, e.g. '(type == pdo && node in 3..=5) || \
+             (cob == 0x701 && data[0] == 0x05) || emcy'",
+        );
+    if response.changed() {
+        let parsed = if raw.trim().is_empty() {
+            Ok(None)
+        } else {
+            FilterExpr::parse(raw).map(Some)
+        };
+        match parsed {
+            Ok(expr) => {
+                data_filter.expr = expr;
+                *error = None;
+            }
+            Err(e) => {
+                data_filter.expr = None;
+                *error = Some(e);
+            }
+        }
+    }
+    if let Some(e) = error {
+        ui.colored_label(OZON_PINK, e);
+    }
+    response.changed()
 }
 
 impl FilterDataPanel {
@@ -17,10 +106,23 @@ impl FilterDataPanel {
             data_filter,
             regex_raw: String::new(),
             regex_cob_raw: String::new(),
+            regex_info_raw: String::new(),
             node_raw: String::new(),
+            node_error: None,
+            expr_raw: String::new(),
+            expr_error: None,
         }
     }
 
+    /// Shows only messages of the node, e.g. when a node is clicked in the node overview.
+    ///
+    /// # Panics
+    pub fn set_node_id(&mut self, node_id: NodeId) {
+        self.node_raw = node_id.to_string();
+        self.node_error = None;
+        self.data_filter.try_borrow_mut().unwrap().node_id = Some(NodeSet::single(node_id));
+    }
+
     /// # Panics
     pub fn update(&mut self, ui: &mut egui::Ui) -> bool {
         let mut data_filter = self.data_filter.try_borrow_mut().unwrap();
@@ -37,30 +139,67 @@ impl FilterDataPanel {
             changed = true;
             data_filter.regex_cob = Regex::new(&self.regex_cob_raw).ok();
         }
+        changed |= update_node(
+            ui,
+            &mut self.node_raw,
+            &mut self.node_error,
+            &mut data_filter,
+        );
         if ui
             .add(
-                TextEdit::singleline(&mut self.node_raw)
-                    .hint_text("nodeID")
-                    .desired_width(55.0),
+                TextEdit::singleline(&mut self.regex_raw)
+                    .hint_text("hex data regex")
+                    .desired_width(200.0),
             )
-            .on_hover_text("This fields needs to be an decimal intenger representing NodeID")
+            .on_hover_text("You can use complex regex filters here. For exemple: '^.. 0A ..'")
             .changed()
         {
             changed = true;
-            data_filter.node_id = self.node_raw.parse().ok();
+            data_filter.regex = Regex::new(&self.regex_raw).ok();
         }
         if ui
             .add(
-                TextEdit::singleline(&mut self.regex_raw)
-                    .hint_text("hex data regex")
-                    .desired_width(200.0),
+                TextEdit::singleline(&mut self.regex_info_raw)
+                    .hint_text("info regex")
+                    .desired_width(150.0),
+            )
+            .on_hover_text(
+                "Regex over the Info column, e.g. DBC message or signal names: 'Engine.*Rpm'",
             )
-            .on_hover_text("You can use complex regex filters here. For exemple: '^.. 0A ..'")
             .changed()
         {
             changed = true;
-            data_filter.regex = Regex::new(&self.regex_raw).ok();
+            data_filter.regex_info = Regex::new(&self.regex_info_raw).ok();
         }
+
+        changed |= update_expr(
+            ui,
+            &mut self.expr_raw,
+            &mut self.expr_error,
+            &mut data_filter,
+        );
+
+        let selected = data_filter
+            .nmt_state
+            .map_or_else(|| "NMT state".to_owned(), |s| s.to_string());
+        ComboBox::from_id_salt(ui.id().with("nmt_state"))
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                changed |= ui
+                    .selectable_value(&mut data_filter.nmt_state, None, "any")
+                    .changed();
+                for state in NmtState::ALL {
+                    changed |= ui
+                        .selectable_value(
+                            &mut data_filter.nmt_state,
+                            Some(state),
+                            state.to_string(),
+                        )
+                        .changed();
+                }
+            })
+            .response
+            .on_hover_text("Show only heartbeat and node guarding messages with this NMT state");
         ui.separator();
         changed
     }
@@ -0,0 +1,439 @@
+use crate::message_cached::MessageCached;
+use oze_canopen::canopen::RxMessageType;
+
+/// Message types usable as keywords, e.g. `emcy` or `type == PDO`.
+const TYPES: [(&str, &[RxMessageType]); 9] = [
+    ("sync", &[RxMessageType::Sync]),
+    ("pdo", &[RxMessageType::Pdo]),
+    ("sdo", &[RxMessageType::SdoTx, RxMessageType::SdoRx]),
+    ("sdotx", &[RxMessageType::SdoTx]),
+    ("sdorx", &[RxMessageType::SdoRx]),
+    ("nmt", &[RxMessageType::Nmt]),
+    ("lss", &[RxMessageType::Lss]),
+    ("emcy", &[RxMessageType::Emcy]),
+    ("guard", &[RxMessageType::Guarding]),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(u64),
+    Or,
+    And,
+    Not,
+    Open,
+    Close,
+    OpenIndex,
+    CloseIndex,
+    Dot,
+    Range,
+    Cmp(CmpOp),
+}
+
+/// Comparison of a value with a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Number taken from a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Node,
+    Cob,
+    Dlc,
+    Byte(usize),
+    Bit(usize, u8),
+}
+
+/// Boolean filter expression over the fields of a message, e.g.
+/// `(type == PDO && node in 3..=5) || (cob == 0x701 && data[0] == 0x05) || emcy`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Type(&'static [RxMessageType]),
+    Cmp(Value, CmpOp, u64),
+    /// Both ends are included.
+    In(Value, u64, u64),
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, String> {
+    let bytes = s.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let next = bytes.get(i + 1).copied();
+        let start = i;
+        let (token, len) = match (c, next) {
+            (b' ' | b'\t', _) => {
+                i += 1;
+                continue;
+            }
+            (b'|', Some(b'|')) => (Token::Or, 2),
+            (b'&', Some(b'&')) => (Token::And, 2),
+            (b'=', Some(b'=')) => (Token::Cmp(CmpOp::Eq), 2),
+            (b'!', Some(b'=')) => (Token::Cmp(CmpOp::Ne), 2),
+            (b'<', Some(b'=')) => (Token::Cmp(CmpOp::Le), 2),
+            (b'>', Some(b'=')) => (Token::Cmp(CmpOp::Ge), 2),
+            (b'.', Some(b'.')) => {
+                if bytes.get(i + 2) != Some(&b'=') {
+                    return Err(format!(
+                        "column {}: expected '..=', ranges include both ends",
+                        i + 1
+                    ));
+                }
+                (Token::Range, 3)
+            }
+            (b'!', _) => (Token::Not, 1),
+            (b'<', _) => (Token::Cmp(CmpOp::Lt), 1),
+            (b'>', _) => (Token::Cmp(CmpOp::Gt), 1),
+            (b'(', _) => (Token::Open, 1),
+            (b')', _) => (Token::Close, 1),
+            (b'[', _) => (Token::OpenIndex, 1),
+            (b']', _) => (Token::CloseIndex, 1),
+            (b'.', _) => (Token::Dot, 1),
+            (b'0'..=b'9', _) => {
+                let len = bytes[i..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_alphanumeric())
+                    .count();
+                let text = &s[i..i + len];
+                let number = match text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => text.parse(),
+                };
+                let number =
+                    number.map_err(|_| format!("column {}: invalid number '{text}'", i + 1))?;
+                (Token::Number(number), len)
+            }
+            (c, _) if c.is_ascii_alphabetic() || c == b'_' => {
+                let len = bytes[i..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+                    .count();
+                (Token::Ident(s[i..i + len].to_ascii_lowercase()), len)
+            }
+            _ => {
+                let c = s[i..].chars().next().unwrap_or_default();
+                return Err(format!("column {}: unexpected '{c}'", i + 1));
+            }
+        };
+        tokens.push((start, token));
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// Nesting of `!` and parentheses allowed, deeper expressions would overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+    /// Current nesting of `!` and parentheses.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, t)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    /// Error at the current token.
+    fn error(&self, expected: &str) -> String {
+        match self.tokens.get(self.pos) {
+            Some((column, _)) => format!("column {}: expected {expected}", column + 1),
+            None => format!("column {}: expected {expected}", self.end + 1),
+        }
+    }
+
+    /// Enters a `!` or parenthesis at the current token.
+    fn nest(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            let column = self.tokens.get(self.pos).map_or(self.end, |(c, _)| *c);
+            return Err(format!("column {}: too deeply nested", column + 1));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expect(&mut self, token: &Token, expected: &str) -> Result<(), String> {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(expected))
+        }
+    }
+
+    fn number(&mut self) -> Result<u64, String> {
+        match self.peek() {
+            Some(Token::Number(n)) => {
+                let n = *n;
+                self.pos += 1;
+                Ok(n)
+            }
+            _ => Err(self.error("a number")),
+        }
+    }
+
+    fn or(&mut self) -> Result<FilterExpr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = FilterExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<FilterExpr, String> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = FilterExpr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<FilterExpr, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.nest()?;
+            let expr = FilterExpr::Not(Box::new(self.unary()?));
+            self.depth -= 1;
+            return Ok(expr);
+        }
+        self.primary()
+    }
+
+    fn type_name(&mut self) -> Result<&'static [RxMessageType], String> {
+        let found = match self.peek() {
+            Some(Token::Ident(name)) => TYPES.iter().find(|(n, _)| *n == name.as_str()),
+            _ => None,
+        };
+        match found {
+            Some((_, types)) => {
+                self.pos += 1;
+                Ok(types)
+            }
+            None => Err(self.error("a message type (sync, pdo, sdo, nmt, lss, emcy, guard)")),
+        }
+    }
+
+    fn primary(&mut self) -> Result<FilterExpr, String> {
+        let expected = "'(', '!', a message type, node, cob, dlc or data[byte]";
+        let value = match self.peek() {
+            Some(Token::Open) => {
+                self.nest()?;
+                let expr = self.or()?;
+                self.expect(&Token::Close, "')'")?;
+                self.depth -= 1;
+                return Ok(expr);
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "type" => {
+                    self.pos += 1;
+                    let Some(Token::Cmp(op @ (CmpOp::Eq | CmpOp::Ne))) = self.peek().cloned()
+                    else {
+                        return Err(self.error("'==' or '!='"));
+                    };
+                    self.pos += 1;
+                    let expr = FilterExpr::Type(self.type_name()?);
+                    return Ok(if op == CmpOp::Ne {
+                        FilterExpr::Not(Box::new(expr))
+                    } else {
+                        expr
+                    });
+                }
+                "node" => Value::Node,
+                "cob" => Value::Cob,
+                "dlc" => Value::Dlc,
+                "data" => {
+                    self.pos += 1;
+                    self.expect(&Token::OpenIndex, "'['")?;
+                    let byte = self.number()?;
+                    if byte > 7 {
+                        self.pos -= 1;
+                        return Err(self.error("a byte index 0-7"));
+                    }
+                    self.expect(&Token::CloseIndex, "']'")?;
+                    #[allow(clippy::cast_possible_truncation)]
+                    let value = if self.peek() == Some(&Token::Dot) {
+                        self.pos += 1;
+                        let bit = self.number()?;
+                        if bit > 7 {
+                            self.pos -= 1;
+                            return Err(self.error("a bit index 0-7"));
+                        }
+                        Value::Bit(byte as usize, bit as u8)
+                    } else {
+                        Value::Byte(byte as usize)
+                    };
+                    return self.comparison(value);
+                }
+                _ => return Ok(FilterExpr::Type(self.type_name()?)),
+            },
+            _ => return Err(self.error(expected)),
+        };
+        self.pos += 1;
+        self.comparison(value)
+    }
+
+    fn comparison(&mut self, value: Value) -> Result<FilterExpr, String> {
+        match self.next() {
+            Some(Token::Cmp(op)) => Ok(FilterExpr::Cmp(value, op, self.number()?)),
+            Some(Token::Ident(name)) if name == "in" => {
+                let from = self.number()?;
+                self.expect(&Token::Range, "'..='")?;
+                let to = self.number()?;
+                Ok(FilterExpr::In(value, from, to))
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.error("a comparison or 'in'"))
+            }
+        }
+    }
+}
+
+impl Value {
+    fn get(self, msg: &MessageCached) -> Option<u64> {
+        let raw = &msg.msg.msg;
+        let byte = |i: usize| (i < raw.dlc).then(|| raw.data[i]);
+        match self {
+            Self::Node => msg.msg.parsed_node_id.map(u64::from),
+            Self::Cob => Some(u64::from(msg.can_id)),
+            Self::Dlc => u64::try_from(raw.dlc).ok(),
+            Self::Byte(i) => byte(i).map(u64::from),
+            Self::Bit(i, bit) => byte(i).map(|b| u64::from((b >> bit) & 1)),
+        }
+    }
+}
+
+impl FilterExpr {
+    /// Parses an expression.
+    ///
+    /// # Errors
+    /// Returns what was expected and the column where it was not found.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            end: s.len(),
+            depth: 0,
+        };
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.error("'&&', '||' or the end"));
+        }
+        Ok(expr)
+    }
+
+    /// Returns `true` if the message matches, comparisons of values the message does not
+    /// have, e.g. the node ID of a SYNC or a byte after the DLC, are false.
+    pub fn matches(&self, msg: &MessageCached) -> bool {
+        match self {
+            Self::Or(a, b) => a.matches(msg) || b.matches(msg),
+            Self::And(a, b) => a.matches(msg) && b.matches(msg),
+            Self::Not(a) => !a.matches(msg),
+            Self::Type(types) => types.contains(&msg.msg.parsed_type),
+            Self::Cmp(value, op, n) => value.get(msg).is_some_and(|v| match op {
+                CmpOp::Eq => v == *n,
+                CmpOp::Ne => v != *n,
+                CmpOp::Lt => v < *n,
+                CmpOp::Le => v <= *n,
+                CmpOp::Gt => v > *n,
+                CmpOp::Ge => v >= *n,
+            }),
+            Self::In(value, from, to) => value.get(msg).is_some_and(|v| (*from..=*to).contains(&v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FilterExpr;
+    use crate::{j1939::J1939Tracker, message_cached::test_message};
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_filter_expr() {
+        let msg = |cob_id, data: &[u8]| test_message(cob_id, data, Instant::now());
+        let pdo4 = &msg(0x184, &[1, 2, 3]);
+        let pdo6 = &msg(0x186, &[1, 2, 3]);
+        let heartbeat = &msg(0x701, &[0x05]);
+        let emcy = &msg(0x085, &[0x10, 0x81, 0, 0, 0, 0, 0, 0]);
+        let sync = &msg(0x080, &[]);
+
+        let expr = FilterExpr::parse(
+            "(type == PDO && node in 3..=5) || (cob == 0x701 && data[0] == 0x05) || emcy",
+        )
+        .unwrap();
+        assert!(expr.matches(pdo4));
+        assert!(!expr.matches(pdo6));
+        assert!(expr.matches(heartbeat));
+        assert!(expr.matches(emcy));
+        assert!(!expr.matches(sync));
+
+        // 29-bit identifier of extended frames
+        let eec1 = &J1939Tracker::default().message(0, 0x0CF0_0400, [0; 8], 8);
+        assert!(FilterExpr::parse("cob == 0xCF00400").unwrap().matches(eec1));
+        assert!(!FilterExpr::parse("cob == 0x400").unwrap().matches(eec1));
+
+        let expr = FilterExpr::parse("!sync && data[1].7 == 1 || dlc > 2 && type != sdo").unwrap();
+        assert!(expr.matches(emcy));
+        assert!(expr.matches(pdo6));
+        assert!(!expr.matches(heartbeat));
+        assert!(!expr.matches(sync));
+
+        // node and bytes after the DLC are missing, so every comparison is false
+        let expr = FilterExpr::parse("node != 1 || data[2] <= 255").unwrap();
+        assert!(!expr.matches(sync));
+        assert!(FilterExpr::parse("!(node != 1)")
+            .unwrap()
+            .matches(heartbeat));
+
+        assert_eq!(
+            FilterExpr::parse("node in 3.5"),
+            Err("column 10: expected '..='".to_owned())
+        );
+        assert_eq!(
+            FilterExpr::parse("node in 3..5"),
+            Err("column 10: expected '..=', ranges include both ends".to_owned())
+        );
+        assert!(FilterExpr::parse(&format!("{}sync", "!".repeat(64))).is_ok());
+        assert_eq!(
+            FilterExpr::parse(&format!("{}sync", "!(".repeat(40))),
+            Err("column 65: too deeply nested".to_owned())
+        );
+        assert_eq!(
+            FilterExpr::parse("(pdo || sdo"),
+            Err("column 12: expected ')'".to_owned())
+        );
+        assert_eq!(
+            FilterExpr::parse("data[8] == 1"),
+            Err("column 6: expected a byte index 0-7".to_owned())
+        );
+        assert_eq!(
+            FilterExpr::parse("cob = 1"),
+            Err("column 5: unexpected '='".to_owned())
+        );
+        assert!(FilterExpr::parse("type == foo").is_err());
+        assert!(FilterExpr::parse("pdo sdo").is_err());
+    }
+}
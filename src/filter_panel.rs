@@ -9,6 +9,8 @@ pub struct FilterPanel {
     pub global_filter: Rc<RefCell<GlobalFilter>>,
     pub data_panel: FilterDataPanel,
     pub stop: bool,
+    /// The filter was changed in the last update.
+    pub changed: bool,
 }
 
 impl FilterPanel {
@@ -18,6 +20,7 @@ impl FilterPanel {
             data_panel,
             global_filter,
             stop: false,
+            changed: false,
         }
     }
 
@@ -55,6 +58,18 @@ impl FilterPanel {
                     }
                 }
             }
+
+            ui.separator();
+            changed |= ui
+                .checkbox(
+                    &mut self.global_filter.borrow_mut().isotp_frames,
+                    "ISO-TP frames",
+                )
+                .on_hover_text(
+                    "Show every frame of segmented ISO-TP messages, otherwise only the frame \
+                     completing a message is shown with the whole UDS message",
+                )
+                .changed();
         });
 
         let mut to_add_fixed_filter: Option<FilterDataPanel> = None;
@@ -71,6 +86,7 @@ impl FilterPanel {
             }
         });
 
+        self.changed = changed;
         to_add_fixed_filter
     }
 }
@@ -0,0 +1,135 @@
+use crate::{
+    cob_stats::{CobStatistics, CobStats, SortColumn},
+    filter::GlobalFilter,
+    message_cached::MessageCached,
+    message_row::MessageRow,
+    theme::OZON_PINK,
+};
+use egui::text::{LayoutJob, TextFormat};
+use oze_canopen::canopen::RxMessageToStringFormat;
+
+const COLUMNS: [(SortColumn, &str); 11] = [
+    (SortColumn::CobId, "COB ID"),
+    (SortColumn::Type, "Packet type"),
+    (SortColumn::NodeId, "Node ID"),
+    (SortColumn::Count, "Count"),
+    (SortColumn::Data, "Data"),
+    (SortColumn::MeanPeriod, "Period ms"),
+    (SortColumn::MinPeriod, "Min ms"),
+    (SortColumn::MaxPeriod, "Max ms"),
+    (SortColumn::Jitter, "Jitter ms"),
+    (SortColumn::Timestamp, "Last timestamp"),
+    (SortColumn::Info, "Info"),
+];
+
+/// One row per COB ID updated in place, like the receive window of PCAN-View.
+#[derive(Debug, Default)]
+pub struct FixedTrace {
+    stats: CobStatistics,
+    sort: SortColumn,
+    descending: bool,
+}
+
+fn period(ui: &mut egui::Ui, seconds: Option<f64>) {
+    ui.label(seconds.map_or_else(|| "-".to_owned(), |s| format!("{:.3}", s * 1000.0)));
+}
+
+/// Hex data with the bytes changed since the previous message highlighted.
+fn hex_data(ui: &mut egui::Ui, stats: &CobStats) {
+    let font_id = egui::TextStyle::Body.resolve(ui.style());
+    let color = ui.visuals().text_color();
+    let mut job = LayoutJob::default();
+    let data = &stats.last.msg.msg.data[..stats.last.msg.msg.dlc.min(8)];
+    for (i, byte) in data.iter().enumerate() {
+        let color = if stats.changed(i) { OZON_PINK } else { color };
+        let separator = if i == 0 { "" } else { " " };
+        job.append(
+            &format!("{separator}{byte:02X}"),
+            0.0,
+            TextFormat::simple(font_id.clone(), color),
+        );
+    }
+    ui.label(job);
+}
+
+impl FixedTrace {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        self.stats.push_data(msg);
+    }
+
+    fn header(&mut self, ui: &mut egui::Ui) {
+        for (column, name) in COLUMNS {
+            let selected = self.sort == column;
+            let arrow = match (selected, self.descending) {
+                (false, _) => "",
+                (true, false) => " ⏶",
+                (true, true) => " ⏷",
+            };
+            if ui
+                .selectable_label(selected, format!("{name}{arrow}"))
+                .on_hover_text("Sort by this column")
+                .clicked()
+            {
+                self.descending = selected && !self.descending;
+                self.sort = column;
+            }
+        }
+    }
+
+    fn row(ui: &mut egui::Ui, stats: &CobStats, message_row: &MessageRow) {
+        let d = &stats.last;
+        ui.label(&d.cob_str);
+        ui.label(d.msg.parsed_type.to_string());
+        ui.label(
+            d.msg
+                .parsed_node_id
+                .map_or_else(|| "   ".to_owned(), |n| format!("{n:3}")),
+        );
+        ui.label(stats.count.to_string());
+        if message_row.format == RxMessageToStringFormat::Hex {
+            hex_data(ui, stats);
+        } else {
+            ui.label(d.get_by_format(message_row.format));
+        }
+        period(ui, stats.mean_period());
+        period(ui, stats.min_period);
+        period(ui, stats.max_period);
+        period(ui, stats.jitter());
+        let time = stats
+            .last_timestamp()
+            .duration_since(message_row.start_time)
+            .as_secs_f32();
+        ui.label(format!("{time:.6}"));
+        ui.label(&d.info_str)
+            .on_hover_text_at_pointer(d.additional.get_tooltip());
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui, message_row: &MessageRow, filter: &GlobalFilter) {
+        ui.horizontal(|ui| {
+            ui.label(format!("COB IDs={}", self.stats.len()));
+            if ui.button("🗑").on_hover_text("Clear statistics").clicked() {
+                self.stats.clear();
+            }
+        });
+
+        egui::ScrollArea::vertical()
+            .id_salt("fixed_trace")
+            .show(ui, |ui| {
+                egui::Grid::new("fixed_trace_grid")
+                    .striped(true)
+                    .spacing([20.0, 4.0])
+                    .show(ui, |ui| {
+                        self.header(ui);
+                        ui.end_row();
+
+                        for stats in self.stats.sorted(self.sort, self.descending) {
+                            if filter.filter(&stats.last) {
+                                continue;
+                            }
+                            Self::row(ui, stats, message_row);
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
@@ -0,0 +1,145 @@
+use crate::series::excess;
+use tokio::time::Instant;
+
+/// Width of one bucket of the frame rate in seconds.
+pub const BUCKET_SECONDS: f64 = 0.1;
+/// Buckets kept, one hour like the bitrate.
+const MAX_BUCKETS: usize = 36_000;
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn bucket_index(start: Instant, time: Instant) -> u64 {
+    let seconds = time.saturating_duration_since(start).as_secs_f64();
+    (seconds / BUCKET_SECONDS) as u64
+}
+
+/// Frames per second over time, of all frames and of the frames passing the filter.
+#[derive(Debug)]
+pub struct FrameRate {
+    start: Instant,
+    /// Seconds since `start` of the bucket and frames per second.
+    total: Vec<[f64; 2]>,
+    /// Same buckets as `total`.
+    filtered: Vec<[f64; 2]>,
+    /// Index of the newest bucket.
+    last: Option<u64>,
+}
+
+impl FrameRate {
+    pub fn new(start: Instant) -> Self {
+        Self {
+            start,
+            total: Vec::new(),
+            filtered: Vec::new(),
+            last: None,
+        }
+    }
+
+    /// Adds empty buckets for the silent period up to the bucket of the time.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn advance(&mut self, time: Instant) {
+        let index = bucket_index(self.start, time);
+        if self.last.is_some_and(|last| index <= last) {
+            return;
+        }
+        // at most the kept buckets
+        let first = self.last.map_or(index, |last| {
+            (last + 1).max(index.saturating_sub(MAX_BUCKETS as u64))
+        });
+        for i in first..=index {
+            let time = i as f64 * BUCKET_SECONDS;
+            self.total.push([time, 0.0]);
+            self.filtered.push([time, 0.0]);
+        }
+        self.last = Some(index);
+        let excess = excess(self.total.len(), MAX_BUCKETS);
+        self.total.drain(..excess);
+        self.filtered.drain(..excess);
+    }
+
+    /// Counts the frame, late frames are counted in the newest bucket.
+    pub fn push(&mut self, time: Instant, passed_filter: bool) {
+        self.advance(time);
+        let rate = 1.0 / BUCKET_SECONDS;
+        if let Some(bucket) = self.total.last_mut() {
+            bucket[1] += rate;
+        }
+        if let (true, Some(bucket)) = (passed_filter, self.filtered.last_mut()) {
+            bucket[1] += rate;
+        }
+    }
+
+    /// Recounts the frames passing a changed filter from the receive times of the kept frames
+    /// passing it, buckets before the `oldest` kept frame keep their counts.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn refilter(&mut self, oldest: Instant, passed: impl IntoIterator<Item = Instant>) {
+        let Some(last) = self.last else {
+            return;
+        };
+        let first = last + 1 - self.filtered.len() as u64;
+        let start = self.start;
+        let position = |time| {
+            let index = bucket_index(start, time);
+            // late frames are counted in the newest bucket
+            (index >= first).then(|| (index.min(last) - first) as usize)
+        };
+
+        let from = position(oldest).unwrap_or(0);
+        for bucket in &mut self.filtered[from..] {
+            bucket[1] = 0.0;
+        }
+        for time in passed {
+            if let Some(n) = position(time) {
+                self.filtered[n][1] += 1.0 / BUCKET_SECONDS;
+            }
+        }
+    }
+
+    /// Buckets up to the newest one, which is still filling when advanced to now.
+    pub fn total(&self) -> &[[f64; 2]] {
+        &self.total
+    }
+
+    pub fn filtered(&self) -> &[[f64; 2]] {
+        &self.filtered
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameRate;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_frame_rate() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut rate = FrameRate::new(start);
+        rate.push(at(10), true);
+        rate.push(at(50), false);
+        rate.push(at(350), true);
+        // late frame
+        rate.push(at(300), false);
+
+        let total: Vec<f64> = rate.total().iter().map(|b| b[1].round()).collect();
+        let filtered: Vec<f64> = rate.filtered().iter().map(|b| b[1].round()).collect();
+        assert_eq!(total, [20.0, 0.0, 0.0, 20.0]);
+        assert_eq!(filtered, [10.0, 0.0, 0.0, 10.0]);
+        assert!((rate.total()[3][0] - 0.3).abs() < 1e-9);
+
+        // silence until now
+        rate.advance(at(650));
+        let total: Vec<f64> = rate.total().iter().map(|b| b[1].round()).collect();
+        assert_eq!(total, [20.0, 0.0, 0.0, 20.0, 0.0, 0.0, 0.0]);
+        assert_eq!(rate.filtered().len(), 7);
+
+        // frames before the oldest kept one keep the old filter result
+        rate.refilter(at(300), [at(350), at(360)]);
+        let filtered: Vec<f64> = rate.filtered().iter().map(|b| b[1].round()).collect();
+        assert_eq!(filtered, [10.0, 0.0, 0.0, 20.0, 0.0, 0.0, 0.0]);
+    }
+}
@@ -1,31 +1,44 @@
 use crate::{
     bitrate::RatesData,
+    bus_load_panel::BusLoadPanel,
     chart::{self, Chart},
-    driver::{Control, ControlCommand, State},
+    drive_panel::DrivePanel,
+    driver::{Control, ControlCommand, Protocol, State},
+    field_layout_panel::FieldLayoutPanel,
     filter::GlobalFilter,
     filter_panel::FilterPanel,
-    message_cached::MessageCached,
+    history::History,
+    io_panel::IoPanel,
+    message_cached::RxMessageAdditional,
+    node_panel::NodePanel,
     pinned_filter::PinnedFilters,
+    sdo_panel::SdoPanel,
+    signal_panel::SignalPanel,
+    supervision_panel::SupervisionPanel,
     theme::{theme, OZON_GRAY, OZON_PINK},
-    viewer::Viewer,
+    timing_panel::TimingPanel,
+    viewer::{ViewMode, Viewer},
 };
 use egui::{emath::Numeric, Button, Layout, TextEdit, Ui};
 use oze_canopen::{
     canopen::RxMessageToStringFormat,
     interface::{CanOpenInfo, Connection},
 };
-use std::{cell::RefCell, collections::VecDeque, rc::Rc, sync::Arc};
-use tokio::{
-    sync::{watch, Mutex},
-    time::Instant,
-};
-
-const MESSAGES_COUNT: usize = 4096;
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+use tokio::{sync::watch, time::Instant};
 
 pub struct Gui {
-    data: VecDeque<MessageCached>,
+    history: History,
     driver: watch::Receiver<State>,
     pinned_filters: PinnedFilters,
+    sdo_panel: SdoPanel,
+    node_panel: NodePanel,
+    supervision_panel: SupervisionPanel,
+    timing_panel: TimingPanel,
+    bus_load_panel: BusLoadPanel,
+    pub signal_panel: SignalPanel,
+    drive_panel: DrivePanel,
+    io_panel: IoPanel,
     viewer: Viewer,
     chart: chart::Chart,
     last: Instant,
@@ -34,13 +47,16 @@ pub struct Gui {
     filter_panel: FilterPanel,
 
     format: RxMessageToStringFormat,
+    pub field_layout_panel: FieldLayoutPanel,
 
     can_name_raw: String,
     bitrate_raw: String,
 
     info: CanOpenInfo,
+    extended_error: Option<String>,
 
     connection: Connection,
+    protocol: Protocol,
     stopped: bool,
     driver_ctrl: watch::Sender<Control>,
 }
@@ -50,35 +66,67 @@ impl Gui {
         cc: &eframe::CreationContext<'_>,
         driver: watch::Receiver<State>,
         driver_ctrl: watch::Sender<Control>,
-        bitrate: Arc<Mutex<RatesData>>,
+        bitrate: RatesData,
     ) -> Self {
         theme(&cc.egui_ctx);
 
         let global_filter = Rc::new(RefCell::new(GlobalFilter::default()));
         let connection_data = driver_ctrl.subscribe().borrow().connection.clone();
+        let protocol = driver_ctrl.subscribe().borrow().protocol;
         let can_name_raw = connection_data.can_name.clone();
         let bitrate_raw = connection_data
             .bitrate
             .map(|b| b.to_string())
             .unwrap_or_default();
 
-        Self {
+        let chart = Chart::new(bitrate);
+        let start_time = chart.start();
+        let mut gui = Self {
             fps: VecDeque::new(),
-            data: VecDeque::new(),
+            history: History::default(),
             pinned_filters: PinnedFilters::default(),
+            sdo_panel: SdoPanel::default(),
+            node_panel: NodePanel::default(),
+            supervision_panel: SupervisionPanel::default(),
+            timing_panel: TimingPanel::default(),
+            bus_load_panel: BusLoadPanel::default(),
+            signal_panel: SignalPanel::default(),
+            drive_panel: DrivePanel::default(),
+            io_panel: IoPanel::default(),
             info: CanOpenInfo::default(),
+            extended_error: None,
             connection: connection_data,
+            protocol,
             format: RxMessageToStringFormat::Hex,
+            field_layout_panel: FieldLayoutPanel::default(),
             viewer: Viewer::new(global_filter.clone()),
             filter_panel: FilterPanel::new(global_filter.clone()),
             last: Instant::now(),
-            chart: Chart::new(bitrate),
+            chart,
             stopped: false,
             global_filter,
             can_name_raw,
             bitrate_raw,
             driver_ctrl,
             driver,
+        };
+        // timestamps relative to the start of the chart
+        gui.viewer.message_row.start_time = start_time;
+        gui.pinned_filters.message_row.start_time = start_time;
+        gui.sdo_panel.message_row.start_time = start_time;
+        gui.drive_panel.start_time = start_time;
+        gui.supervision_panel.start_time = start_time;
+        gui.signal_panel.recorder.start = start_time;
+        gui
+    }
+
+    /// Applies a changed filter to the message list and the filtered frame rate.
+    fn refilter(&mut self) {
+        self.history.refilter(&self.global_filter.borrow());
+        if let Some(oldest) = self.history.oldest() {
+            let passed =
+                (0..self.history.filtered_len()).map(|n| self.history.filtered(n).get_timestamp());
+            self.chart.refilter_frames(oldest.get_timestamp(), passed);
         }
     }
 
@@ -90,29 +138,45 @@ impl Gui {
                 ControlCommand::Process
             },
             connection: self.connection.clone(),
+            protocol: self.protocol,
         });
     }
 
     fn get_data_from_driver(&mut self) -> bool {
         let driver = self.driver.borrow();
         for i in &driver.data {
-            if let Some(last) = self.data.front() {
+            if let Some(last) = self.history.newest() {
                 if i.index <= last.index {
                     continue;
                 }
             }
 
             self.pinned_filters.push_data(i);
-            if !self.global_filter.borrow().filter(i) {
-                self.data.push_front(i.clone());
+            self.viewer.push_data(i);
+            self.node_panel.push_data(i);
+            self.supervision_panel.push_data(i);
+            self.timing_panel.push_data(i);
+            self.bus_load_panel.push_data(i);
+            self.signal_panel.push_data(i);
+            self.drive_panel.push_data(i);
+            self.io_panel.push_data(i);
+            if let Some(transfer) = self.sdo_panel.push_data(i) {
+                self.node_panel.push_transfer(transfer);
+                self.supervision_panel.push_transfer(transfer);
+                self.drive_panel.push_transfer(transfer);
+                self.io_panel.push_transfer(transfer);
+            }
+            let passed = !self.global_filter.borrow().filter(i);
+            self.chart.push_frame(i.get_timestamp(), passed);
+            self.history.push(i.clone(), passed);
+            if let RxMessageAdditional::IsoTp(info) = &i.additional {
+                self.history
+                    .fold(&info.segments, &self.global_filter.borrow());
             }
-        }
-
-        while self.data.len() > MESSAGES_COUNT {
-            self.data.pop_back();
         }
 
         self.info = driver.info.clone();
+        self.extended_error.clone_from(&driver.extended_error);
 
         driver.exit_signal
     }
@@ -157,6 +221,22 @@ impl Gui {
             self.connection.bitrate = bitrate;
             self.send_driver_control();
         }
+
+        let before = self.protocol;
+        egui::ComboBox::from_id_salt("protocol")
+            .selected_text(match self.protocol {
+                Protocol::CanOpen => "CANopen",
+                Protocol::J1939 => "CANopen + J1939",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.protocol, Protocol::CanOpen, "CANopen");
+                ui.selectable_value(&mut self.protocol, Protocol::J1939, "CANopen + J1939");
+            })
+            .response
+            .on_hover_text("J1939 mode also receives 29-bit frames and decodes them as J1939");
+        if before != self.protocol {
+            self.send_driver_control();
+        }
     }
 
     fn show_format_ui(&mut self, ui: &mut Ui) {
@@ -181,6 +261,60 @@ impl Gui {
         {
             self.format = RxMessageToStringFormat::Ascii;
         }
+        ui.separator();
+        if ui
+            .selectable_label(self.field_layout_panel.show, "fields")
+            .on_hover_text("Show message data decoded with the field layouts in an extra column")
+            .clicked()
+        {
+            self.field_layout_panel.show = !self.field_layout_panel.show;
+        }
+        if ui
+            .selectable_label(self.field_layout_panel.open, "✏")
+            .on_hover_text("Edit field layouts")
+            .clicked()
+        {
+            self.field_layout_panel.open = !self.field_layout_panel.open;
+        }
+    }
+
+    fn show_view_mode_ui(&mut self, ui: &mut Ui) {
+        ui.selectable_value(&mut self.viewer.mode, ViewMode::List, "list")
+            .on_hover_text("Show messages chronologically");
+        ui.selectable_value(&mut self.viewer.mode, ViewMode::Fixed, "fixed")
+            .on_hover_text("Show one row per COB ID with period statistics, updated in place");
+    }
+
+    /// Collapsible analysis panels between the filters and the message list.
+    fn show_panels(&mut self, ui: &mut Ui) {
+        egui::CollapsingHeader::new("Nodes").show(ui, |ui| {
+            if let Some(node_id) = self.node_panel.update(ui) {
+                self.filter_panel.data_panel.set_node_id(node_id);
+                self.refilter();
+            }
+        });
+        egui::CollapsingHeader::new("Supervision").show(ui, |ui| {
+            self.supervision_panel.update(ui);
+        });
+        egui::CollapsingHeader::new("Timing analysis").show(ui, |ui| {
+            self.timing_panel.update(ui);
+        });
+        egui::CollapsingHeader::new("Bus load").show(ui, |ui| {
+            self.bus_load_panel.update(ui);
+        });
+        egui::CollapsingHeader::new("Signals").show(ui, |ui| {
+            self.signal_panel
+                .update(ui, &self.field_layout_panel.layouts);
+        });
+        egui::CollapsingHeader::new("SDO transfers").show(ui, |ui| {
+            self.sdo_panel.update(ui);
+        });
+        egui::CollapsingHeader::new("Drives (CiA 402)").show(ui, |ui| {
+            self.drive_panel.update(ui);
+        });
+        egui::CollapsingHeader::new("I/O modules (CiA 401)").show(ui, |ui| {
+            self.io_panel.update(ui);
+        });
     }
 
     fn show_connection_help(ui: &mut Ui) {
@@ -206,6 +340,10 @@ impl eframe::App for Gui {
             ctx.request_repaint();
             return;
         }
+        // messages are not received while stopped, deadlines can't be supervised
+        if !self.stopped {
+            self.supervision_panel.supervisor.check(Instant::now());
+        }
 
         egui::TopBottomPanel::top("top").show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -215,16 +353,35 @@ impl eframe::App for Gui {
                 self.show_format_ui(ui);
                 ui.separator();
 
+                self.show_view_mode_ui(ui);
+                ui.separator();
+
                 ui.label(format!(
                     "rx {} tx {}",
                     self.info.receiver_socket, self.info.transmitter_socket,
                 ));
+                if let Some(e) = &self.extended_error {
+                    ui.colored_label(OZON_PINK, "⚠ J1939")
+                        .on_hover_text(format!("Socket for extended frames failed: {e}"));
+                }
 
                 ui.separator();
-                ui.label(format!("packets={}", self.data.len()));
+                ui.label(format!(
+                    "packets={}/{}",
+                    self.history.filtered_len(),
+                    self.history.len()
+                ))
+                .on_hover_text("Messages passing the filter / all kept messages");
+
+                let alarms = self.supervision_panel.supervisor.unacknowledged();
+                if alarms > 0 {
+                    ui.separator();
+                    ui.colored_label(OZON_PINK, format!("⚠ {alarms} alarms"))
+                        .on_hover_text("Unacknowledged supervision alarms, see Supervision");
+                }
 
                 ui.with_layout(Layout::right_to_left(egui::Align::RIGHT), |ui| {
-                    ui.label(format!("{fps} FPS",));
+                    ui.label(format!("{fps} FPS"));
                 });
             });
 
@@ -235,23 +392,44 @@ impl eframe::App for Gui {
 
         self.viewer.message_row.format = self.format;
         self.pinned_filters.message_row.format = self.format;
+        self.sdo_panel.message_row.format = self.format;
+        let fields = self
+            .field_layout_panel
+            .show
+            .then(|| self.field_layout_panel.layouts.clone());
+        self.viewer.message_row.fields.clone_from(&fields);
+        self.pinned_filters.message_row.fields.clone_from(&fields);
+        self.sdo_panel.message_row.fields = fields;
+        self.field_layout_panel.update(ctx);
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.add_enabled_ui(connected, |ui| {
-                self.chart.ui(ui);
+                self.chart.set_connection(&self.connection);
+                if self.chart.ui(ui) {
+                    self.viewer.highlight(self.chart.cursor());
+                }
+                self.viewer.time_range = self.chart.selection();
                 ui.separator();
                 let to_pin = self.filter_panel.update(ui);
                 if self.stopped != self.filter_panel.stop {
                     self.stopped = self.filter_panel.stop;
+                    if !self.stopped {
+                        self.supervision_panel.supervisor.resume(Instant::now());
+                    }
                     self.send_driver_control();
                 }
+                if self.filter_panel.changed {
+                    self.refilter();
+                }
                 if let Some(to_pin) = to_pin {
-                    self.pinned_filters.pin_filter(to_pin, &self.data);
+                    self.pinned_filters.pin_filter(to_pin, &self.history);
                 }
 
                 ui.separator();
                 self.pinned_filters.update(ui);
                 ui.separator();
-                self.viewer.update(ui, &self.data);
+                self.show_panels(ui);
+                ui.separator();
+                self.viewer.update(ui, &self.history);
             });
         });
 
@@ -0,0 +1,82 @@
+use core::fmt;
+
+const TOGGLE_BIT: u8 = 0b1000_0000;
+
+/// NMT state reported by heartbeat, boot-up and node guarding messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NmtState {
+    BootUp,
+    Stopped,
+    Operational,
+    PreOperational,
+    Unknown(u8),
+}
+
+/// Heartbeat, boot-up or node guarding response (COB-ID 0x700 + node ID).
+///
+/// Node guarding responses are answers to RTR frames which are not received by the viewer,
+/// so a guarding response is recognized only by its toggle bit. Responses with the toggle bit
+/// cleared are indistinguishable from heartbeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    pub state: NmtState,
+    pub toggle: bool,
+}
+
+impl NmtState {
+    /// States which can be selected in filters.
+    pub const ALL: [NmtState; 4] = [
+        NmtState::BootUp,
+        NmtState::Stopped,
+        NmtState::Operational,
+        NmtState::PreOperational,
+    ];
+
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            0x00 => NmtState::BootUp,
+            0x04 => NmtState::Stopped,
+            0x05 => NmtState::Operational,
+            0x7F => NmtState::PreOperational,
+            b => NmtState::Unknown(b),
+        }
+    }
+}
+
+impl fmt::Display for NmtState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NmtState::BootUp => write!(f, "Boot-up"),
+            NmtState::Stopped => write!(f, "Stopped"),
+            NmtState::Operational => write!(f, "Operational"),
+            NmtState::PreOperational => write!(f, "Pre-operational"),
+            NmtState::Unknown(b) => write!(f, "Unknown state {b:02X}"),
+        }
+    }
+}
+
+impl Heartbeat {
+    /// Parses the message data, which has to be exactly one byte.
+    pub fn new(data: &[u8]) -> Option<Self> {
+        let [byte] = data else {
+            return None;
+        };
+
+        Some(Self {
+            state: NmtState::from_byte(byte & !TOGGLE_BIT),
+            toggle: byte & TOGGLE_BIT != 0,
+        })
+    }
+}
+
+impl fmt::Display for Heartbeat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.toggle {
+            write!(f, "Guard     {} toggle=1", self.state)
+        } else if self.state == NmtState::BootUp {
+            write!(f, "Boot-up")
+        } else {
+            write!(f, "Heartbeat {}", self.state)
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    filter::GlobalFilter,
+    message_cached::{MessageCached, RxMessageAdditional},
+    series::excess,
+};
+use std::{collections::VecDeque, ops::Range};
+use tokio::time::Instant;
+
+/// Messages kept, about 100 MB of decoded messages.
+const MAX_MESSAGES: usize = 300_000;
+
+/// Every received message with the positions of the ones passing the global filter.
+///
+/// The filter only selects what is shown, so changing it shows older messages again.
+#[derive(Debug, Default)]
+pub struct History {
+    /// Oldest first.
+    messages: VecDeque<MessageCached>,
+    /// Messages dropped from the front, the sequence number of `messages[0]`.
+    dropped: u64,
+    /// Sequence numbers of the messages passing the filter, oldest first.
+    filtered: VecDeque<u64>,
+}
+
+impl History {
+    /// Stores the message, `passed` is the result of the current global filter.
+    pub fn push(&mut self, msg: MessageCached, passed: bool) {
+        if passed {
+            self.filtered
+                .push_back(self.dropped + self.messages.len() as u64);
+        }
+        self.messages.push_back(msg);
+        let excess = excess(self.messages.len(), MAX_MESSAGES);
+        if excess > 0 {
+            self.messages.drain(..excess);
+            self.dropped += excess as u64;
+            while self.filtered.front().is_some_and(|s| *s < self.dropped) {
+                self.filtered.pop_front();
+            }
+        }
+    }
+
+    /// Applies a changed filter to all stored messages.
+    pub fn refilter(&mut self, filter: &GlobalFilter) {
+        let dropped = self.dropped;
+        self.filtered = self
+            .messages
+            .iter()
+            .zip(dropped..)
+            .filter(|(msg, _)| !filter.filter(msg))
+            .map(|(_, sequence)| sequence)
+            .collect();
+    }
+
+    /// Folds frames of a completed ISO-TP message into the row of the frame completing it.
+    pub fn fold(&mut self, indices: &[u64], filter: &GlobalFilter) {
+        for index in indices {
+            let position = self.messages.partition_point(|m| m.index < *index);
+            let Some(msg) = self.messages.get_mut(position) else {
+                continue;
+            };
+            let RxMessageAdditional::IsoTp(info) = &mut msg.additional else {
+                continue;
+            };
+            info.folded = true;
+            if filter.filter(msg) {
+                let sequence = self.dropped + position as u64;
+                if let Ok(n) = self.filtered.binary_search(&sequence) {
+                    self.filtered.remove(n);
+                }
+            }
+        }
+    }
+
+    pub fn newest(&self) -> Option<&MessageCached> {
+        self.messages.back()
+    }
+
+    pub fn oldest(&self) -> Option<&MessageCached> {
+        self.messages.front()
+    }
+
+    /// All messages, newest first.
+    pub fn iter(&self) -> impl Iterator<Item = &MessageCached> {
+        self.messages.iter().rev()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn filtered_len(&self) -> usize {
+        self.filtered.len()
+    }
+
+    /// Message passing the filter at the position, oldest first.
+    pub fn filtered(&self, position: usize) -> &MessageCached {
+        self.message(self.filtered[position])
+    }
+
+    /// Position of the first filtered message received at or after the time.
+    pub fn filtered_position(&self, time: Instant) -> usize {
+        self.filtered
+            .partition_point(|s| self.message(*s).get_timestamp() < time)
+    }
+
+    /// Positions of the filtered messages received in the range, all without a range.
+    pub fn filtered_range(&self, range: Option<(Instant, Instant)>) -> Range<usize> {
+        range.map_or(0..self.filtered.len(), |(from, to)| {
+            let start = self.filtered_position(from);
+            let end = self
+                .filtered
+                .partition_point(|s| self.message(*s).get_timestamp() <= to);
+            start..end.max(start)
+        })
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn message(&self, sequence: u64) -> &MessageCached {
+        &self.messages[(sequence - self.dropped) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{History, MAX_MESSAGES};
+    use crate::{
+        filter::{GlobalFilter, NodeSet},
+        isotp::IsoTpTracker,
+        message_cached::{test_message, MessageCached, RxMessageAdditional},
+    };
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_history() {
+        let start = Instant::now();
+        let msg = |index: u64| MessageCached {
+            index,
+            ..test_message(
+                0x181 + u16::try_from(index % 2).unwrap(),
+                &[0; 8],
+                start + Duration::from_millis(index),
+            )
+        };
+        let node1 = GlobalFilter::default();
+        node1.data.borrow_mut().node_id = Some(NodeSet::single(1));
+        let all = GlobalFilter::default();
+
+        let mut history = History::default();
+        for index in 0..10 {
+            history.push(msg(index), !node1.filter(&msg(index)));
+        }
+        assert_eq!(history.len(), 10);
+        assert_eq!(history.filtered_len(), 5);
+        assert_eq!(history.filtered(1).index, 2);
+
+        // loosening the filter brings back the other node
+        history.refilter(&all);
+        assert_eq!(history.filtered_len(), 10);
+        let at = |ms| start + Duration::from_millis(ms);
+        assert_eq!(history.filtered_range(Some((at(3), at(5)))), 3..6);
+        assert_eq!(history.filtered_range(Some((at(20), at(30)))), 10..10);
+
+        for index in 10..=(MAX_MESSAGES as u64) {
+            history.push(msg(index), index % 2 == 0);
+        }
+        assert_eq!(history.len(), MAX_MESSAGES - MAX_MESSAGES / 10);
+        assert_eq!(history.newest().map(|m| m.index), Some(MAX_MESSAGES as u64));
+        let oldest = history.iter().last().unwrap().index;
+        assert_eq!(history.filtered(0).index, oldest + oldest % 2);
+    }
+
+    #[test]
+    fn test_isotp_folding() {
+        let mut tracker = IsoTpTracker::default();
+        tracker.add_pair("7E0:7E8".parse().unwrap());
+        let mut filter = GlobalFilter::default();
+        let mut history = History::default();
+        let frames = [
+            (0x7E8, [0x10, 0x0A, 0x62, 0xF1, 0x90, b'W', b'0', b'L']),
+            (0x7E0, [0x30, 0x00, 0x0A, 0, 0, 0, 0, 0]),
+            (0x7E8, [0x21, b'V', b'W', b'Z', b'Z', 0x55, 0x55, 0x55]),
+        ];
+        for (index, (cob_id, data)) in (0..).zip(frames) {
+            let mut msg = MessageCached {
+                index,
+                ..test_message(cob_id, &data, Instant::now())
+            };
+            tracker.process(&mut msg);
+            history.push(msg.clone(), !filter.filter(&msg));
+            if let RxMessageAdditional::IsoTp(info) = &msg.additional {
+                history.fold(&info.segments, &filter);
+            }
+        }
+        assert_eq!(history.filtered_len(), 1);
+        assert_eq!(history.filtered(0).index, 2);
+
+        filter.isotp_frames = true;
+        history.refilter(&filter);
+        assert_eq!(history.filtered_len(), 3);
+    }
+}
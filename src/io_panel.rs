@@ -0,0 +1,122 @@
+use crate::{
+    cia401::{IoModules, IoNode, ANALOG_INPUTS, ANALOG_OUTPUTS, DIGITAL_INPUTS, DIGITAL_OUTPUTS},
+    message_cached::MessageCached,
+    sdo_session::SdoTransfer,
+    theme::{OZON_BLUE, OZON_PINK},
+};
+use egui::{CollapsingHeader, Color32, ProgressBar, Sense, TextEdit};
+use std::collections::BTreeMap;
+
+const LED_SIZE: f32 = 12.0;
+
+/// Shows digital and analog I/O of `CiA 401` modules as live indicators.
+#[derive(Debug, Default)]
+pub struct IoPanel {
+    modules: IoModules,
+    node_raw: String,
+}
+
+/// Draws 8 indicators for one byte of digital I/O, bit 0 first.
+fn leds(ui: &mut egui::Ui, byte: u8) {
+    for bit in 0..8 {
+        let (rect, response) =
+            ui.allocate_exact_size(egui::vec2(LED_SIZE, LED_SIZE), Sense::hover());
+        let on = byte & (1 << bit) != 0;
+        let color = if on { OZON_PINK } else { Color32::DARK_GRAY };
+        ui.painter()
+            .circle_filled(rect.center(), LED_SIZE / 2.0 - 1.0, color);
+        response.on_hover_text(format!("bit {bit} = {}", u8::from(on)));
+    }
+}
+
+fn show_digital(ui: &mut egui::Ui, name: &str, index: u16, values: &BTreeMap<u8, u8>) {
+    for (subindex, byte) in values {
+        ui.label(format!("{name} {index:04X}:{subindex:02X}"));
+        ui.horizontal(|ui| leds(ui, *byte));
+        ui.label(format!("{byte:02X}"));
+        ui.end_row();
+    }
+}
+
+fn show_analog(ui: &mut egui::Ui, name: &str, index: u16, values: &BTreeMap<u8, i16>) {
+    for (subindex, value) in values {
+        ui.label(format!("{name} {index:04X}:{subindex:02X}"));
+        let fill = (f32::from(*value) - f32::from(i16::MIN)) / f32::from(u16::MAX);
+        ui.add(
+            ProgressBar::new(fill)
+                .desired_width(8.0 * (LED_SIZE + ui.spacing().item_spacing.x))
+                .fill(OZON_BLUE),
+        );
+        ui.label(value.to_string());
+        ui.end_row();
+    }
+}
+
+impl IoPanel {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        self.modules.push_data(msg);
+    }
+
+    pub fn push_transfer(&mut self, transfer: &SdoTransfer) {
+        self.modules.push_transfer(transfer);
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("modules={}", self.modules.len()));
+            if ui.button("🗑").on_hover_text("Clear I/O states").clicked() {
+                self.modules.clear();
+            }
+            ui.separator();
+            ui.add(
+                TextEdit::singleline(&mut self.node_raw)
+                    .hint_text("nodeID")
+                    .desired_width(55.0),
+            );
+            let node_id = self.node_raw.parse().ok();
+            if ui
+                .add_enabled(
+                    node_id.is_some(),
+                    egui::Button::new("➕ default PDO layout"),
+                )
+                .on_hover_text(
+                    "Decode PDOs of this node with the default CiA 401 layout. \
+                     Nodes reporting device profile 401 in object 0x1000 are added automatically.",
+                )
+                .clicked()
+            {
+                if let Some(node_id) = node_id {
+                    self.modules.use_default_layout(node_id);
+                }
+            }
+        });
+
+        egui::ScrollArea::vertical()
+            .id_salt("io_modules")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for node in self.modules.iter() {
+                    let layout = if self.modules.uses_default_layout(node.node_id) {
+                        " (default PDO layout)"
+                    } else {
+                        ""
+                    };
+                    CollapsingHeader::new(format!("node {:3}{layout}", node.node_id))
+                        .id_salt(("io_node", node.node_id))
+                        .default_open(true)
+                        .show(ui, |ui| Self::show_node(ui, node));
+                }
+            });
+    }
+
+    fn show_node(ui: &mut egui::Ui, node: &IoNode) {
+        egui::Grid::new(("io_grid", node.node_id))
+            .striped(true)
+            .show(ui, |ui| {
+                show_digital(ui, "DI", DIGITAL_INPUTS, &node.digital_inputs);
+                show_digital(ui, "DO", DIGITAL_OUTPUTS, &node.digital_outputs);
+                show_analog(ui, "AI", ANALOG_INPUTS, &node.analog_inputs);
+                show_analog(ui, "AO", ANALOG_OUTPUTS, &node.analog_outputs);
+            });
+    }
+}
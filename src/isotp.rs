@@ -0,0 +1,357 @@
+use crate::{
+    message_cached::{MessageCached, RxMessageAdditional},
+    uds::UdsMessage,
+};
+use core::fmt;
+use std::{collections::HashMap, str::FromStr};
+
+/// Largest payload accepted from a first frame, the 12 bit length of classic CAN.
+const MAX_PAYLOAD: usize = 4095;
+
+/// Request and response identifiers of one ISO-TP (ISO 15765-2) channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IsoTpPair {
+    pub request: u16,
+    pub response: u16,
+}
+
+impl FromStr for IsoTpPair {
+    type Err = String;
+
+    /// Parses hexadecimal identifiers in the `7E0:7E8` form.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |id: &str| {
+            let id = id.trim();
+            let id = id
+                .strip_prefix("0x")
+                .or_else(|| id.strip_prefix("0X"))
+                .unwrap_or(id);
+            match u16::from_str_radix(id, 16) {
+                Ok(id) if id <= 0x7FF => Ok(id),
+                _ => Err(format!("invalid CAN ID '{id}'")),
+            }
+        };
+        let (request, response) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected REQUEST:RESPONSE, got '{s}'"))?;
+        Ok(IsoTpPair {
+            request: parse(request)?,
+            response: parse(response)?,
+        })
+    }
+}
+
+/// Protocol control information of a single frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsoTpFrame {
+    Single {
+        len: usize,
+    },
+    First {
+        len: usize,
+    },
+    Consecutive {
+        seq: u8,
+    },
+    FlowControl {
+        status: u8,
+        block_size: u8,
+        st_min: u8,
+    },
+}
+
+impl IsoTpFrame {
+    pub fn new(data: &[u8]) -> Option<Self> {
+        let pci = *data.first()?;
+        let low = pci & 0x0F;
+        match pci >> 4 {
+            0 => Some(IsoTpFrame::Single {
+                len: usize::from(low),
+            }),
+            1 => Some(IsoTpFrame::First {
+                len: usize::from(low) << 8 | usize::from(*data.get(1)?),
+            }),
+            2 => Some(IsoTpFrame::Consecutive { seq: low }),
+            3 => Some(IsoTpFrame::FlowControl {
+                status: low,
+                block_size: data.get(1).copied().unwrap_or(0),
+                st_min: data.get(2).copied().unwrap_or(0),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for IsoTpFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IsoTpFrame::Single { len } => write!(f, "SF {len} bytes"),
+            IsoTpFrame::First { len } => write!(f, "FF {len} bytes"),
+            IsoTpFrame::Consecutive { seq } => write!(f, "CF {seq}"),
+            IsoTpFrame::FlowControl {
+                status,
+                block_size,
+                st_min,
+            } => {
+                let status = match status {
+                    0 => "CTS",
+                    1 => "WAIT",
+                    2 => "OVERFLOW",
+                    _ => "invalid",
+                };
+                write!(f, "FC {status} bs={block_size} st={st_min}")
+            }
+        }
+    }
+}
+
+/// Frame of an ISO-TP channel with the UDS message it completed.
+#[derive(Debug, Clone)]
+pub struct IsoTpInfo {
+    pub frame: IsoTpFrame,
+    /// Sent on the request identifier of the pair.
+    pub request: bool,
+    /// Bytes received so far and expected size of a segmented message.
+    pub progress: Option<(usize, usize)>,
+    /// Complete payload, set on the frame finishing the message.
+    pub payload: Option<Vec<u8>>,
+    pub uds: Option<UdsMessage>,
+    pub error: Option<&'static str>,
+    /// Indices of the other frames of the segmented message completed by this frame.
+    pub segments: Vec<u64>,
+    /// Frame of a segmented message, shown as the row of the frame completing it.
+    pub folded: bool,
+}
+
+impl IsoTpInfo {
+    pub fn get_tooltip(&self) -> String {
+        let mut lines = vec![format!(
+            "ISO-TP {} {}",
+            if self.request { "request" } else { "response" },
+            self.frame
+        )];
+        if let Some((received, size)) = self.progress {
+            lines.push(format!("received {received}/{size} bytes"));
+        }
+        if let Some(payload) = &self.payload {
+            lines.push(format!("payload {payload:02X?}"));
+        }
+        if let Some(error) = self.error {
+            lines.push(format!("error: {error}"));
+        }
+        lines.join("\n")
+    }
+}
+
+impl fmt::Display for IsoTpInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(error) = self.error {
+            return write!(f, "ISO-TP {} {error}", self.frame);
+        }
+        match (&self.uds, self.frame) {
+            (Some(uds), IsoTpFrame::Single { .. }) => write!(f, "{uds}"),
+            (Some(uds), _) => {
+                let len = self.payload.as_ref().map_or(0, Vec::len);
+                let frames = self.segments.len() + 1;
+                write!(f, "{uds} ({len} bytes, {frames} frames)")
+            }
+            (None, frame) => {
+                write!(f, "ISO-TP {frame}")?;
+                if let (IsoTpFrame::Consecutive { .. }, Some((received, size))) =
+                    (frame, self.progress)
+                {
+                    write!(f, " {received}/{size}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Reassembly {
+    size: usize,
+    data: Vec<u8>,
+    next_seq: u8,
+    /// Indices of the first, consecutive and flow control frames so far.
+    frames: Vec<u64>,
+}
+
+/// Reassembles ISO-TP messages on the configured identifier pairs and decodes them as UDS.
+#[derive(Debug, Default, Clone)]
+pub struct IsoTpTracker {
+    pairs: Vec<IsoTpPair>,
+    /// Message being received, by the identifier carrying its data.
+    sessions: HashMap<u16, Reassembly>,
+}
+
+impl IsoTpTracker {
+    pub fn add_pair(&mut self, pair: IsoTpPair) {
+        if !self.pairs.contains(&pair) {
+            self.pairs.push(pair);
+        }
+    }
+
+    pub fn pairs(&self) -> &[IsoTpPair] {
+        &self.pairs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Decodes the frame, the identifier of a configured pair takes precedence over `CANopen`.
+    pub fn process(&mut self, msg: &mut MessageCached) {
+        let cob_id = msg.msg.msg.cob_id;
+        let request = if self.pairs.iter().any(|p| p.request == cob_id) {
+            true
+        } else if self.pairs.iter().any(|p| p.response == cob_id) {
+            false
+        } else {
+            return;
+        };
+
+        let data = &msg.msg.msg.data[..msg.msg.msg.dlc.min(8)];
+        if let Some(info) = self.decode(msg.index, cob_id, request, data) {
+            msg.additional = RxMessageAdditional::IsoTp(info);
+        }
+    }
+
+    fn decode(&mut self, index: u64, cob_id: u16, request: bool, data: &[u8]) -> Option<IsoTpInfo> {
+        let frame = IsoTpFrame::new(data)?;
+        let mut info = IsoTpInfo {
+            frame,
+            request,
+            progress: None,
+            payload: None,
+            uds: None,
+            error: None,
+            segments: Vec::new(),
+            folded: false,
+        };
+
+        match frame {
+            IsoTpFrame::Single { len } => {
+                if len == 0 || len + 1 > data.len() {
+                    info.error = Some("invalid length");
+                } else {
+                    info.payload = Some(data[1..=len].to_vec());
+                }
+            }
+            IsoTpFrame::First { len } => {
+                if len <= 7 || len > MAX_PAYLOAD {
+                    info.error = Some("invalid length");
+                } else {
+                    let received = data.get(2..).unwrap_or_default().to_vec();
+                    info.progress = Some((received.len(), len));
+                    self.sessions.insert(
+                        cob_id,
+                        Reassembly {
+                            size: len,
+                            data: received,
+                            next_seq: 1,
+                            frames: vec![index],
+                        },
+                    );
+                }
+            }
+            IsoTpFrame::Consecutive { seq } => match self.sessions.get_mut(&cob_id) {
+                None => info.error = Some("unexpected consecutive frame"),
+                Some(session) if session.next_seq != seq => {
+                    self.sessions.remove(&cob_id);
+                    info.error = Some("sequence error");
+                }
+                Some(session) => {
+                    session.next_seq = (session.next_seq + 1) & 0x0F;
+                    let missing = session.size - session.data.len();
+                    let chunk = &data[1..];
+                    session
+                        .data
+                        .extend_from_slice(&chunk[..chunk.len().min(missing)]);
+                    info.progress = Some((session.data.len(), session.size));
+                    if session.data.len() == session.size {
+                        if let Some(session) = self.sessions.remove(&cob_id) {
+                            info.payload = Some(session.data);
+                            info.segments = session.frames;
+                        }
+                    } else {
+                        session.frames.push(index);
+                    }
+                }
+            },
+            IsoTpFrame::FlowControl { .. } => {
+                // flow control is sent on the other identifier of the pair
+                let data_id = self.pairs.iter().find_map(|p| match request {
+                    true if p.request == cob_id => Some(p.response),
+                    false if p.response == cob_id => Some(p.request),
+                    _ => None,
+                });
+                if let Some(session) = data_id.and_then(|id| self.sessions.get_mut(&id)) {
+                    session.frames.push(index);
+                }
+            }
+        }
+
+        info.uds = info.payload.as_deref().and_then(UdsMessage::parse);
+        Some(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IsoTpPair, IsoTpTracker};
+
+    #[test]
+    fn test_isotp_reassembly() {
+        let pair: IsoTpPair = "0x7E0:7E8".parse().unwrap();
+        assert_eq!(pair.request, 0x7E0);
+        assert_eq!(pair.response, 0x7E8);
+        assert!("7E0".parse::<IsoTpPair>().is_err());
+
+        let mut tracker = IsoTpTracker::default();
+        tracker.add_pair(pair);
+
+        let req = tracker
+            .decode(
+                0,
+                0x7E0,
+                true,
+                &[0x03, 0x22, 0xF1, 0x90, 0xAA, 0xAA, 0xAA, 0xAA],
+            )
+            .unwrap();
+        assert_eq!(req.to_string(), "UDS → ReadDataByIdentifier DID F190");
+
+        let first = tracker
+            .decode(
+                1,
+                0x7E8,
+                false,
+                &[0x10, 0x0A, 0x62, 0xF1, 0x90, b'W', b'0', b'L'],
+            )
+            .unwrap();
+        assert_eq!(first.to_string(), "ISO-TP FF 10 bytes");
+        let fc = tracker
+            .decode(2, 0x7E0, true, &[0x30, 0x00, 0x0A, 0, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(fc.to_string(), "ISO-TP FC CTS bs=0 st=10");
+        let last = tracker
+            .decode(
+                3,
+                0x7E8,
+                false,
+                &[0x21, b'V', b'W', b'Z', b'Z', 0x55, 0x55, 0x55],
+            )
+            .unwrap();
+        assert_eq!(
+            last.to_string(),
+            "UDS ← ReadDataByIdentifier DID F190 = \"W0LVWZZ\" (10 bytes, 3 frames)"
+        );
+        assert_eq!(last.segments, [1, 2]);
+
+        tracker.decode(4, 0x7E8, false, &[0x10, 0x0A, 0x62, 0xF1, 0x90, 0, 0, 0]);
+        let broken = tracker
+            .decode(5, 0x7E8, false, &[0x22, 0, 0, 0, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(broken.to_string(), "ISO-TP CF 2 sequence error");
+    }
+}
@@ -0,0 +1,479 @@
+use crate::message_cached::{MessageCached, RxMessageAdditional};
+use core::fmt;
+use oze_canopen::canopen::{RxMessage, RxMessageParsed, RxMessageToStringFormat, RxMessageType};
+use std::collections::HashMap;
+use tokio::time::Instant;
+
+/// Transport protocol connection management (TP.CM).
+const PGN_TP_CM: u32 = 0xEC00;
+/// Transport protocol data transfer (TP.DT).
+const PGN_TP_DT: u32 = 0xEB00;
+const PGN_REQUEST: u32 = 0xEA00;
+const PGN_ADDRESS_CLAIMED: u32 = 0xEE00;
+const PGN_EEC2: u32 = 0xF003;
+const PGN_EEC1: u32 = 0xF004;
+const PGN_DM1: u32 = 0xFECA;
+const PGN_COMPONENT_ID: u32 = 0xFEEB;
+const PGN_VEHICLE_ID: u32 = 0xFEEC;
+const PGN_ET1: u32 = 0xFEEE;
+const PGN_EFLP1: u32 = 0xFEEF;
+const PGN_CCVS: u32 = 0xFEF1;
+const PGN_LFE: u32 = 0xFEF2;
+const PGN_VEP1: u32 = 0xFEF7;
+
+const TP_RTS: u8 = 0x10;
+const TP_CTS: u8 = 0x11;
+const TP_END_OF_MSG_ACK: u8 = 0x13;
+const TP_BAM: u8 = 0x20;
+const TP_ABORT: u8 = 0xFF;
+const TP_DT_PAYLOAD: usize = 7;
+
+/// PDU format values below this one address a destination (PDU1).
+const PDU2_FORMAT: u32 = 240;
+const GLOBAL_ADDRESS: u8 = 0xFF;
+
+/// Fields of a 29-bit J1939 identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct J1939Id {
+    pub priority: u8,
+    pub pgn: u32,
+    pub source: u8,
+    /// Destination address of PDU1 messages, `None` for broadcast PDU2 messages.
+    pub destination: Option<u8>,
+}
+
+/// Transport protocol connection management message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TpControl {
+    Rts { size: u16, packets: u8 },
+    Cts { packets: u8, next: u8 },
+    EndOfMsgAck { size: u16, packets: u8 },
+    Bam { size: u16, packets: u8 },
+    Abort { reason: u8 },
+    Unknown(u8),
+}
+
+/// Meaning of one J1939 frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum J1939Kind {
+    /// Single frame message with its decoded parameters.
+    Single(Vec<(&'static str, String)>),
+    /// Transport protocol connection management for the given PGN.
+    TpCm { pgn: u32, control: TpControl },
+    /// Transport protocol data packet.
+    TpDt { seq: u8 },
+    /// Last data packet completing a multi-packet message.
+    Reassembled {
+        pgn: u32,
+        data: Vec<u8>,
+        params: Vec<(&'static str, String)>,
+    },
+}
+
+/// Decoded J1939 frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct J1939Message {
+    pub id: J1939Id,
+    pub kind: J1939Kind,
+}
+
+/// Multi-packet message being reassembled.
+#[derive(Debug, Clone)]
+struct TpSession {
+    pgn: u32,
+    size: usize,
+    packets: u8,
+    data: Vec<u8>,
+    next_seq: u8,
+}
+
+/// Reassembles transport protocol (BAM and RTS/CTS) sessions.
+#[derive(Debug, Default, Clone)]
+pub struct J1939Tracker {
+    /// Sessions by source and destination address.
+    sessions: HashMap<(u8, u8), TpSession>,
+}
+
+impl J1939Id {
+    pub fn from_raw(raw: u32) -> Self {
+        let [source, pdu_specific, pdu_format, page] = raw.to_le_bytes();
+        let data_page = u32::from(page & 0x03) << 16;
+        let (pgn, destination) = if u32::from(pdu_format) < PDU2_FORMAT {
+            (data_page | (u32::from(pdu_format) << 8), Some(pdu_specific))
+        } else {
+            (
+                data_page | (u32::from(pdu_format) << 8) | u32::from(pdu_specific),
+                None,
+            )
+        };
+
+        Self {
+            priority: (page >> 2) & 0x07,
+            pgn,
+            source,
+            destination,
+        }
+    }
+}
+
+impl fmt::Display for J1939Id {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "P{} PGN {:5}", self.priority, self.pgn)?;
+        if let Some(name) = pgn_name(self.pgn) {
+            write!(f, " {name:<5}")?;
+        }
+        write!(f, " SA {:02X}", self.source)?;
+        if let Some(da) = self.destination {
+            write!(f, " DA {da:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+impl TpControl {
+    fn parse(data: &[u8]) -> Option<(u32, Self)> {
+        let [control, b1, b2, b3, _, p0, p1, p2] = data else {
+            return None;
+        };
+        let size = u16::from_le_bytes([*b1, *b2]);
+        let pgn = u32::from_le_bytes([*p0, *p1, *p2, 0]);
+        let control = match *control {
+            TP_RTS => TpControl::Rts { size, packets: *b3 },
+            TP_CTS => TpControl::Cts {
+                packets: *b1,
+                next: *b2,
+            },
+            TP_END_OF_MSG_ACK => TpControl::EndOfMsgAck { size, packets: *b3 },
+            TP_BAM => TpControl::Bam { size, packets: *b3 },
+            TP_ABORT => TpControl::Abort { reason: *b1 },
+            c => TpControl::Unknown(c),
+        };
+        Some((pgn, control))
+    }
+}
+
+impl fmt::Display for TpControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TpControl::Rts { size, packets } => write!(f, "RTS {size} bytes {packets} packets"),
+            TpControl::Cts { packets, next } => write!(f, "CTS {packets} packets from {next}"),
+            TpControl::EndOfMsgAck { size, packets } => {
+                write!(f, "EndOfMsgAck {size} bytes {packets} packets")
+            }
+            TpControl::Bam { size, packets } => write!(f, "BAM {size} bytes {packets} packets"),
+            TpControl::Abort { reason } => write!(f, "Abort reason {reason}"),
+            TpControl::Unknown(c) => write!(f, "control {c:02X}"),
+        }
+    }
+}
+
+fn write_params(f: &mut fmt::Formatter, params: &[(&'static str, String)]) -> fmt::Result {
+    for (name, value) in params {
+        write!(f, " {name}={value}")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for J1939Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        match &self.kind {
+            J1939Kind::Single(params) => write_params(f, params),
+            J1939Kind::TpCm { pgn, control } => write!(f, " {control} for PGN {pgn}"),
+            J1939Kind::TpDt { seq } => write!(f, " seq {seq}"),
+            J1939Kind::Reassembled { pgn, data, params } => {
+                write!(f, " PGN {pgn}")?;
+                if let Some(name) = pgn_name(*pgn) {
+                    write!(f, " {name}")?;
+                }
+                write!(f, " {} bytes:", data.len())?;
+                write_params(f, params)
+            }
+        }
+    }
+}
+
+impl J1939Message {
+    pub fn get_tooltip(&self) -> String {
+        let mut lines = vec![format!(
+            "priority {} PGN {} (0x{:05X}) source {:02X}",
+            self.id.priority, self.id.pgn, self.id.pgn, self.id.source
+        )];
+        if let Some(da) = self.id.destination {
+            lines.push(format!("destination {da:02X}"));
+        }
+        if let J1939Kind::Reassembled { data, .. } = &self.kind {
+            lines.push(
+                data.iter()
+                    .map(|b| format!("{b:02X}"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+        }
+        lines.join("\n")
+    }
+}
+
+fn pgn_name(pgn: u32) -> Option<&'static str> {
+    Some(match pgn {
+        PGN_TP_CM => "TP.CM",
+        PGN_TP_DT => "TP.DT",
+        PGN_REQUEST => "RQST",
+        PGN_ADDRESS_CLAIMED => "AC",
+        PGN_EEC1 => "EEC1",
+        PGN_EEC2 => "EEC2",
+        PGN_DM1 => "DM1",
+        PGN_COMPONENT_ID => "CI",
+        PGN_VEHICLE_ID => "VI",
+        PGN_ET1 => "ET1",
+        PGN_EFLP1 => "EFL/P1",
+        PGN_CCVS => "CCVS",
+        PGN_LFE => "LFE1",
+        PGN_VEP1 => "VEP1",
+        _ => return None,
+    })
+}
+
+/// Formats a scaled byte parameter, 0xFE and 0xFF mean error and not available.
+fn scaled_u8(byte: Option<&u8>, factor: f64, offset: f64, unit: &str) -> String {
+    match byte {
+        Some(b) if *b < 0xFE => format!("{}{unit}", f64::from(*b) * factor + offset),
+        _ => "n/a".to_owned(),
+    }
+}
+
+/// Formats a scaled little endian word parameter, values above 0xFAFF are not valid.
+fn scaled_u16(data: &[u8], at: usize, factor: f64, offset: f64, unit: &str) -> String {
+    match data.get(at..at + 2) {
+        Some([lo, hi]) if u16::from_le_bytes([*lo, *hi]) <= 0xFAFF => {
+            let raw = u16::from_le_bytes([*lo, *hi]);
+            format!("{}{unit}", f64::from(raw) * factor + offset)
+        }
+        _ => "n/a".to_owned(),
+    }
+}
+
+fn ascii_fields(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_end_matches(['*', '\0', ' '])
+        .replace('*', " | ")
+}
+
+/// Decodes the parameters of common PGNs.
+pub fn decode_pgn(pgn: u32, data: &[u8]) -> Vec<(&'static str, String)> {
+    match pgn {
+        PGN_REQUEST if data.len() >= 3 => {
+            let requested = u32::from_le_bytes([data[0], data[1], data[2], 0]);
+            vec![("pgn", requested.to_string())]
+        }
+        PGN_ADDRESS_CLAIMED if data.len() == 8 => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(data);
+            let name = u64::from_le_bytes(bytes);
+            vec![
+                ("identity", (name & 0x1F_FFFF).to_string()),
+                ("manufacturer", ((name >> 21) & 0x7FF).to_string()),
+                ("function", ((name >> 40) & 0xFF).to_string()),
+                ("industry", ((name >> 60) & 0x07).to_string()),
+            ]
+        }
+        PGN_EEC1 => vec![
+            ("torque", scaled_u8(data.get(2), 1.0, -125.0, "%")),
+            ("speed", scaled_u16(data, 3, 0.125, 0.0, "rpm")),
+        ],
+        PGN_EEC2 => vec![("pedal", scaled_u8(data.get(1), 0.4, 0.0, "%"))],
+        PGN_ET1 => vec![("coolant", scaled_u8(data.first(), 1.0, -40.0, "°C"))],
+        PGN_EFLP1 => vec![("oil_pressure", scaled_u8(data.get(3), 4.0, 0.0, "kPa"))],
+        PGN_CCVS => vec![("speed", scaled_u16(data, 1, 1.0 / 256.0, 0.0, "km/h"))],
+        PGN_LFE => vec![("fuel_rate", scaled_u16(data, 0, 0.05, 0.0, "L/h"))],
+        PGN_VEP1 => vec![("battery", scaled_u16(data, 4, 0.05, 0.0, "V"))],
+        PGN_VEHICLE_ID | PGN_COMPONENT_ID => vec![("id", ascii_fields(data))],
+        PGN_DM1 => decode_dm1(data),
+        _ => Vec::new(),
+    }
+}
+
+/// Decodes lamp status and active diagnostic trouble codes.
+fn decode_dm1(data: &[u8]) -> Vec<(&'static str, String)> {
+    let Some(lamps) = data.first() else {
+        return Vec::new();
+    };
+    let mut params = vec![("lamps", format!("{lamps:02X}"))];
+    for dtc in data.get(2..).unwrap_or_default().chunks_exact(4) {
+        let spn = u32::from_le_bytes([dtc[0], dtc[1], dtc[2] >> 5, 0]);
+        if spn == 0 {
+            continue;
+        }
+        params.push((
+            "dtc",
+            format!("SPN {spn} FMI {} OC {}", dtc[2] & 0x1F, dtc[3] & 0x7F),
+        ));
+    }
+    params
+}
+
+impl TpSession {
+    fn new(pgn: u32, size: u16, packets: u8) -> Self {
+        Self {
+            pgn,
+            size: usize::from(size),
+            packets,
+            data: Vec::with_capacity(usize::from(size)),
+            next_seq: 1,
+        }
+    }
+}
+
+impl J1939Tracker {
+    /// Decodes an extended frame and follows transport protocol sessions.
+    pub fn decode(&mut self, raw_id: u32, data: &[u8]) -> J1939Message {
+        let id = J1939Id::from_raw(raw_id);
+        let destination = id.destination.unwrap_or(GLOBAL_ADDRESS);
+        let kind = match id.pgn {
+            PGN_TP_CM => match TpControl::parse(data) {
+                Some((pgn, control)) => {
+                    self.on_control(id.source, destination, pgn, control);
+                    J1939Kind::TpCm { pgn, control }
+                }
+                None => J1939Kind::Single(Vec::new()),
+            },
+            PGN_TP_DT => self.on_data(id.source, destination, data),
+            pgn => J1939Kind::Single(decode_pgn(pgn, data)),
+        };
+        J1939Message { id, kind }
+    }
+
+    fn on_control(&mut self, source: u8, destination: u8, pgn: u32, control: TpControl) {
+        match control {
+            TpControl::Rts { size, packets } | TpControl::Bam { size, packets } => {
+                self.sessions
+                    .insert((source, destination), TpSession::new(pgn, size, packets));
+            }
+            // sent by the receiver of the data
+            TpControl::Abort { .. } => {
+                self.sessions.remove(&(source, destination));
+                self.sessions.remove(&(destination, source));
+            }
+            TpControl::Cts { .. } | TpControl::EndOfMsgAck { .. } | TpControl::Unknown(_) => {}
+        }
+    }
+
+    fn on_data(&mut self, source: u8, destination: u8, data: &[u8]) -> J1939Kind {
+        let Some((&seq, payload)) = data.split_first() else {
+            return J1939Kind::Single(Vec::new());
+        };
+        let key = (source, destination);
+        let Some(session) = self.sessions.get_mut(&key) else {
+            return J1939Kind::TpDt { seq };
+        };
+
+        if seq != session.next_seq {
+            // lost or repeated packet, the message can't be reassembled
+            self.sessions.remove(&key);
+            return J1939Kind::TpDt { seq };
+        }
+
+        session.next_seq = session.next_seq.wrapping_add(1);
+        session
+            .data
+            .extend_from_slice(&payload[..payload.len().min(TP_DT_PAYLOAD)]);
+        if seq < session.packets {
+            return J1939Kind::TpDt { seq };
+        }
+
+        let Some(mut session) = self.sessions.remove(&key) else {
+            return J1939Kind::TpDt { seq };
+        };
+        session.data.truncate(session.size);
+        J1939Kind::Reassembled {
+            pgn: session.pgn,
+            params: decode_pgn(session.pgn, &session.data),
+            data: session.data,
+        }
+    }
+
+    /// Builds a viewer message for an extended frame.
+    pub fn message(&mut self, index: u64, raw_id: u32, data: [u8; 8], dlc: usize) -> MessageCached {
+        let j1939 = self.decode(raw_id, &data[..dlc.min(8)]);
+        // the 11-bit COB-ID can't hold the identifier, it is kept in can_id
+        let msg = RxMessage {
+            timestamp: Instant::now(),
+            cob_id: 0,
+            data,
+            dlc,
+        };
+
+        let additional = RxMessageAdditional::J1939(j1939);
+        MessageCached {
+            index,
+            msg: RxMessageParsed {
+                parsed_type: RxMessageType::Unknown,
+                parsed_node_id: None,
+                msg,
+            },
+            info_str: additional.to_string(),
+            additional,
+            can_id: raw_id,
+            extended: true,
+            cob_str: format!("{raw_id:08X}"),
+            hex_str: msg.data_to_string(RxMessageToStringFormat::Hex),
+            bin_str: msg.data_to_string(RxMessageToStringFormat::Binary),
+            ascii_str: msg.data_to_string(RxMessageToStringFormat::Ascii),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{J1939Id, J1939Kind, J1939Tracker, TpControl};
+
+    #[test]
+    fn test_id_and_pgn_decoding() {
+        let id = J1939Id::from_raw(0x0CF0_0400);
+        assert_eq!(id.priority, 3);
+        assert_eq!(id.pgn, 61444);
+        assert_eq!(id.source, 0);
+        assert_eq!(id.destination, None);
+
+        let id = J1939Id::from_raw(0x18EA_0017);
+        assert_eq!(id.pgn, 0xEA00);
+        assert_eq!(id.destination, Some(0));
+
+        let mut tracker = J1939Tracker::default();
+        let eec1 = tracker.decode(0x0CF0_0400, &[0xF0, 0x7D, 0x91, 0x40, 0x1F, 0, 0xF0, 0xFF]);
+        assert_eq!(
+            eec1.to_string(),
+            "P3 PGN 61444 EEC1  SA 00 torque=20% speed=1000rpm"
+        );
+
+        let msg = tracker.message(0, 0x18FE_F100, [0; 8], 8);
+        assert_eq!(msg.can_id, 0x18FE_F100);
+        assert!(msg.extended);
+    }
+
+    #[test]
+    fn test_bam_reassembly() {
+        let mut tracker = J1939Tracker::default();
+        // DM1 with two DTCs (10 bytes) from address 0x00 as BAM
+        let cm = tracker.decode(0x1CEC_FF00, &[0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]);
+        assert_eq!(
+            cm.kind,
+            J1939Kind::TpCm {
+                pgn: 0xFECA,
+                control: TpControl::Bam {
+                    size: 10,
+                    packets: 2
+                }
+            }
+        );
+
+        let first = tracker.decode(0x1CEB_FF00, &[1, 0x04, 0xFF, 0x6E, 0x00, 0x03, 0x01, 0xBE]);
+        assert_eq!(first.kind, J1939Kind::TpDt { seq: 1 });
+        let last = tracker.decode(0x1CEB_FF00, &[2, 0x00, 0x04, 0x02, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(
+            last.to_string(),
+            "P7 PGN 60160 TP.DT SA 00 DA FF PGN 65226 DM1 10 bytes: lamps=04 \
+             dtc=SPN 110 FMI 3 OC 1 dtc=SPN 190 FMI 4 OC 2"
+        );
+    }
+}
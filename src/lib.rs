@@ -1,12 +1,45 @@
 pub mod bitrate;
+pub mod bus_load;
+pub mod bus_load_panel;
 pub mod chart;
+pub mod cia401;
+pub mod cia402;
+pub mod cob_stats;
+pub mod dbc;
+pub mod drive_panel;
 pub mod driver;
+pub mod emcy;
+pub mod field_layout;
+pub mod field_layout_panel;
 pub mod filter;
 pub mod filter_data_panel;
+pub mod filter_expr;
 pub mod filter_panel;
+pub mod fixed_trace;
+pub mod frame_rate;
 pub mod gui;
+pub mod heartbeat;
+pub mod history;
+pub mod io_panel;
+pub mod isotp;
+pub mod j1939;
 pub mod message_cached;
 pub mod message_row;
+pub mod node_overview;
+pub mod node_panel;
+pub mod pdo_mapping;
 pub mod pinned_filter;
+pub mod sdo_block;
+pub mod sdo_panel;
+pub mod sdo_session;
+pub mod series;
+pub mod signal;
+pub mod signal_panel;
+pub mod supervision;
+pub mod supervision_panel;
+pub mod sync_time;
 pub mod theme;
+pub mod timing;
+pub mod timing_panel;
+pub mod uds;
 pub mod viewer;
@@ -1,12 +1,17 @@
 use clap::Parser;
 use oze_canopen::interface::Connection;
 use oze_canopen_viewer::bitrate;
-use oze_canopen_viewer::driver::{self, Control};
+use oze_canopen_viewer::dbc::DbcDatabase;
+use oze_canopen_viewer::driver::{self, Control, Protocol};
+use oze_canopen_viewer::emcy::EmcyCatalogue;
+use oze_canopen_viewer::field_layout::FieldLayouts;
+use oze_canopen_viewer::field_layout_panel::FieldLayoutPanel;
 use oze_canopen_viewer::gui::Gui;
-use std::sync::Arc;
+use oze_canopen_viewer::isotp::IsoTpPair;
+use std::path::PathBuf;
 use std::thread;
 use tokio::runtime::Runtime;
-use tokio::sync::{watch, Mutex};
+use tokio::sync::watch;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -15,25 +20,69 @@ struct Args {
     can: Option<String>,
     #[arg(short, long)]
     bitrate: Option<u32>,
+    /// File with vendor EMCY error codes, one `0xFF01 = description` per line
+    #[arg(long)]
+    emcy_codes: Option<PathBuf>,
+    /// DBC file used to decode non-CANopen frames, may be repeated
+    #[arg(long)]
+    dbc: Vec<PathBuf>,
+    /// Decode 29-bit frames as J1939
+    #[arg(long)]
+    j1939: bool,
+    /// ISO-TP request and response IDs decoded as UDS, e.g. 7E0:7E8, may be repeated
+    #[arg(long, value_name = "REQUEST:RESPONSE")]
+    isotp: Vec<IsoTpPair>,
+    /// File with payload field layouts, one `181 = speed:u16@0*0.1; brake:bit@2.3` per line
+    #[arg(long)]
+    fields: Option<PathBuf>,
+    /// Trace of the signal plot, e.g. `181 = speed:i16@0*0.1`, `pdo 5 6078:00 signed` or `dbc Engine.Speed`, may be repeated
+    #[arg(long)]
+    signal: Vec<String>,
 }
 
 fn main() -> eframe::Result<()> {
     pretty_env_logger::init();
     let args = Args::parse();
 
+    let emcy_catalogue = match &args.emcy_codes {
+        Some(path) => EmcyCatalogue::load(path).unwrap_or_else(|e| {
+            eprintln!("Unable to load EMCY codes: {e}");
+            EmcyCatalogue::default()
+        }),
+        None => EmcyCatalogue::default(),
+    };
+
+    let mut dbc = DbcDatabase::default();
+    for path in &args.dbc {
+        if let Err(e) = dbc.load(path) {
+            eprintln!("Unable to load DBC: {e}");
+        }
+    }
+
+    let field_layouts = args.fields.as_ref().and_then(|path| {
+        FieldLayouts::load(path)
+            .map(|text| (text, path))
+            .map_err(|e| eprintln!("Unable to load field layouts: {e}"))
+            .ok()
+    });
+
     let initial_control = Control {
         command: driver::ControlCommand::Process,
         connection: Connection {
             can_name: args.can.clone().unwrap_or_default(),
             bitrate: args.bitrate,
         },
+        protocol: if args.j1939 {
+            Protocol::J1939
+        } else {
+            Protocol::CanOpen
+        },
     };
 
     let (state_snd, state_rcv) = watch::channel(driver::State::default());
     let (ctrl_snd, ctrl_rcv) = watch::channel(initial_control.clone());
 
-    let bitrates = Arc::new(Mutex::new(Vec::new()));
-    let bitrates_thr = bitrates.clone();
+    let (rates_sender, rates) = bitrate::rates_channel();
     let ctrl_snd_thr = ctrl_snd.clone();
     let rt = Runtime::new().expect("Unable to create Runtime");
 
@@ -41,8 +90,13 @@ fn main() -> eframe::Result<()> {
 
     thread::spawn(move || {
         rt.block_on(async {
-            let drv = driver::Driver::new(state_snd, ctrl_rcv);
-            let br = bitrate::Bitrate::new(drv.co.info.clone(), bitrates_thr.clone());
+            let mut drv = driver::Driver::new(state_snd, ctrl_rcv);
+            drv.emcy_catalogue = emcy_catalogue;
+            drv.dbc = dbc;
+            for pair in args.isotp {
+                drv.isotp.add_pair(pair);
+            }
+            let br = bitrate::Bitrate::new(drv.co.info.clone(), rates_sender);
             drv.start_thread();
             br.start_thread();
 
@@ -68,6 +122,17 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "OZON CanOpen Viewer",
         native_options,
-        Box::new(|cc| Ok(Box::new(Gui::new(cc, state_rcv, ctrl_snd, bitrates)))),
+        Box::new(|cc| {
+            let mut gui = Gui::new(cc, state_rcv, ctrl_snd, rates);
+            if let Some((text, path)) = field_layouts {
+                gui.field_layout_panel = FieldLayoutPanel::new(text, path);
+            }
+            for spec in &args.signal {
+                if let Err(e) = gui.signal_panel.recorder.add(spec, 1) {
+                    eprintln!("Unable to add signal: {e}");
+                }
+            }
+            Ok(Box::new(gui))
+        }),
     )
 }
@@ -1,8 +1,17 @@
+use crate::{
+    dbc::DbcDecoded,
+    emcy::EmcyInfo,
+    heartbeat::Heartbeat,
+    isotp::IsoTpInfo,
+    j1939::J1939Message,
+    pdo_mapping::PdoDecoded,
+    sdo_block::SdoBlock,
+    sync_time::{SyncInfo, TimeOfDay, TIME_COB_ID},
+};
 use core::fmt;
 use oze_canopen::{
     canopen::{RxMessage, RxMessageParsed, RxMessageToStringFormat, RxMessageType},
     proto::{
-        emcy::Emcy,
         nmt::NmtCommand,
         sdo::{ResponseData, SdoRequest, SdoRequestData, SdoResponse},
     },
@@ -15,8 +24,16 @@ use tokio::time::Instant;
 pub enum RxMessageAdditional {
     SdoTx(SdoResponse),
     SdoRx(SdoRequest),
+    SdoBlock(SdoBlock),
     Nmt(NmtCommand),
-    Emcy(Emcy),
+    Emcy(EmcyInfo),
+    Heartbeat(Heartbeat),
+    Sync(SyncInfo),
+    Time(TimeOfDay),
+    Pdo(PdoDecoded),
+    Dbc(DbcDecoded),
+    J1939(J1939Message),
+    IsoTp(IsoTpInfo),
     None,
 }
 
@@ -25,10 +42,16 @@ pub struct MessageCached {
     pub index: u64,
     pub msg: RxMessageParsed,
     pub additional: RxMessageAdditional,
+    /// Identifier of the frame, 29-bit frames have the COB ID 0 in `msg`.
+    pub can_id: u32,
+    /// 29-bit frame, only received in the J1939 mode.
+    pub extended: bool,
     pub cob_str: String,
     pub hex_str: String,
     pub bin_str: String,
     pub ascii_str: String,
+    /// Info column, see [`MessageCached::cache_info`].
+    pub info_str: String,
 }
 
 impl RxMessageAdditional {
@@ -109,9 +132,15 @@ impl RxMessageAdditional {
             RxMessageAdditional::Nmt(n) => {
                 format!("{n:?}")
             }
-            RxMessageAdditional::Emcy(n) => {
-                format!("{n:?}")
-            }
+            RxMessageAdditional::Emcy(n) => n.get_tooltip(),
+            RxMessageAdditional::SdoBlock(b) => format!("{b:?}"),
+            RxMessageAdditional::Heartbeat(h) => format!("{h:?}"),
+            RxMessageAdditional::Sync(s) => format!("{s:?}"),
+            RxMessageAdditional::Time(t) => format!("{t:?}"),
+            RxMessageAdditional::Pdo(p) => p.get_tooltip(),
+            RxMessageAdditional::Dbc(d) => d.get_tooltip(),
+            RxMessageAdditional::J1939(j) => j.get_tooltip(),
+            RxMessageAdditional::IsoTp(i) => i.get_tooltip(),
             RxMessageAdditional::None => String::new(),
         }
     }
@@ -129,13 +158,15 @@ impl fmt::Display for RxMessageAdditional {
             RxMessageAdditional::Nmt(n) => {
                 write!(f, "{:?} node_id: {}", n.command_specifier, n.node_id)
             }
-            RxMessageAdditional::Emcy(n) => {
-                write!(
-                    f,
-                    "{:?} {:X} reg {:X} data {:X?}",
-                    n.code, n.vendor_code, n.error_register, n.data
-                )
-            }
+            RxMessageAdditional::Emcy(n) => write!(f, "{n}"),
+            RxMessageAdditional::SdoBlock(b) => write!(f, "{b}"),
+            RxMessageAdditional::Heartbeat(h) => write!(f, "{h}"),
+            RxMessageAdditional::Sync(s) => write!(f, "{s}"),
+            RxMessageAdditional::Time(t) => write!(f, "{t}"),
+            RxMessageAdditional::Pdo(p) => write!(f, "{p}"),
+            RxMessageAdditional::Dbc(d) => write!(f, "{d}"),
+            RxMessageAdditional::J1939(j) => write!(f, "{j}"),
+            RxMessageAdditional::IsoTp(i) => write!(f, "{i}"),
             RxMessageAdditional::None => write!(f, ""),
         }
     }
@@ -146,6 +177,7 @@ impl MessageCached {
     pub fn new(index: u64, msg: RxMessage) -> Self {
         let parsed = RxMessageParsed::new(msg);
         let mut dat = Cursor::new(msg.data);
+        let data = &msg.data[..msg.dlc.min(8)];
 
         let additional = match parsed.parsed_type {
             RxMessageType::SdoTx => {
@@ -172,23 +204,40 @@ impl MessageCached {
                 }
             }
             RxMessageType::Emcy => {
-                if let Ok(d) = Emcy::read(&mut dat) {
+                if let Some(d) = EmcyInfo::new(data) {
                     RxMessageAdditional::Emcy(d)
                 } else {
                     RxMessageAdditional::None
                 }
             }
-            RxMessageType::Guarding
-            | RxMessageType::Lss
-            | RxMessageType::Pdo
-            | RxMessageType::Sync
-            | RxMessageType::Unknown => RxMessageAdditional::None,
+            RxMessageType::Guarding => {
+                if let Some(h) = Heartbeat::new(data) {
+                    RxMessageAdditional::Heartbeat(h)
+                } else {
+                    RxMessageAdditional::None
+                }
+            }
+            RxMessageType::Sync => RxMessageAdditional::Sync(SyncInfo::new(data)),
+            RxMessageType::Pdo if msg.cob_id == TIME_COB_ID => {
+                if let Some(t) = TimeOfDay::new(data) {
+                    RxMessageAdditional::Time(t)
+                } else {
+                    RxMessageAdditional::None
+                }
+            }
+            RxMessageType::Lss | RxMessageType::Pdo | RxMessageType::Unknown => {
+                RxMessageAdditional::None
+            }
         };
 
+        let info_str = additional.to_string();
         Self {
             index,
             msg: parsed,
             additional,
+            info_str,
+            can_id: u32::from(msg.cob_id),
+            extended: false,
             cob_str: msg.cob_id_to_string(),
             hex_str: msg.data_to_string(RxMessageToStringFormat::Hex),
             bin_str: msg.data_to_string(RxMessageToStringFormat::Binary),
@@ -210,4 +259,31 @@ impl MessageCached {
     pub fn get_timestamp(&self) -> Instant {
         self.msg.msg.timestamp
     }
+
+    /// Key of statistics per identifier, standard and extended frames with the same number
+    /// are different frames.
+    pub fn frame_id(&self) -> (bool, u32) {
+        (self.extended, self.can_id)
+    }
+
+    /// Formats the Info column again after the decoders changed `additional`.
+    pub fn cache_info(&mut self) {
+        self.info_str = self.additional.to_string();
+    }
+}
+
+/// Message with the payload received at the time, for tests of the decoders.
+#[cfg(test)]
+pub(crate) fn test_message(cob_id: u16, data: &[u8], timestamp: Instant) -> MessageCached {
+    let mut buf = [0u8; 8];
+    buf[..data.len()].copy_from_slice(data);
+    MessageCached::new(
+        0,
+        RxMessage {
+            timestamp,
+            cob_id,
+            data: buf,
+            dlc: data.len(),
+        },
+    )
 }
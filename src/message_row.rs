@@ -1,11 +1,17 @@
-use crate::message_cached::MessageCached;
+use crate::{field_layout::FieldLayouts, message_cached::MessageCached, theme::OZON_PINK};
+use egui::RichText;
 use oze_canopen::canopen::RxMessageToStringFormat;
+use std::rc::Rc;
 use tokio::time::Instant;
 
 #[derive(Debug)]
 pub struct MessageRow {
     pub start_time: Instant,
     pub format: RxMessageToStringFormat,
+    /// Layouts of the fields column, hidden if not set.
+    pub fields: Option<Rc<FieldLayouts>>,
+    /// Timestamps of messages received in this range are highlighted, e.g. at the chart cursor.
+    pub highlight: Option<(Instant, Instant)>,
 }
 
 impl Default for MessageRow {
@@ -13,6 +19,8 @@ impl Default for MessageRow {
         Self {
             start_time: Instant::now(),
             format: RxMessageToStringFormat::Hex,
+            fields: None,
+            highlight: None,
         }
     }
 }
@@ -37,6 +45,9 @@ impl MessageRow {
             RxMessageToStringFormat::Ascii => "ASCII data",
             RxMessageToStringFormat::Utf8 => "UTF8 data",
         });
+        if self.fields.is_some() {
+            ui.label("Fields");
+        }
 
         ui.label("Packet type");
         ui.label("Node ID");
@@ -50,6 +61,9 @@ impl MessageRow {
     pub fn message_custom_timestamp(&self, ui: &mut egui::Ui, d: &MessageCached, time: &Instant) {
         let desc = d.msg.parsed_type.to_string();
 
+        let highlighted = self
+            .highlight
+            .is_some_and(|(from, to)| (from..=to).contains(&d.get_timestamp()));
         let time = d.get_timestamp().duration_since(*time).as_secs_f32();
         let time = format!("{time:.6}");
         let cob = &d.cob_str;
@@ -60,7 +74,11 @@ impl MessageRow {
             "   ".to_owned()
         };
 
-        ui.label(time);
+        if highlighted {
+            ui.label(RichText::new(time).color(OZON_PINK).strong());
+        } else {
+            ui.label(time);
+        }
         ui.label(cob);
         ui.label(data).on_hover_ui(|ui| {
             // data in all formats on hover
@@ -68,9 +86,12 @@ impl MessageRow {
             ui.label(format!("BIN:   {}", d.bin_str));
             ui.label(format!("ASCII: {}", d.ascii_str));
         });
+        if let Some(fields) = &self.fields {
+            ui.label(fields.decode(d).unwrap_or_default());
+        }
         ui.label(desc);
         ui.label(node_id);
-        ui.label(d.additional.to_string())
+        ui.label(&d.info_str)
             .on_hover_text_at_pointer(d.additional.get_tooltip());
     }
 }
@@ -0,0 +1,189 @@
+use crate::{
+    heartbeat::NmtState,
+    message_cached::{MessageCached, RxMessageAdditional},
+    sdo_session::{SdoResult, SdoTransfer},
+};
+use oze_canopen::{canopen::NodeId, proto::nmt::NmtCommandSpecifier};
+use std::{collections::BTreeMap, time::Duration};
+use tokio::time::Instant;
+
+/// Manufacturer device name, read by SDO upload.
+const DEVICE_NAME: u16 = 0x1008;
+/// Messages are counted over this window to calculate the rate.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Status of one node collected from all its messages.
+#[derive(Debug, Clone)]
+pub struct NodeStatus {
+    pub node_id: NodeId,
+    /// State from the last heartbeat, boot-up or node guarding response.
+    pub nmt_state: Option<NmtState>,
+    /// Last NMT command addressed to the node or broadcast.
+    pub nmt_command: Option<NmtCommandSpecifier>,
+    pub last_heartbeat: Option<Instant>,
+    pub boot_ups: u32,
+    pub emcy_count: u32,
+    pub last_emcy: Option<String>,
+    pub sdo_errors: u32,
+    pub device_name: Option<String>,
+    pub messages: u64,
+    pub last_seen: Instant,
+    rate: f64,
+    window_start: Instant,
+    window_count: u32,
+}
+
+impl NodeStatus {
+    fn new(node_id: NodeId, time: Instant) -> Self {
+        Self {
+            node_id,
+            nmt_state: None,
+            nmt_command: None,
+            last_heartbeat: None,
+            boot_ups: 0,
+            emcy_count: 0,
+            last_emcy: None,
+            sdo_errors: 0,
+            device_name: None,
+            messages: 0,
+            last_seen: time,
+            rate: 0.0,
+            window_start: time,
+            window_count: 0,
+        }
+    }
+
+    fn count(&mut self, time: Instant) {
+        self.messages += 1;
+        self.last_seen = time;
+        self.window_count += 1;
+        let elapsed = time.saturating_duration_since(self.window_start);
+        if elapsed >= RATE_WINDOW {
+            self.rate = f64::from(self.window_count) / elapsed.as_secs_f64();
+            self.window_start = time;
+            self.window_count = 0;
+        }
+    }
+
+    /// Messages per second, zero once the node stayed silent for two windows.
+    pub fn rate(&self, now: Instant) -> f64 {
+        if now.saturating_duration_since(self.last_seen) > 2 * RATE_WINDOW {
+            0.0
+        } else {
+            self.rate
+        }
+    }
+}
+
+/// Collects the status of every node seen on the bus.
+#[derive(Debug, Default)]
+pub struct Nodes {
+    nodes: BTreeMap<NodeId, NodeStatus>,
+}
+
+impl Nodes {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        let time = msg.get_timestamp();
+        if let RxMessageAdditional::Nmt(nmt) = &msg.additional {
+            for node in self.nodes.values_mut() {
+                if nmt.node_id == 0 || nmt.node_id == node.node_id {
+                    node.nmt_command = Some(nmt.command_specifier);
+                }
+            }
+            return;
+        }
+
+        let Some(node_id) = msg.msg.parsed_node_id else {
+            return;
+        };
+        let node = self
+            .nodes
+            .entry(node_id)
+            .or_insert_with(|| NodeStatus::new(node_id, time));
+        node.count(time);
+
+        match &msg.additional {
+            RxMessageAdditional::Heartbeat(h) => {
+                if h.state == NmtState::BootUp {
+                    node.boot_ups += 1;
+                }
+                node.nmt_state = Some(h.state);
+                node.last_heartbeat = Some(time);
+            }
+            RxMessageAdditional::Emcy(e) => {
+                node.emcy_count += 1;
+                node.last_emcy = Some(e.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    /// Counts failed SDO transfers and takes the device name from uploads of 0x1008.
+    pub fn push_transfer(&mut self, transfer: &SdoTransfer) {
+        let Some(node) = self.nodes.get_mut(&transfer.node_id) else {
+            return;
+        };
+        match &transfer.result {
+            SdoResult::InProgress => {}
+            SdoResult::Success => {
+                if transfer.index == DEVICE_NAME && !transfer.data.is_empty() {
+                    let name = String::from_utf8_lossy(&transfer.data);
+                    node.device_name = Some(name.trim_end_matches('\0').trim().to_owned());
+                }
+            }
+            _ => node.sdo_errors += 1,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NodeStatus> {
+        self.nodes.values()
+    }
+
+    pub fn get(&self, node_id: NodeId) -> Option<&NodeStatus> {
+        self.nodes.get(&node_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Nodes;
+    use crate::{heartbeat::NmtState, message_cached::test_message};
+    use oze_canopen::proto::nmt::NmtCommandSpecifier;
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_node_status() {
+        let mut nodes = Nodes::default();
+        nodes.push_data(&test_message(0x705, &[0x00], Instant::now()));
+        nodes.push_data(&test_message(0x705, &[0x7F], Instant::now()));
+        nodes.push_data(&test_message(0x000, &[0x01, 0x00], Instant::now()));
+        nodes.push_data(&test_message(
+            0x085,
+            &[0x10, 0x81, 0x11, 0, 0, 0, 0, 0],
+            Instant::now(),
+        ));
+        nodes.push_data(&test_message(0x185, &[0x01], Instant::now()));
+        nodes.push_data(&test_message(0x705, &[0x05], Instant::now()));
+
+        let node = nodes.get(5).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(node.messages, 5);
+        assert_eq!(node.boot_ups, 1);
+        assert_eq!(node.emcy_count, 1);
+        assert!(node.last_emcy.is_some());
+        assert_eq!(node.nmt_state, Some(NmtState::Operational));
+        assert_eq!(node.nmt_command, Some(NmtCommandSpecifier::StartRemoteNode));
+    }
+}
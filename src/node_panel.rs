@@ -0,0 +1,127 @@
+use crate::{
+    heartbeat::NmtState,
+    message_cached::MessageCached,
+    node_overview::{NodeStatus, Nodes},
+    sdo_session::SdoTransfer,
+    theme::OZON_PINK,
+};
+use egui::RichText;
+use oze_canopen::canopen::NodeId;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Heartbeats older than this are shown as missing.
+const HEARTBEAT_STALE: Duration = Duration::from_secs(3);
+
+/// Shows which nodes are alive and in what state.
+#[derive(Debug, Default)]
+pub struct NodePanel {
+    nodes: Nodes,
+}
+
+fn show_node(ui: &mut egui::Ui, node: &NodeStatus, now: Instant) -> bool {
+    let clicked = ui
+        .link(format!("{:3}", node.node_id))
+        .on_hover_text("Filter the viewer to this node")
+        .clicked();
+    ui.label(node.device_name.as_deref().unwrap_or("-"));
+
+    let state = node
+        .nmt_state
+        .map_or_else(|| "-".to_owned(), |s| s.to_string());
+    if matches!(
+        node.nmt_state,
+        Some(NmtState::Stopped | NmtState::Unknown(_))
+    ) {
+        ui.colored_label(OZON_PINK, state);
+    } else {
+        ui.label(state);
+    }
+    ui.label(
+        node.nmt_command
+            .map_or_else(|| "-".to_owned(), |c| format!("{c:?}")),
+    );
+
+    match node.last_heartbeat {
+        Some(last) => {
+            let age = now.saturating_duration_since(last);
+            let text = format!("{:.1} s", age.as_secs_f32());
+            if age > HEARTBEAT_STALE {
+                ui.colored_label(OZON_PINK, text);
+            } else {
+                ui.label(text);
+            }
+        }
+        None => {
+            ui.label("-");
+        }
+    }
+
+    ui.label(node.boot_ups.to_string());
+    if node.emcy_count > 0 {
+        ui.colored_label(OZON_PINK, node.emcy_count.to_string());
+    } else {
+        ui.label("0");
+    }
+    ui.label(node.last_emcy.as_deref().unwrap_or("-"));
+    if node.sdo_errors > 0 {
+        ui.colored_label(OZON_PINK, node.sdo_errors.to_string());
+    } else {
+        ui.label("0");
+    }
+    ui.label(format!("{:.1}", node.rate(now)));
+    clicked
+}
+
+impl NodePanel {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        self.nodes.push_data(msg);
+    }
+
+    pub fn push_transfer(&mut self, transfer: &SdoTransfer) {
+        self.nodes.push_transfer(transfer);
+    }
+
+    /// Returns the node clicked to filter the viewer.
+    pub fn update(&mut self, ui: &mut egui::Ui) -> Option<NodeId> {
+        ui.horizontal(|ui| {
+            ui.label(format!("nodes={}", self.nodes.len()));
+            if ui.button("🗑").on_hover_text("Clear nodes").clicked() {
+                self.nodes.clear();
+            }
+        });
+
+        let now = Instant::now();
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .id_salt("nodes")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("nodes_grid").striped(true).show(ui, |ui| {
+                    for header in [
+                        "Node ID",
+                        "Device name",
+                        "NMT state",
+                        "Last NMT command",
+                        "Heartbeat",
+                        "Boot-ups",
+                        "EMCY",
+                        "Last EMCY",
+                        "SDO errors",
+                        "msg/s",
+                    ] {
+                        ui.label(RichText::new(header).strong());
+                    }
+                    ui.end_row();
+
+                    for node in self.nodes.iter() {
+                        if show_node(ui, node, now) {
+                            clicked = Some(node.node_id);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        clicked
+    }
+}
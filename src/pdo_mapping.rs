@@ -0,0 +1,489 @@
+use crate::{
+    cia402,
+    message_cached::{MessageCached, RxMessageAdditional},
+};
+use core::fmt;
+use oze_canopen::{
+    canopen::{NodeId, RxMessageType},
+    proto::{
+        sdo::{Flags, ResponseData, SdoRequest, SdoRequestData, SdoResponse},
+        CobId,
+    },
+};
+use std::collections::HashMap;
+
+const RPDO_COMM: u16 = 0x1400;
+const RPDO_MAPPING: u16 = 0x1600;
+const RPDO_PARAMS_END: u16 = 0x17FF;
+const TPDO_COMM: u16 = 0x1800;
+const TPDO_MAPPING: u16 = 0x1A00;
+const PDO_PARAMS_END: u16 = 0x1BFF;
+const PDO_PARAMS_RANGE: u16 = 0x200;
+
+/// Set in a PDO COB-ID entry when the PDO is disabled.
+const COB_ID_INVALID: u32 = 0x8000_0000;
+/// Indexes below this value in a mapping entry are dummy (padding) objects.
+const DUMMY_INDEX_END: u16 = 0x0020;
+
+/// Direction of a PDO as seen from the node that owns its parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PdoKind {
+    Rx,
+    Tx,
+}
+
+/// Identifies one PDO of one node, e.g. TPDO1 of node 5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PdoKey {
+    pub node_id: NodeId,
+    pub kind: PdoKind,
+    /// Zero based PDO number, TPDO1 has number 0.
+    pub number: u16,
+}
+
+/// Object mapped into a PDO, as written to the mapping parameter (0x1600/0x1A00).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MappedObject {
+    pub index: u16,
+    pub subindex: u8,
+    pub bits: u8,
+}
+
+/// PDO layout reconstructed from the communication and mapping parameters of one PDO.
+#[derive(Debug, Default, Clone)]
+pub struct PdoLayout {
+    /// Raw value of the COB-ID entry (subindex 1 of the communication parameter) if it was observed.
+    pub cob_id: Option<u32>,
+    /// Number of valid mapping entries (subindex 0 of the mapping parameter).
+    pub count: u8,
+    entries: HashMap<u8, MappedObject>,
+}
+
+/// Value of one mapped object extracted from a PDO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdoValue {
+    pub object: MappedObject,
+    pub raw: u64,
+}
+
+/// PDO decoded with a learned layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdoDecoded {
+    pub key: PdoKey,
+    pub values: Vec<PdoValue>,
+}
+
+/// SDO download to a PDO parameter which was not confirmed by the server yet.
+#[derive(Debug, Clone)]
+struct PendingDownload {
+    index: u16,
+    subindex: u8,
+    data: Vec<u8>,
+    complete: bool,
+}
+
+/// Passive tracker of PDO configuration.
+///
+/// Watches SDO downloads to the PDO communication and mapping parameters (0x1400-0x1BFF),
+/// applies them once the server confirms the transfer and uses the resulting layouts to decode PDOs.
+#[derive(Debug, Default, Clone)]
+pub struct PdoMappingTracker {
+    layouts: HashMap<PdoKey, PdoLayout>,
+    by_cob_id: HashMap<CobId, PdoKey>,
+    pending: HashMap<NodeId, PendingDownload>,
+}
+
+impl MappedObject {
+    fn from_raw(raw: u32) -> Self {
+        let [bits, subindex, index_lo, index_hi] = raw.to_le_bytes();
+        Self {
+            index: u16::from_le_bytes([index_lo, index_hi]),
+            subindex,
+            bits,
+        }
+    }
+
+    /// Returns `true` for dummy entries which only reserve space in the PDO.
+    pub fn is_dummy(&self) -> bool {
+        self.index < DUMMY_INDEX_END
+    }
+}
+
+impl PdoKey {
+    /// COB-ID from the predefined connection set, used when the COB-ID entry was not observed.
+    fn default_cob_id(self) -> Option<CobId> {
+        if self.number >= 4 {
+            return None;
+        }
+
+        let base = match self.kind {
+            PdoKind::Tx => 0x180,
+            PdoKind::Rx => 0x200,
+        };
+        Some(base + 0x100 * self.number + CobId::from(self.node_id))
+    }
+}
+
+impl fmt::Display for PdoKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            PdoKind::Rx => "RPDO",
+            PdoKind::Tx => "TPDO",
+        };
+        write!(f, "{kind}{}", self.number + 1)
+    }
+}
+
+impl PdoLayout {
+    /// COB-ID this PDO is transmitted with, `None` if the PDO is disabled.
+    fn effective_cob_id(&self, key: PdoKey) -> Option<CobId> {
+        match self.cob_id {
+            Some(raw) if raw & COB_ID_INVALID != 0 => None,
+            Some(raw) => CobId::try_from(raw & 0x7FF).ok(),
+            None => key.default_cob_id(),
+        }
+    }
+
+    /// Iterates over the mapped objects in the order they appear in the PDO.
+    pub fn mapped(&self) -> impl Iterator<Item = &MappedObject> {
+        (1..=self.count).filter_map(|i| self.entries.get(&i))
+    }
+}
+
+impl PdoValue {
+    /// Formats the raw value as zero padded hex with the width of the mapped object.
+    pub fn to_hex(&self) -> String {
+        let width = usize::from(self.object.bits).div_ceil(4);
+        format!("{:0width$X}", self.raw)
+    }
+}
+
+impl fmt::Display for PdoDecoded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.key)?;
+        for v in &self.values {
+            write!(
+                f,
+                " {:04X}:{:02X}={}",
+                v.object.index,
+                v.object.subindex,
+                v.to_hex()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl PdoDecoded {
+    pub fn get_tooltip(&self) -> String {
+        self.values
+            .iter()
+            .map(|v| {
+                let line = format!(
+                    "{:04X}:{:02X} ({} bit) = 0x{} = {}",
+                    v.object.index,
+                    v.object.subindex,
+                    v.object.bits,
+                    v.to_hex(),
+                    v.raw
+                );
+                match cia402::describe(v.object.index, v.raw) {
+                    Some(desc) => format!("{line} ({desc})"),
+                    None => line,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Returns the raw value of the given object if it is mapped into this PDO.
+    pub fn get(&self, index: u16, subindex: u8) -> Option<u64> {
+        self.values
+            .iter()
+            .find(|v| v.object.index == index && v.object.subindex == subindex)
+            .map(|v| v.raw)
+    }
+}
+
+fn is_pdo_parameter(index: u16) -> bool {
+    (RPDO_COMM..=PDO_PARAMS_END).contains(&index)
+}
+
+/// Extracts `bits` bits starting at bit `offset` from little endian data.
+fn extract_bits(data: [u8; 8], offset: usize, bits: usize) -> u64 {
+    let value = u64::from_le_bytes(data) >> offset;
+    if bits >= 64 {
+        value
+    } else {
+        value & ((1u64 << bits) - 1)
+    }
+}
+
+impl PdoMappingTracker {
+    /// Observes SDO traffic and decodes PDOs using the learned layouts.
+    pub fn process(&mut self, msg: &mut MessageCached) {
+        let Some(node_id) = msg.msg.parsed_node_id else {
+            return;
+        };
+
+        match &msg.additional {
+            RxMessageAdditional::SdoRx(req) => self.observe_request(node_id, req),
+            RxMessageAdditional::SdoTx(resp) => self.observe_response(node_id, resp),
+            _ => {}
+        }
+
+        if msg.msg.parsed_type == RxMessageType::Pdo {
+            if let Some(decoded) = self.decode(msg) {
+                msg.additional = RxMessageAdditional::Pdo(decoded);
+            }
+        }
+    }
+
+    /// Returns the learned layout of the given PDO.
+    pub fn layout(&self, key: &PdoKey) -> Option<&PdoLayout> {
+        self.layouts.get(key)
+    }
+
+    fn observe_request(&mut self, node_id: NodeId, req: &SdoRequest) {
+        match &req.req {
+            SdoRequestData::InitiateDownloadExpedited(r) if is_pdo_parameter(r.index) => {
+                self.pending.insert(
+                    node_id,
+                    PendingDownload {
+                        index: r.index,
+                        subindex: r.subindex,
+                        data: r.data.clone(),
+                        complete: true,
+                    },
+                );
+            }
+            SdoRequestData::InitiateDownload(r) if is_pdo_parameter(r.index) => {
+                self.pending.insert(
+                    node_id,
+                    PendingDownload {
+                        index: r.index,
+                        subindex: r.subindex,
+                        data: Vec::new(),
+                        complete: false,
+                    },
+                );
+            }
+            SdoRequestData::DownloadSegment(seg) => {
+                if let Some(pending) = self.pending.get_mut(&node_id) {
+                    pending.data.extend_from_slice(&seg.data);
+                    // c bit set means this is the last segment
+                    pending.complete = req.cmd.contains(Flags::CONTINUE);
+                }
+            }
+            _ => {
+                self.pending.remove(&node_id);
+            }
+        }
+    }
+
+    fn observe_response(&mut self, node_id: NodeId, resp: &SdoResponse) {
+        match &resp.resp {
+            ResponseData::Download(r) => {
+                let Some(pending) = self.pending.get(&node_id) else {
+                    return;
+                };
+                if pending.complete && pending.index == r.index && pending.subindex == r.subindex {
+                    let pending = self.pending.remove(&node_id).unwrap();
+                    self.apply(node_id, &pending);
+                }
+            }
+            ResponseData::DownloadSegment(_) => {
+                if self.pending.get(&node_id).is_some_and(|p| p.complete) {
+                    let pending = self.pending.remove(&node_id).unwrap();
+                    self.apply(node_id, &pending);
+                }
+            }
+            ResponseData::Abort(_) => {
+                self.pending.remove(&node_id);
+            }
+            ResponseData::Upload(_) | ResponseData::UploadSegment(_) => {}
+        }
+    }
+
+    fn apply(&mut self, node_id: NodeId, pending: &PendingDownload) {
+        let mut bytes = [0u8; 4];
+        let len = pending.data.len().min(4);
+        bytes[..len].copy_from_slice(&pending.data[..len]);
+        let value = u32::from_le_bytes(bytes);
+
+        let (kind, base) = match pending.index {
+            RPDO_COMM..=RPDO_PARAMS_END => (PdoKind::Rx, pending.index & !(PDO_PARAMS_RANGE - 1)),
+            TPDO_COMM..=PDO_PARAMS_END => (PdoKind::Tx, pending.index & !(PDO_PARAMS_RANGE - 1)),
+            _ => return,
+        };
+        let is_mapping = base == RPDO_MAPPING || base == TPDO_MAPPING;
+        let key = PdoKey {
+            node_id,
+            kind,
+            number: pending.index - base,
+        };
+
+        let layout = self.layouts.entry(key).or_default();
+        match (is_mapping, pending.subindex) {
+            (false, 1) => layout.cob_id = Some(value),
+            (true, 0) => layout.count = bytes[0],
+            (true, sub) => {
+                layout.entries.insert(sub, MappedObject::from_raw(value));
+            }
+            (false, _) => return,
+        }
+
+        self.rebuild_cob_ids();
+    }
+
+    fn rebuild_cob_ids(&mut self) {
+        self.by_cob_id = self
+            .layouts
+            .iter()
+            .filter_map(|(key, layout)| Some((layout.effective_cob_id(*key)?, *key)))
+            .collect();
+    }
+
+    /// Decodes a PDO with the layout learned for its COB-ID.
+    pub fn decode(&self, msg: &MessageCached) -> Option<PdoDecoded> {
+        let key = self.by_cob_id.get(&msg.msg.msg.cob_id)?;
+        let layout = self.layouts.get(key)?;
+        if layout.count == 0 {
+            return None;
+        }
+
+        let available = msg.msg.msg.dlc.min(8) * 8;
+        let mut offset = 0;
+        let mut values = Vec::with_capacity(usize::from(layout.count));
+        for object in layout.mapped() {
+            let bits = usize::from(object.bits);
+            if bits == 0 {
+                // malformed entry without data, could shift past the 64 bits
+                continue;
+            }
+            if offset + bits > available {
+                break;
+            }
+
+            if !object.is_dummy() {
+                values.push(PdoValue {
+                    object: *object,
+                    raw: extract_bits(msg.msg.msg.data, offset, bits),
+                });
+            }
+            offset += bits;
+        }
+
+        Some(PdoDecoded { key: *key, values })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PdoDecoded, PdoKey, PdoKind, PdoMappingTracker};
+    use crate::message_cached::{test_message, MessageCached, RxMessageAdditional};
+    use tokio::time::Instant;
+
+    fn feed(tracker: &mut PdoMappingTracker, cob_id: u16, data: &[u8]) -> MessageCached {
+        let mut msg = test_message(cob_id, data, Instant::now());
+        tracker.process(&mut msg);
+        msg
+    }
+
+    fn decoded(msg: &MessageCached) -> Option<&PdoDecoded> {
+        match &msg.additional {
+            RxMessageAdditional::Pdo(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// Expedited download of a u32 followed by the server confirmation.
+    fn download(tracker: &mut PdoMappingTracker, node: u16, index: u16, sub: u8, value: u32) {
+        let [i0, i1] = index.to_le_bytes();
+        let [v0, v1, v2, v3] = value.to_le_bytes();
+        feed(tracker, 0x600 + node, &[0x23, i0, i1, sub, v0, v1, v2, v3]);
+        feed(tracker, 0x580 + node, &[0x60, i0, i1, sub, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_learn_tpdo_mapping() {
+        let mut tracker = PdoMappingTracker::default();
+        download(&mut tracker, 5, 0x1A00, 1, 0x6041_0010);
+        download(&mut tracker, 5, 0x1A00, 2, 0x6064_0020);
+        download(&mut tracker, 5, 0x1A00, 0, 2);
+
+        let msg = feed(&mut tracker, 0x185, &[0x37, 0x02, 0xD2, 0x04, 0, 0]);
+        let decoded = decoded(&msg).unwrap();
+        assert_eq!(
+            decoded.key,
+            PdoKey {
+                node_id: 5,
+                kind: PdoKind::Tx,
+                number: 0
+            }
+        );
+        assert_eq!(decoded.get(0x6041, 0), Some(0x0237));
+        assert_eq!(decoded.get(0x6064, 0), Some(0x04D2));
+        assert_eq!(decoded.to_string(), "TPDO1 6041:00=0237 6064:00=000004D2");
+    }
+
+    #[test]
+    fn test_zero_length_entry_after_64_bits() {
+        let mut tracker = PdoMappingTracker::default();
+        download(&mut tracker, 5, 0x1A01, 1, 0x6065_0040);
+        download(&mut tracker, 5, 0x1A01, 2, 0x6066_0000);
+        download(&mut tracker, 5, 0x1A01, 0, 2);
+        let msg = feed(&mut tracker, 0x285, &[1, 0, 0, 0, 0, 0, 0, 0]);
+        let tpdo2 = decoded(&msg).unwrap();
+        assert_eq!(tpdo2.get(0x6065, 0), Some(1));
+        assert_eq!(tpdo2.get(0x6066, 0), None);
+    }
+
+    #[test]
+    fn test_custom_cob_id_and_disable() {
+        let mut tracker = PdoMappingTracker::default();
+        download(&mut tracker, 3, 0x1601, 1, 0x2000_0108);
+        download(&mut tracker, 3, 0x1601, 0, 1);
+        download(&mut tracker, 3, 0x1401, 1, 0x0000_0456);
+
+        let msg = feed(&mut tracker, 0x456, &[0xAA]);
+        assert_eq!(decoded(&msg).unwrap().get(0x2000, 1), Some(0xAA));
+        // default COB-ID of RPDO2 is not used anymore
+        let msg = feed(&mut tracker, 0x303, &[0xAA]);
+        assert!(decoded(&msg).is_none());
+
+        download(&mut tracker, 3, 0x1401, 1, 0x8000_0456);
+        let msg = feed(&mut tracker, 0x456, &[0xAA]);
+        assert!(decoded(&msg).is_none());
+    }
+
+    #[test]
+    fn test_unconfirmed_download_ignored() {
+        let mut tracker = PdoMappingTracker::default();
+        feed(
+            &mut tracker,
+            0x605,
+            &[0x23, 0x00, 0x1A, 1, 0x08, 0, 0, 0x20],
+        );
+        // abort from the server
+        feed(
+            &mut tracker,
+            0x585,
+            &[0x80, 0x00, 0x1A, 1, 0x41, 0, 0x04, 0x06],
+        );
+        download(&mut tracker, 5, 0x1A00, 0, 1);
+
+        assert_eq!(
+            tracker
+                .layout(&PdoKey {
+                    node_id: 5,
+                    kind: PdoKind::Tx,
+                    number: 0
+                })
+                .unwrap()
+                .mapped()
+                .count(),
+            0
+        );
+    }
+}
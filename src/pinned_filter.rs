@@ -1,7 +1,8 @@
 use crate::{
-    filter_data_panel::FilterDataPanel, message_cached::MessageCached, message_row::MessageRow,
+    filter_data_panel::FilterDataPanel, history::History, message_cached::MessageCached,
+    message_row::MessageRow,
 };
-use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+use std::{cell::RefCell, rc::Rc};
 use tokio::time::Instant;
 
 #[derive(Debug, Default)]
@@ -11,9 +12,9 @@ pub struct PinnedFilters {
 }
 
 impl PinnedFilters {
-    pub fn pin_filter(&mut self, mut filt: FilterDataPanel, data: &VecDeque<MessageCached>) {
+    pub fn pin_filter(&mut self, mut filt: FilterDataPanel, history: &History) {
         let data_filter = filt.data_filter.borrow().clone();
-        let new_data = data.iter().find(|i| !data_filter.filter(i));
+        let new_data = history.iter().find(|i| !data_filter.filter(i));
 
         filt.data_filter = Rc::new(RefCell::new(data_filter));
         self.data.push((filt, Instant::now(), new_data.cloned()));
@@ -0,0 +1,535 @@
+use crate::message_cached::{MessageCached, RxMessageAdditional};
+use core::fmt;
+use oze_canopen::canopen::{NodeId, RxMessageType};
+use std::collections::HashMap;
+
+/// Command specifier of the block download requests and block upload responses.
+const CS_BLOCK_DOWNLOAD: u8 = 6;
+/// Command specifier of the block upload requests and block download responses.
+const CS_BLOCK_UPLOAD: u8 = 5;
+/// First byte of the abort transfer frame.
+const ABORT_CMD: u8 = 0x80;
+
+/// Subcommand bits, for initiate frames they also contain the size indicator.
+const SUBCOMMAND_MASK: u8 = 0b0000_0011;
+const SUB_INITIATE: u8 = 0;
+const SUB_END: u8 = 1;
+const SUB_ACK: u8 = 2;
+const SUB_START: u8 = 3;
+
+const CRC_SUPPORTED: u8 = 0b0000_0100;
+const SIZE_INDICATED: u8 = 0b0000_0010;
+const LAST_SEGMENT: u8 = 0b1000_0000;
+
+/// Decoded frame of an SDO block download or block upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SdoBlock {
+    InitiateDownloadRequest {
+        index: u16,
+        subindex: u8,
+        size: Option<u32>,
+        crc: bool,
+    },
+    InitiateDownloadResponse {
+        index: u16,
+        subindex: u8,
+        blksize: u8,
+        crc: bool,
+    },
+    InitiateUploadRequest {
+        index: u16,
+        subindex: u8,
+        blksize: u8,
+        /// Protocol switch threshold.
+        pst: u8,
+        crc: bool,
+    },
+    InitiateUploadResponse {
+        index: u16,
+        subindex: u8,
+        size: Option<u32>,
+        crc: bool,
+    },
+    StartUpload,
+    Segment {
+        seqno: u8,
+        last: bool,
+        data: [u8; 7],
+    },
+    /// Acknowledgement of a sub-block.
+    Ack {
+        ackseq: u8,
+        blksize: u8,
+    },
+    /// End of block download request or end of block upload response.
+    End {
+        /// Number of bytes in the last segment that do not contain data.
+        unused: u8,
+        crc: u16,
+        /// CRC calculated over the reassembled data, `None` if CRC is not used or data was not seen.
+        calculated: Option<u16>,
+    },
+    /// End of block download response or end of block upload acknowledgement.
+    EndResponse,
+}
+
+/// Data of one block transfer, reassembled from sub-blocks taking acknowledgements into account.
+#[derive(Debug, Default, Clone)]
+pub struct BlockReassembly {
+    data: Vec<u8>,
+    sub_block: Vec<(u8, [u8; 7], bool)>,
+    complete: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockDirection {
+    Download,
+    Upload,
+}
+
+#[derive(Debug, Clone)]
+struct BlockState {
+    direction: BlockDirection,
+    crc: bool,
+    segments: bool,
+    reassembly: BlockReassembly,
+}
+
+/// Stateful decoder of SDO block transfers.
+///
+/// Sub-block segments carry only a sequence number, so they can be told apart from other SDO
+/// frames only by following the transfer of each node from its initiate frames.
+#[derive(Debug, Default, Clone)]
+pub struct SdoBlockTracker {
+    nodes: HashMap<NodeId, BlockState>,
+}
+
+/// CRC-16-CCITT (polynomial 0x1021, initial value 0) used by the block transfers.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x1021
+            };
+        }
+    }
+    crc
+}
+
+impl BlockReassembly {
+    pub fn push_segment(&mut self, seqno: u8, last: bool, data: [u8; 7]) {
+        self.sub_block.push((seqno, data, last));
+    }
+
+    /// Commits segments of the sub-block up to `ackseq`, the rest will be retransmitted.
+    pub fn ack(&mut self, ackseq: u8) {
+        for (expected, (seqno, data, last)) in (1..).zip(self.sub_block.drain(..)) {
+            if seqno != expected || seqno > ackseq {
+                break;
+            }
+            self.data.extend_from_slice(&data);
+            self.complete |= last;
+        }
+    }
+
+    /// Returns `true` when the last segment was acknowledged.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Reassembled data without the unused bytes of the last segment.
+    pub fn finish(&mut self, unused: u8) -> &[u8] {
+        let len = self.data.len().saturating_sub(usize::from(unused));
+        self.data.truncate(len);
+        &self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl fmt::Display for SdoBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let crc_str = |crc: &bool| if *crc { " crc" } else { "" };
+        match self {
+            SdoBlock::InitiateDownloadRequest {
+                index,
+                subindex,
+                size,
+                crc,
+            } => write!(
+                f,
+                "Req  BlkDown  ind {index:X} sub {subindex:X} size {size:X?}{}",
+                crc_str(crc)
+            ),
+            SdoBlock::InitiateDownloadResponse {
+                index,
+                subindex,
+                blksize,
+                crc,
+            } => write!(
+                f,
+                "Resp BlkDown  ind {index:X} sub {subindex:X} blksize {blksize}{}",
+                crc_str(crc)
+            ),
+            SdoBlock::InitiateUploadRequest {
+                index,
+                subindex,
+                blksize,
+                pst,
+                crc,
+            } => write!(
+                f,
+                "Req  BlkUp    ind {index:X} sub {subindex:X} blksize {blksize} pst {pst}{}",
+                crc_str(crc)
+            ),
+            SdoBlock::InitiateUploadResponse {
+                index,
+                subindex,
+                size,
+                crc,
+            } => write!(
+                f,
+                "Resp BlkUp    ind {index:X} sub {subindex:X} size {size:X?}{}",
+                crc_str(crc)
+            ),
+            SdoBlock::StartUpload => write!(f, "Req  BlkStart"),
+            SdoBlock::Segment { seqno, last, data } => write!(
+                f,
+                "Blk  Seg {seqno:3} {data:X?}{}",
+                if *last { " last" } else { "" }
+            ),
+            SdoBlock::Ack { ackseq, blksize } => {
+                write!(f, "Blk  Ack      seq {ackseq} blksize {blksize}")
+            }
+            SdoBlock::End {
+                unused,
+                crc,
+                calculated,
+            } => {
+                write!(f, "Blk  End      n {unused} crc {crc:04X}")?;
+                match calculated {
+                    Some(c) if c == crc => write!(f, " OK"),
+                    Some(c) => write!(f, " MISMATCH calculated {c:04X}"),
+                    None => Ok(()),
+                }
+            }
+            SdoBlock::EndResponse => write!(f, "Blk  EndResp"),
+        }
+    }
+}
+
+impl SdoBlock {
+    /// Returns `true` if CRC was verified and did not match.
+    pub fn is_crc_error(&self) -> bool {
+        matches!(self, SdoBlock::End { crc, calculated: Some(c), .. } if c != crc)
+    }
+}
+
+fn index_of(data: [u8; 8]) -> (u16, u8) {
+    (u16::from_le_bytes([data[1], data[2]]), data[3])
+}
+
+fn size_of(cmd: u8, data: [u8; 8]) -> Option<u32> {
+    (cmd & SIZE_INDICATED != 0).then(|| u32::from_le_bytes([data[4], data[5], data[6], data[7]]))
+}
+
+impl SdoBlockTracker {
+    /// Decodes block transfer frames and replaces the regular SDO decoding of them.
+    pub fn process(&mut self, msg: &mut MessageCached) {
+        let from_client = match msg.msg.parsed_type {
+            RxMessageType::SdoRx => true,
+            RxMessageType::SdoTx => false,
+            _ => return,
+        };
+        let Some(node_id) = msg.msg.parsed_node_id else {
+            return;
+        };
+
+        if let Some(block) = self.decode(node_id, from_client, msg.msg.msg.data) {
+            msg.additional = RxMessageAdditional::SdoBlock(block);
+        }
+    }
+
+    fn decode(&mut self, node_id: NodeId, from_client: bool, data: [u8; 8]) -> Option<SdoBlock> {
+        // Sequence numbers start at 1, so a segment can't look exactly like an abort frame.
+        if data[0] == ABORT_CMD {
+            self.nodes.remove(&node_id);
+            return None;
+        }
+
+        if let Some(segment) = self.decode_segment(node_id, from_client, data) {
+            return Some(segment);
+        }
+
+        if from_client {
+            self.decode_request(node_id, data)
+        } else {
+            self.decode_response(node_id, data)
+        }
+    }
+
+    fn decode_segment(
+        &mut self,
+        node_id: NodeId,
+        from_client: bool,
+        data: [u8; 8],
+    ) -> Option<SdoBlock> {
+        let state = self.nodes.get_mut(&node_id)?;
+        let segment_sender = match state.direction {
+            BlockDirection::Download => from_client,
+            BlockDirection::Upload => !from_client,
+        };
+        if !state.segments || !segment_sender {
+            return None;
+        }
+
+        let seqno = data[0] & !LAST_SEGMENT;
+        let last = data[0] & LAST_SEGMENT != 0;
+        let mut segment = [0u8; 7];
+        segment.copy_from_slice(&data[1..]);
+        state.reassembly.push_segment(seqno, last, segment);
+        Some(SdoBlock::Segment {
+            seqno,
+            last,
+            data: segment,
+        })
+    }
+
+    fn start(&mut self, node_id: NodeId, direction: BlockDirection, crc: bool) {
+        self.nodes.insert(
+            node_id,
+            BlockState {
+                direction,
+                crc,
+                segments: false,
+                reassembly: BlockReassembly::default(),
+            },
+        );
+    }
+
+    /// Decodes frames sent by the client.
+    fn decode_request(&mut self, node_id: NodeId, data: [u8; 8]) -> Option<SdoBlock> {
+        let cmd = data[0];
+        let (index, subindex) = index_of(data);
+        let crc = cmd & CRC_SUPPORTED != 0;
+        let block = match (cmd >> 5, cmd & SUBCOMMAND_MASK) {
+            (CS_BLOCK_DOWNLOAD, SUB_INITIATE | SIZE_INDICATED) => {
+                self.start(node_id, BlockDirection::Download, crc);
+                SdoBlock::InitiateDownloadRequest {
+                    index,
+                    subindex,
+                    size: size_of(cmd, data),
+                    crc,
+                }
+            }
+            (CS_BLOCK_DOWNLOAD, _) => self.end(node_id, data),
+            (CS_BLOCK_UPLOAD, SUB_INITIATE) => {
+                self.start(node_id, BlockDirection::Upload, crc);
+                SdoBlock::InitiateUploadRequest {
+                    index,
+                    subindex,
+                    blksize: data[4],
+                    pst: data[5],
+                    crc,
+                }
+            }
+            (CS_BLOCK_UPLOAD, SUB_START) => {
+                if let Some(state) = self.nodes.get_mut(&node_id) {
+                    state.segments = true;
+                }
+                SdoBlock::StartUpload
+            }
+            (CS_BLOCK_UPLOAD, SUB_ACK) => self.ack(node_id, data),
+            (CS_BLOCK_UPLOAD, SUB_END) => {
+                self.nodes.remove(&node_id);
+                SdoBlock::EndResponse
+            }
+            _ => return None,
+        };
+
+        Some(block)
+    }
+
+    /// Decodes frames sent by the server.
+    fn decode_response(&mut self, node_id: NodeId, data: [u8; 8]) -> Option<SdoBlock> {
+        let cmd = data[0];
+        let (index, subindex) = index_of(data);
+        let crc = cmd & CRC_SUPPORTED != 0;
+        let block = match (cmd >> 5, cmd & SUBCOMMAND_MASK) {
+            (CS_BLOCK_DOWNLOAD, SUB_INITIATE | SIZE_INDICATED) => {
+                self.confirm(node_id, crc);
+                SdoBlock::InitiateUploadResponse {
+                    index,
+                    subindex,
+                    size: size_of(cmd, data),
+                    crc,
+                }
+            }
+            (CS_BLOCK_DOWNLOAD, _) => self.end(node_id, data),
+            (CS_BLOCK_UPLOAD, SUB_INITIATE) => {
+                self.confirm(node_id, crc);
+                SdoBlock::InitiateDownloadResponse {
+                    index,
+                    subindex,
+                    blksize: data[4],
+                    crc,
+                }
+            }
+            (CS_BLOCK_UPLOAD, SUB_ACK) => self.ack(node_id, data),
+            (CS_BLOCK_UPLOAD, SUB_END) => {
+                self.nodes.remove(&node_id);
+                SdoBlock::EndResponse
+            }
+            _ => return None,
+        };
+
+        Some(block)
+    }
+
+    /// Server accepted the transfer, CRC is used only if both sides support it.
+    fn confirm(&mut self, node_id: NodeId, crc: bool) {
+        if let Some(state) = self.nodes.get_mut(&node_id) {
+            state.crc &= crc;
+            state.segments = state.direction == BlockDirection::Download;
+        }
+    }
+
+    fn ack(&mut self, node_id: NodeId, data: [u8; 8]) -> SdoBlock {
+        if let Some(state) = self.nodes.get_mut(&node_id) {
+            state.reassembly.ack(data[1]);
+            state.segments = !state.reassembly.is_complete();
+        }
+        SdoBlock::Ack {
+            ackseq: data[1],
+            blksize: data[2],
+        }
+    }
+
+    fn end(&mut self, node_id: NodeId, data: [u8; 8]) -> SdoBlock {
+        let unused = (data[0] >> 2) & 0b111;
+        let crc = u16::from_le_bytes([data[1], data[2]]);
+        let calculated = self
+            .nodes
+            .get_mut(&node_id)
+            .filter(|state| state.crc)
+            .map(|state| crc16(state.reassembly.finish(unused)));
+
+        SdoBlock::End {
+            unused,
+            crc,
+            calculated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc16, SdoBlock, SdoBlockTracker};
+    use crate::message_cached::{test_message, RxMessageAdditional};
+    use tokio::time::Instant;
+
+    fn feed(tracker: &mut SdoBlockTracker, cob_id: u16, data: [u8; 8]) -> Option<SdoBlock> {
+        let mut msg = test_message(cob_id, &data, Instant::now());
+        tracker.process(&mut msg);
+        match msg.additional {
+            RxMessageAdditional::SdoBlock(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(crc16(&[]), 0);
+    }
+
+    #[test]
+    fn test_block_download() {
+        let payload = b"0123456789ABCDEFGH";
+        let crc = crc16(payload).to_le_bytes();
+        let mut tracker = SdoBlockTracker::default();
+
+        let init = feed(&mut tracker, 0x605, [0xC6, 0x50, 0x1F, 1, 18, 0, 0, 0]).unwrap();
+        assert_eq!(
+            init,
+            SdoBlock::InitiateDownloadRequest {
+                index: 0x1F50,
+                subindex: 1,
+                size: Some(18),
+                crc: true
+            }
+        );
+        feed(&mut tracker, 0x585, [0xA4, 0x50, 0x1F, 1, 127, 0, 0, 0]).unwrap();
+
+        // segment 1 looks like a regular download segment if decoded without state
+        let seg = feed(
+            &mut tracker,
+            0x605,
+            [0x01, b'0', b'1', b'2', b'3', b'4', b'5', b'6'],
+        );
+        assert!(matches!(
+            seg,
+            Some(SdoBlock::Segment {
+                seqno: 1,
+                last: false,
+                ..
+            })
+        ));
+        // segment 2 is lost and retransmitted after the acknowledgement
+        feed(&mut tracker, 0x605, [0x03, b'E', b'F', b'G', b'H', 0, 0, 0]).unwrap();
+        feed(&mut tracker, 0x585, [0xA2, 1, 127, 0, 0, 0, 0, 0]).unwrap();
+        feed(
+            &mut tracker,
+            0x605,
+            [0x01, b'7', b'8', b'9', b'A', b'B', b'C', b'D'],
+        )
+        .unwrap();
+        feed(&mut tracker, 0x605, [0x82, b'E', b'F', b'G', b'H', 0, 0, 0]).unwrap();
+        feed(&mut tracker, 0x585, [0xA2, 2, 127, 0, 0, 0, 0, 0]).unwrap();
+
+        // 3 unused bytes in the last segment
+        let end = feed(&mut tracker, 0x605, [0xCD, crc[0], crc[1], 0, 0, 0, 0, 0]).unwrap();
+        assert!(!end.is_crc_error());
+        assert!(end.to_string().ends_with(" OK"));
+        assert_eq!(
+            feed(&mut tracker, 0x585, [0xA1, 0, 0, 0, 0, 0, 0, 0]),
+            Some(SdoBlock::EndResponse)
+        );
+
+        // regular SDO decoding again after the transfer
+        assert_eq!(
+            feed(&mut tracker, 0x605, [0x40, 0, 0x10, 0, 0, 0, 0, 0]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_block_upload_crc_error() {
+        let mut tracker = SdoBlockTracker::default();
+        feed(&mut tracker, 0x603, [0xA4, 0x00, 0x20, 0, 127, 0, 0, 0]).unwrap();
+        feed(&mut tracker, 0x583, [0xC6, 0x00, 0x20, 0, 4, 0, 0, 0]).unwrap();
+        assert_eq!(
+            feed(&mut tracker, 0x603, [0xA3, 0, 0, 0, 0, 0, 0, 0]),
+            Some(SdoBlock::StartUpload)
+        );
+        feed(&mut tracker, 0x583, [0x81, 1, 2, 3, 4, 0, 0, 0]).unwrap();
+        feed(&mut tracker, 0x603, [0xA2, 1, 127, 0, 0, 0, 0, 0]).unwrap();
+
+        let end = feed(&mut tracker, 0x583, [0xCD, 0xAD, 0xDE, 0, 0, 0, 0, 0]).unwrap();
+        assert!(end.is_crc_error());
+        assert_eq!(
+            feed(&mut tracker, 0x603, [0xA1, 0, 0, 0, 0, 0, 0, 0]),
+            Some(SdoBlock::EndResponse)
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{
+    message_cached::MessageCached,
+    message_row::MessageRow,
+    sdo_session::{SdoResult, SdoSessions, SdoTransfer},
+    theme::OZON_PINK,
+};
+use egui::{CollapsingHeader, RichText};
+
+#[derive(Debug, Default)]
+pub struct SdoPanel {
+    sessions: SdoSessions,
+    pub message_row: MessageRow,
+}
+
+impl SdoPanel {
+    /// Returns the transfer finished by this message.
+    pub fn push_data(&mut self, msg: &MessageCached) -> Option<&SdoTransfer> {
+        self.sessions.push_data(msg)
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("transfers={}", self.sessions.len()));
+            if ui
+                .button("🗑")
+                .on_hover_text("Clear SDO transfers")
+                .clicked()
+            {
+                self.sessions.clear();
+            }
+        });
+
+        egui::ScrollArea::vertical()
+            .id_salt("sdo_transfers")
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for transfer in self.sessions.iter() {
+                    let text = RichText::new(transfer.to_string());
+                    let text = match transfer.result {
+                        SdoResult::Success | SdoResult::InProgress => text,
+                        _ => text.color(OZON_PINK),
+                    };
+
+                    CollapsingHeader::new(text)
+                        .id_salt(("sdo_transfer", transfer.node_id, transfer.started))
+                        .show(ui, |ui| {
+                            egui::Grid::new((
+                                "sdo_transfer_grid",
+                                transfer.node_id,
+                                transfer.started,
+                            ))
+                            .striped(true)
+                            .show(ui, |ui| {
+                                self.message_row.header(ui);
+                                ui.end_row();
+                                for msg in &transfer.messages {
+                                    self.message_row.message(ui, msg);
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                }
+            });
+    }
+}
@@ -0,0 +1,510 @@
+use crate::{
+    message_cached::{MessageCached, RxMessageAdditional},
+    sdo_block::{BlockReassembly, SdoBlock},
+};
+use core::fmt;
+use oze_canopen::{
+    canopen::{NodeId, RxMessageType},
+    proto::{
+        sdo::{Flags, ResponseData, SdoRequest, SdoRequestData, SdoResponse, UploadResponseData},
+        sdo_abort::AbortCode,
+    },
+    BinRead,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Cursor,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Maximum number of finished transfers kept in history.
+const MAX_FINISHED: usize = 256;
+/// Client command specifier of the abort transfer request.
+const ABORT_CMD: u8 = 0x80;
+/// Longer values are shortened in the transfer summary.
+const MAX_VALUE_BYTES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdoDirection {
+    /// Client reads the object from the server.
+    Upload,
+    /// Client writes the object to the server.
+    Download,
+}
+
+/// Result of an SDO transfer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SdoResult {
+    InProgress,
+    Success,
+    /// Transfer aborted by the server or the client with the given reason.
+    Abort(String),
+    /// Segment toggle bit did not alternate.
+    ToggleError,
+    /// CRC of a block transfer did not match.
+    CrcError,
+    /// A new transfer to the same node was initiated before this one finished.
+    Interrupted,
+}
+
+/// One SDO transaction reassembled from its request and response frames.
+#[derive(Debug, Clone)]
+pub struct SdoTransfer {
+    pub node_id: NodeId,
+    pub direction: SdoDirection,
+    pub index: u16,
+    pub subindex: u8,
+    /// Size announced in the initiate frames.
+    pub size: Option<u32>,
+    pub data: Vec<u8>,
+    pub started: Instant,
+    pub finished: Option<Instant>,
+    pub result: SdoResult,
+    /// Frames of the transfer in order of reception.
+    pub messages: Vec<MessageCached>,
+    expedited: bool,
+    toggle: bool,
+    last_segment: bool,
+    block: Option<BlockReassembly>,
+}
+
+/// Tracks SDO transfers per node, pairing requests with responses.
+#[derive(Debug, Default)]
+pub struct SdoSessions {
+    active: HashMap<NodeId, SdoTransfer>,
+    finished: VecDeque<SdoTransfer>,
+}
+
+impl fmt::Display for SdoResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SdoResult::InProgress => write!(f, "in progress"),
+            SdoResult::Success => write!(f, "OK"),
+            SdoResult::Abort(reason) => write!(f, "abort: {reason}"),
+            SdoResult::ToggleError => write!(f, "toggle bit not alternated"),
+            SdoResult::CrcError => write!(f, "CRC error"),
+            SdoResult::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl SdoTransfer {
+    fn new(
+        msg: &MessageCached,
+        direction: SdoDirection,
+        index: u16,
+        subindex: u8,
+        size: Option<u32>,
+    ) -> Self {
+        Self {
+            node_id: msg.msg.parsed_node_id.unwrap_or_default(),
+            direction,
+            index,
+            subindex,
+            size,
+            data: Vec::new(),
+            started: msg.get_timestamp(),
+            finished: None,
+            result: SdoResult::InProgress,
+            messages: Vec::new(),
+            expedited: false,
+            toggle: false,
+            last_segment: false,
+            block: None,
+        }
+    }
+
+    /// Time from the initiate request to the last frame of the transfer.
+    pub fn duration(&self) -> Option<Duration> {
+        self.finished.map(|f| f.duration_since(self.started))
+    }
+
+    /// Formats the transferred value: integer for up to 4 bytes, text if printable, hex otherwise.
+    pub fn value_to_string(&self) -> String {
+        let hex = self
+            .data
+            .iter()
+            .take(MAX_VALUE_BYTES)
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if self.data.len() > MAX_VALUE_BYTES {
+            return format!("{hex} ... ({} bytes)", self.data.len());
+        }
+
+        if (1..=4).contains(&self.data.len()) {
+            let mut bytes = [0u8; 4];
+            bytes[..self.data.len()].copy_from_slice(&self.data);
+            return format!("{} [{hex}]", u32::from_le_bytes(bytes));
+        }
+
+        if !self.data.is_empty()
+            && self
+                .data
+                .iter()
+                .all(|b| b.is_ascii_graphic() || *b == b' ' || *b == 0)
+        {
+            let text = String::from_utf8_lossy(&self.data);
+            return format!("\"{}\"", text.trim_end_matches('\0'));
+        }
+
+        hex
+    }
+
+    fn on_block(&mut self, block: &SdoBlock) {
+        let Some(reassembly) = &mut self.block else {
+            return;
+        };
+
+        match block {
+            SdoBlock::InitiateUploadResponse { size, .. } => self.size = *size,
+            SdoBlock::Segment { seqno, last, data } => {
+                reassembly.push_segment(*seqno, *last, *data);
+            }
+            SdoBlock::Ack { ackseq, .. } => reassembly.ack(*ackseq),
+            SdoBlock::End { unused, .. } => {
+                self.data = reassembly.finish(*unused).to_vec();
+                if block.is_crc_error() {
+                    self.result = SdoResult::CrcError;
+                }
+            }
+            SdoBlock::EndResponse => self.result = SdoResult::Success,
+            SdoBlock::InitiateDownloadRequest { .. }
+            | SdoBlock::InitiateDownloadResponse { .. }
+            | SdoBlock::InitiateUploadRequest { .. }
+            | SdoBlock::StartUpload => {}
+        }
+    }
+
+    fn check_toggle(&mut self, cmd: Flags) -> bool {
+        if cmd.contains(Flags::TOGGLE_BIT) == self.toggle {
+            true
+        } else {
+            self.result = SdoResult::ToggleError;
+            false
+        }
+    }
+}
+
+impl fmt::Display for SdoTransfer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dir = match self.direction {
+            SdoDirection::Upload => "Upload  ",
+            SdoDirection::Download => "Download",
+        };
+        let duration = self
+            .duration()
+            .map(|d| format!("{:.1} ms", d.as_secs_f64() * 1000.0))
+            .unwrap_or_default();
+        write!(
+            f,
+            "node {:3} {dir} {:04X}:{:02X} {duration:>10} {} = {}",
+            self.node_id,
+            self.index,
+            self.subindex,
+            self.result,
+            self.value_to_string()
+        )
+    }
+}
+
+/// Reads the abort code from an abort frame, which the request parser does not cover.
+fn parse_abort_reason(msg: &MessageCached) -> String {
+    let mut cursor = Cursor::new(&msg.msg.msg.data[4..]);
+    AbortCode::read_le(&mut cursor).map_or_else(
+        |_| {
+            let code = u32::from_le_bytes(msg.msg.msg.data[4..8].try_into().unwrap());
+            format!("0x{code:08X}")
+        },
+        |code| code.to_str().to_owned(),
+    )
+}
+
+impl SdoSessions {
+    /// Feeds a received message, SDO frames update the transfer of their node.
+    ///
+    /// Returns the transfer finished by this message.
+    pub fn push_data(&mut self, msg: &MessageCached) -> Option<&SdoTransfer> {
+        let node_id = msg.msg.parsed_node_id?;
+
+        match (&msg.additional, msg.msg.parsed_type) {
+            (RxMessageAdditional::SdoRx(req), _) => self.on_request(node_id, msg, req),
+            (RxMessageAdditional::SdoTx(resp), _) => self.on_response(node_id, msg, resp),
+            (RxMessageAdditional::SdoBlock(block), _) => self.on_block(node_id, msg, block),
+            (RxMessageAdditional::None, RxMessageType::SdoRx)
+                if msg.msg.msg.data[0] == ABORT_CMD =>
+            {
+                if let Some(transfer) = self.active.get_mut(&node_id) {
+                    transfer.result = SdoResult::Abort(parse_abort_reason(msg));
+                }
+            }
+            _ => return None,
+        }
+
+        let done = self.active.get_mut(&node_id).is_some_and(|transfer| {
+            transfer.messages.push(msg.clone());
+            transfer.result != SdoResult::InProgress
+        });
+        if done {
+            if let Some(mut transfer) = self.active.remove(&node_id) {
+                transfer.finished = Some(msg.get_timestamp());
+                self.finish(transfer);
+                return self.finished.front();
+            }
+        }
+
+        None
+    }
+
+    /// Transfers in progress and finished ones, most recent first.
+    pub fn iter(&self) -> impl Iterator<Item = &SdoTransfer> {
+        let mut active: Vec<&SdoTransfer> = self.active.values().collect();
+        active.sort_by_key(|t| std::cmp::Reverse(t.started));
+        active.into_iter().chain(self.finished.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.active.len() + self.finished.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+        self.finished.clear();
+    }
+
+    fn finish(&mut self, transfer: SdoTransfer) {
+        self.finished.push_front(transfer);
+        while self.finished.len() > MAX_FINISHED {
+            self.finished.pop_back();
+        }
+    }
+
+    fn start(&mut self, node_id: NodeId, transfer: SdoTransfer) {
+        if let Some(mut old) = self.active.remove(&node_id) {
+            old.result = SdoResult::Interrupted;
+            self.finish(old);
+        }
+        self.active.insert(node_id, transfer);
+    }
+
+    fn on_block(&mut self, node_id: NodeId, msg: &MessageCached, block: &SdoBlock) {
+        let (direction, index, subindex, size) = match block {
+            SdoBlock::InitiateDownloadRequest {
+                index,
+                subindex,
+                size,
+                ..
+            } => (SdoDirection::Download, *index, *subindex, *size),
+            SdoBlock::InitiateUploadRequest {
+                index, subindex, ..
+            } => (SdoDirection::Upload, *index, *subindex, None),
+            _ => {
+                if let Some(t) = self.active.get_mut(&node_id) {
+                    t.on_block(block);
+                }
+                return;
+            }
+        };
+
+        let mut t = SdoTransfer::new(msg, direction, index, subindex, size);
+        t.block = Some(BlockReassembly::default());
+        self.start(node_id, t);
+    }
+
+    fn on_request(&mut self, node_id: NodeId, msg: &MessageCached, req: &SdoRequest) {
+        match &req.req {
+            SdoRequestData::InitiateDownloadExpedited(r) => {
+                let mut t =
+                    SdoTransfer::new(msg, SdoDirection::Download, r.index, r.subindex, None);
+                t.data.clone_from(&r.data);
+                t.expedited = true;
+                self.start(node_id, t);
+            }
+            SdoRequestData::InitiateDownload(r) => {
+                let size = req.cmd.contains(Flags::SIZE_SPECIFIED).then_some(r.size);
+                let t = SdoTransfer::new(msg, SdoDirection::Download, r.index, r.subindex, size);
+                self.start(node_id, t);
+            }
+            SdoRequestData::InitiateUpload(r) => {
+                let t = SdoTransfer::new(msg, SdoDirection::Upload, r.index, r.subindex, None);
+                self.start(node_id, t);
+            }
+            SdoRequestData::DownloadSegment(seg) => {
+                let Some(t) = self.active.get_mut(&node_id) else {
+                    return;
+                };
+                if t.direction == SdoDirection::Download && t.check_toggle(req.cmd) {
+                    t.data.extend_from_slice(&seg.data);
+                    // c bit set means this is the last segment
+                    t.last_segment = req.cmd.contains(Flags::CONTINUE);
+                }
+            }
+            SdoRequestData::UploadSegment(_) => {
+                if let Some(t) = self.active.get_mut(&node_id) {
+                    t.check_toggle(req.cmd);
+                }
+            }
+        }
+    }
+
+    fn on_response(&mut self, node_id: NodeId, msg: &MessageCached, resp: &SdoResponse) {
+        let Some(t) = self.active.get_mut(&node_id) else {
+            return;
+        };
+
+        match &resp.resp {
+            ResponseData::Download(_) => {
+                if t.expedited {
+                    t.result = SdoResult::Success;
+                }
+            }
+            ResponseData::DownloadSegment(_) => {
+                if t.check_toggle(resp.cmd) {
+                    t.toggle = !t.toggle;
+                    if t.last_segment {
+                        t.result = SdoResult::Success;
+                    }
+                }
+            }
+            ResponseData::Upload(r) => match &r.data {
+                UploadResponseData::DataExpedited(data) => {
+                    t.data.clone_from(data);
+                    t.result = SdoResult::Success;
+                }
+                UploadResponseData::ExpeditedWithoutSize => {
+                    t.data = msg.msg.msg.data[4..8].to_vec();
+                    t.result = SdoResult::Success;
+                }
+                UploadResponseData::Size(size) => t.size = Some(*size),
+                UploadResponseData::E0S0NotSupported => {}
+            },
+            ResponseData::UploadSegment(seg) => {
+                if t.check_toggle(resp.cmd) {
+                    t.toggle = !t.toggle;
+                    t.data.extend_from_slice(&seg.data);
+                    // c bit set means this is the last segment
+                    if resp.cmd.contains(Flags::CONTINUE) {
+                        t.result = SdoResult::Success;
+                    }
+                }
+            }
+            ResponseData::Abort(a) => {
+                t.result = SdoResult::Abort(a.reason.to_str().to_owned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SdoDirection, SdoResult, SdoSessions};
+    use crate::{message_cached::test_message, sdo_block::SdoBlockTracker};
+    use tokio::time::Instant;
+
+    fn feed(sessions: &mut SdoSessions, cob_id: u16, data: [u8; 8]) {
+        sessions.push_data(&test_message(cob_id, &data, Instant::now()));
+    }
+
+    #[test]
+    fn test_expedited_download() {
+        let mut sessions = SdoSessions::default();
+        feed(
+            &mut sessions,
+            0x605,
+            [0x2B, 0x17, 0x10, 0, 0xE8, 0x03, 0, 0],
+        );
+        assert_eq!(
+            sessions.iter().next().unwrap().result,
+            SdoResult::InProgress
+        );
+        feed(&mut sessions, 0x585, [0x60, 0x17, 0x10, 0, 0, 0, 0, 0]);
+
+        let t = sessions.iter().next().unwrap();
+        assert_eq!(t.direction, SdoDirection::Download);
+        assert_eq!((t.index, t.subindex), (0x1017, 0));
+        assert_eq!(t.result, SdoResult::Success);
+        assert_eq!(t.data, vec![0xE8, 0x03]);
+        assert_eq!(t.messages.len(), 2);
+        assert!(t.value_to_string().starts_with("1000 "));
+    }
+
+    #[test]
+    fn test_segmented_upload() {
+        let mut sessions = SdoSessions::default();
+        feed(&mut sessions, 0x605, [0x40, 0x08, 0x10, 0, 0, 0, 0, 0]);
+        feed(&mut sessions, 0x585, [0x41, 0x08, 0x10, 0, 10, 0, 0, 0]);
+        feed(&mut sessions, 0x605, [0x60, 0, 0, 0, 0, 0, 0, 0]);
+        feed(
+            &mut sessions,
+            0x585,
+            [0x00, b'D', b'r', b'i', b'v', b'e', b' ', b'X'],
+        );
+        feed(&mut sessions, 0x605, [0x70, 0, 0, 0, 0, 0, 0, 0]);
+        // toggle = 1, 4 unused bytes, last segment
+        feed(&mut sessions, 0x585, [0x19, b'-', b'1', b'2', 0, 0, 0, 0]);
+
+        let t = sessions.iter().next().unwrap();
+        assert_eq!(t.result, SdoResult::Success);
+        assert_eq!(t.size, Some(10));
+        assert_eq!(t.value_to_string(), "\"Drive X-12\"");
+    }
+
+    #[test]
+    fn test_toggle_error_and_abort() {
+        let mut sessions = SdoSessions::default();
+        feed(&mut sessions, 0x605, [0x40, 0x08, 0x10, 0, 0, 0, 0, 0]);
+        feed(&mut sessions, 0x585, [0x41, 0x08, 0x10, 0, 10, 0, 0, 0]);
+        // first segment must have toggle bit cleared
+        feed(&mut sessions, 0x605, [0x70, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            sessions.iter().next().unwrap().result,
+            SdoResult::ToggleError
+        );
+
+        feed(&mut sessions, 0x603, [0x40, 0x00, 0x20, 1, 0, 0, 0, 0]);
+        feed(
+            &mut sessions,
+            0x583,
+            [0x80, 0x00, 0x20, 1, 0x00, 0x00, 0x02, 0x06],
+        );
+        let t = sessions.iter().next().unwrap();
+        assert_eq!(t.node_id, 3);
+        assert_eq!(
+            t.result,
+            SdoResult::Abort("Object does not exist in the object dictionary.".to_owned())
+        );
+        assert_eq!(sessions.len(), 2);
+    }
+
+    #[test]
+    fn test_block_download() {
+        let mut sessions = SdoSessions::default();
+        let mut block = SdoBlockTracker::default();
+        let frames: [(u16, [u8; 8]); 7] = [
+            (0x602, [0xC2, 0x50, 0x1F, 1, 9, 0, 0, 0]),
+            (0x582, [0xA0, 0x50, 0x1F, 1, 127, 0, 0, 0]),
+            (0x602, [0x01, 1, 2, 3, 4, 5, 6, 7]),
+            (0x602, [0x82, 8, 9, 0, 0, 0, 0, 0]),
+            (0x582, [0xA2, 2, 127, 0, 0, 0, 0, 0]),
+            (0x602, [0xD5, 0, 0, 0, 0, 0, 0, 0]),
+            (0x582, [0xA1, 0, 0, 0, 0, 0, 0, 0]),
+        ];
+        for (cob_id, data) in frames {
+            let mut msg = test_message(cob_id, &data, Instant::now());
+            block.process(&mut msg);
+            sessions.push_data(&msg);
+        }
+
+        let t = sessions.iter().next().unwrap();
+        assert_eq!((t.index, t.subindex, t.size), (0x1F50, 1, Some(9)));
+        assert_eq!(t.result, SdoResult::Success);
+        assert_eq!(t.data, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(t.messages.len(), 7);
+    }
+}
@@ -0,0 +1,64 @@
+use std::ops::RangeInclusive;
+
+/// Oldest entries to drop when a buffer of `len` entries grew over `max`.
+///
+/// A tenth is dropped at once instead of shifting every entry on each push.
+pub fn excess(len: usize, max: usize) -> usize {
+    if len > max {
+        len - max + max / 10
+    } else {
+        0
+    }
+}
+
+/// Points of the time ordered series in the range reduced to at most about `max_points`,
+/// keeping the minimum and maximum of the merged points.
+pub fn downsample(
+    series: &[[f64; 2]],
+    range: RangeInclusive<f64>,
+    max_points: usize,
+) -> Vec<[f64; 2]> {
+    // one more point on each side so the line reaches the plot border
+    let first = series
+        .partition_point(|[t, _]| t < range.start())
+        .saturating_sub(1);
+    let last = (series.partition_point(|[t, _]| t <= range.end()) + 1).min(series.len());
+    let series = &series[first..last];
+    let chunk = series.len().div_ceil((max_points / 2).max(1)).max(1);
+    if chunk == 1 {
+        return series.to_vec();
+    }
+
+    let mut points = Vec::with_capacity(max_points);
+    for c in series.chunks(chunk) {
+        let min = c.iter().min_by(|a, b| a[1].total_cmp(&b[1]));
+        let max = c.iter().max_by(|a, b| a[1].total_cmp(&b[1]));
+        if let (Some(min), Some(max)) = (min, max) {
+            if min[0] <= max[0] {
+                points.extend([*min, *max]);
+            } else {
+                points.extend([*max, *min]);
+            }
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{downsample, excess};
+
+    #[test]
+    fn test_series() {
+        assert_eq!(excess(100, 100), 0);
+        assert_eq!(excess(101, 100), 11);
+
+        let rates = vec![[0.0, 900.0], [1.0, 100.0], [1.5, 300.0], [2.0, 200.0]];
+        assert_eq!(downsample(&rates, 1.2..=1.8, 10), rates[1..4]);
+        assert_eq!(
+            downsample(&rates, 0.0..=2.0, 2),
+            [[0.0, 900.0], [1.0, 100.0]]
+        );
+        assert!(downsample(&rates, 5.0..=6.0, 10).len() <= 1);
+    }
+}
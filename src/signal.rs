@@ -0,0 +1,361 @@
+use crate::{
+    field_layout::{Field, FieldValue, LayoutMatch},
+    message_cached::{MessageCached, RxMessageAdditional},
+    series::{downsample, excess},
+};
+use core::fmt;
+use oze_canopen::canopen::NodeId;
+use std::{ops::RangeInclusive, str::FromStr};
+use tokio::time::Instant;
+
+/// Samples kept per trace, older ones are dropped.
+const MAX_SAMPLES: usize = 100_000;
+
+/// Where the values of a trace come from.
+#[derive(Debug, Clone)]
+pub enum SignalSource {
+    /// Payload field of messages matching a COB ID or regex, as in the field layouts.
+    Field { matcher: LayoutMatch, field: Field },
+    /// Object mapped into a PDO of the node, decoded with the learned PDO mapping.
+    Pdo {
+        node_id: NodeId,
+        index: u16,
+        subindex: u8,
+        /// Sign extends the raw value from the mapped width.
+        signed: bool,
+    },
+    /// Signal of a DBC message, optionally qualified with the message name.
+    Dbc {
+        message: Option<String>,
+        signal: String,
+    },
+}
+
+impl FromStr for SignalSource {
+    type Err = String;
+
+    /// Parses `cob = field`, `pdo node index:subindex [signed]` or `dbc [message.]signal`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(rest) = s.strip_prefix("pdo ") {
+            let mut parts = rest.split_whitespace();
+            let node_id = parts
+                .next()
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(|| format!("'{s}': expected pdo node index:subindex"))?;
+            let (index, subindex) = parts
+                .next()
+                .and_then(|o| o.split_once(':'))
+                .and_then(|(i, sub)| {
+                    Some((
+                        u16::from_str_radix(i, 16).ok()?,
+                        u8::from_str_radix(sub, 16).ok()?,
+                    ))
+                })
+                .ok_or_else(|| format!("'{s}': expected hexadecimal index:subindex"))?;
+            let signed = match parts.next() {
+                None => false,
+                Some("signed") => true,
+                Some(other) => return Err(format!("'{s}': unexpected '{other}'")),
+            };
+            return Ok(SignalSource::Pdo {
+                node_id,
+                index,
+                subindex,
+                signed,
+            });
+        }
+
+        if let Some(rest) = s.strip_prefix("dbc ") {
+            let rest = rest.trim();
+            if rest.is_empty() {
+                return Err(format!("'{s}': expected dbc [message.]signal"));
+            }
+            return Ok(match rest.split_once('.') {
+                Some((message, signal)) => SignalSource::Dbc {
+                    message: Some(message.to_owned()),
+                    signal: signal.to_owned(),
+                },
+                None => SignalSource::Dbc {
+                    message: None,
+                    signal: rest.to_owned(),
+                },
+            });
+        }
+
+        let (matcher, field) = s
+            .split_once('=')
+            .ok_or_else(|| format!("'{s}': expected cob = field, pdo or dbc"))?;
+        Ok(SignalSource::Field {
+            matcher: LayoutMatch::parse(matcher)?,
+            field: Field::parse(field.trim())?,
+        })
+    }
+}
+
+impl fmt::Display for SignalSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignalSource::Field { field, .. } => write!(f, "{}", field.name),
+            SignalSource::Pdo {
+                node_id,
+                index,
+                subindex,
+                ..
+            } => write!(f, "node {node_id} {index:04X}:{subindex:02X}"),
+            SignalSource::Dbc { signal, .. } => write!(f, "{signal}"),
+        }
+    }
+}
+
+impl SignalSource {
+    /// Value of the signal carried by the message.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_wrap)]
+    pub fn sample(&self, msg: &MessageCached) -> Option<f64> {
+        match self {
+            SignalSource::Field { matcher, field } => {
+                if !matcher.matches(msg) {
+                    return None;
+                }
+                let data = &msg.msg.msg.data[..msg.msg.msg.dlc.min(8)];
+                field.decode(data).map(FieldValue::as_f64)
+            }
+            SignalSource::Pdo {
+                node_id,
+                index,
+                subindex,
+                signed,
+            } => {
+                let RxMessageAdditional::Pdo(pdo) = &msg.additional else {
+                    return None;
+                };
+                if pdo.key.node_id != *node_id {
+                    return None;
+                }
+                let value = pdo
+                    .values
+                    .iter()
+                    .find(|v| v.object.index == *index && v.object.subindex == *subindex)?;
+                let bits = u32::from(value.object.bits).clamp(1, 64);
+                Some(if *signed {
+                    let shift = 64 - bits;
+                    (((value.raw << shift) as i64) >> shift) as f64
+                } else {
+                    value.raw as f64
+                })
+            }
+            SignalSource::Dbc { message, signal } => {
+                let RxMessageAdditional::Dbc(dbc) = &msg.additional else {
+                    return None;
+                };
+                if message.as_ref().is_some_and(|m| *m != dbc.message) {
+                    return None;
+                }
+                dbc.signals
+                    .iter()
+                    .find(|v| v.name == *signal)
+                    .map(|v| v.value)
+            }
+        }
+    }
+}
+
+/// Samples of one signal over time.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    /// Text the source was parsed from.
+    pub spec: String,
+    pub source: SignalSource,
+    /// Traces with the same axis share a lane of the plot.
+    pub axis: usize,
+    /// Seconds since the start of the recorder and values.
+    samples: Vec<[f64; 2]>,
+}
+
+impl Trace {
+    pub fn samples(&self) -> &[[f64; 2]] {
+        &self.samples
+    }
+
+    /// Samples in the time range reduced to at most about `max_points`.
+    pub fn points(&self, range: RangeInclusive<f64>, max_points: usize) -> Vec<[f64; 2]> {
+        downsample(&self.samples, range, max_points)
+    }
+
+    /// Last value at or before the time.
+    pub fn value_at(&self, time: f64) -> Option<f64> {
+        let n = self.samples.partition_point(|[t, _]| *t <= time);
+        n.checked_sub(1).map(|n| self.samples[n][1])
+    }
+}
+
+/// Records the traces added by the user from the received messages.
+#[derive(Debug)]
+pub struct SignalRecorder {
+    /// Time of zero on the time axis.
+    pub start: Instant,
+    traces: Vec<Trace>,
+}
+
+impl Default for SignalRecorder {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            traces: Vec::new(),
+        }
+    }
+}
+
+impl SignalRecorder {
+    /// Adds a trace, see [`SignalSource::from_str`] for the syntax.
+    ///
+    /// # Errors
+    /// Returns a description of the malformed source.
+    pub fn add(&mut self, spec: &str, axis: usize) -> Result<(), String> {
+        let source = spec.parse()?;
+        self.add_source(spec.trim().to_owned(), source, axis);
+        Ok(())
+    }
+
+    pub fn add_source(&mut self, spec: String, source: SignalSource, axis: usize) {
+        self.traces.push(Trace {
+            spec,
+            source,
+            axis,
+            samples: Vec::new(),
+        });
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.traces.len() {
+            self.traces.remove(index);
+        }
+    }
+
+    pub fn traces(&self) -> &[Trace] {
+        &self.traces
+    }
+
+    pub fn traces_mut(&mut self) -> &mut [Trace] {
+        &mut self.traces
+    }
+
+    /// Removes the samples, the traces are kept.
+    pub fn clear(&mut self) {
+        for trace in &mut self.traces {
+            trace.samples.clear();
+        }
+    }
+
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        let time = msg
+            .get_timestamp()
+            .saturating_duration_since(self.start)
+            .as_secs_f64();
+        for trace in &mut self.traces {
+            let Some(value) = trace.source.sample(msg) else {
+                continue;
+            };
+            trace.samples.push([time, value]);
+            trace
+                .samples
+                .drain(..excess(trace.samples.len(), MAX_SAMPLES));
+        }
+    }
+
+    /// Samples as CSV with a time column and one column per trace, values of samples received
+    /// at the same time share a row.
+    // samples of one message have exactly the same time
+    #[allow(clippy::float_cmp)]
+    pub fn to_csv(&self) -> String {
+        let mut samples: Vec<(f64, usize, f64)> = self
+            .traces
+            .iter()
+            .enumerate()
+            .flat_map(|(n, trace)| trace.samples.iter().map(move |[t, v]| (*t, n, *v)))
+            .collect();
+        samples.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let header = std::iter::once("time".to_owned())
+            .chain(self.traces.iter().map(|t| {
+                let name = t.source.to_string();
+                format!("\"{}\"", name.replace('"', "\"\""))
+            }))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut lines = vec![header];
+        let mut row: Option<(f64, Vec<Option<f64>>)> = None;
+        let format_row = |(time, values): (f64, Vec<Option<f64>>)| {
+            std::iter::once(format!("{time:.6}"))
+                .chain(
+                    values
+                        .iter()
+                        .map(|v| v.map(|v| v.to_string()).unwrap_or_default()),
+                )
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        for (time, n, value) in samples {
+            match &mut row {
+                Some((t, values)) if *t == time && values[n].is_none() => values[n] = Some(value),
+                _ => {
+                    let mut values = vec![None; self.traces.len()];
+                    values[n] = Some(value);
+                    if let Some(row) = row.replace((time, values)) {
+                        lines.push(format_row(row));
+                    }
+                }
+            }
+        }
+        lines.extend(row.map(format_row));
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignalRecorder;
+    use crate::message_cached::test_message;
+    use std::time::Duration;
+
+    #[test]
+    fn test_signal_recorder() {
+        let mut recorder = SignalRecorder::default();
+        recorder.add("181 = speed:i16@0*0.5", 0).unwrap();
+        recorder.add("/18./ = flag:bit@2.0", 1).unwrap();
+        assert!(recorder.add("pdo 5 6064", 0).is_err());
+        assert!(recorder.add("181 speed", 0).is_err());
+        assert!(recorder.add("pdo 5 6064:00 signed", 0).is_ok());
+        assert!(recorder.add("dbc Engine.Speed", 0).is_ok());
+        recorder.remove(3);
+        recorder.remove(2);
+
+        let start = recorder.start;
+        for (ms, cob_id, data) in [
+            (10, 0x181, [0xFE, 0xFF, 0x01]),
+            (20, 0x182, [0x00, 0x00, 0x00]),
+            (30, 0x181, [0x04, 0x00, 0x00]),
+        ] {
+            recorder.push_data(&test_message(
+                cob_id,
+                &data,
+                start + Duration::from_millis(ms),
+            ));
+        }
+
+        let traces = recorder.traces();
+        assert_eq!(traces.len(), 2);
+        let (speed, flag) = (&traces[0], &traces[1]);
+        assert_eq!(speed.samples().len(), 2);
+        assert_eq!(flag.samples().len(), 3);
+        assert_eq!(speed.value_at(0.025), Some(-1.0));
+        assert_eq!(speed.value_at(0.005), None);
+        assert_eq!(flag.points(0.0..=1.0, 2), [[0.01, 1.0], [0.02, 0.0]]);
+        assert_eq!(flag.points(0.025..=1.0, 10).len(), 2);
+        assert_eq!(
+            recorder.to_csv(),
+            "time,\"speed\",\"flag\"\n0.010000,-1,1\n0.020000,,0\n0.030000,2,0\n"
+        );
+    }
+}
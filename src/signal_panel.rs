@@ -0,0 +1,219 @@
+use crate::{
+    field_layout::FieldLayouts,
+    message_cached::MessageCached,
+    signal::{SignalRecorder, SignalSource},
+    theme::{OZON_BLUE, OZON_PINK},
+};
+use egui::{DragValue, RichText, TextEdit};
+use egui_plot::{Legend, Line, Plot, PlotPoints, VLine};
+use std::{collections::BTreeSet, fs};
+
+const HINT: &str = "181 = speed:i16@0*0.1, pdo 5 6078:00 signed or dbc Engine.Speed";
+/// Points drawn at most per trace, samples are merged when zoomed out.
+const MAX_POINTS: usize = 2000;
+
+/// Plots decoded values over time in lanes with a shared time axis.
+#[derive(Debug)]
+pub struct SignalPanel {
+    pub recorder: SignalRecorder,
+    spec: String,
+    axis: usize,
+    error: Option<String>,
+    /// Cursors A and B in seconds.
+    cursors: [Option<f64>; 2],
+    csv_path: String,
+    csv_status: Option<Result<String, String>>,
+}
+
+impl Default for SignalPanel {
+    fn default() -> Self {
+        Self {
+            recorder: SignalRecorder::default(),
+            spec: String::new(),
+            axis: 1,
+            error: None,
+            cursors: [None; 2],
+            csv_path: "signals.csv".to_owned(),
+            csv_status: None,
+        }
+    }
+}
+
+fn value_text(value: Option<f64>) -> String {
+    value.map_or_else(|| "-".to_owned(), |v| format!("{v:.3}"))
+}
+
+impl SignalPanel {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        self.recorder.push_data(msg);
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui, layouts: &FieldLayouts) {
+        self.show_editor(ui, layouts);
+        ui.separator();
+        self.show_traces(ui);
+        self.show_lanes(ui);
+    }
+
+    fn show_editor(&mut self, ui: &mut egui::Ui, layouts: &FieldLayouts) {
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                TextEdit::singleline(&mut self.spec)
+                    .hint_text(HINT)
+                    .desired_width(400.0),
+            );
+            ui.label("axis");
+            ui.add(DragValue::new(&mut self.axis).range(1..=8))
+                .on_hover_text("Traces with the same axis are drawn in the same lane");
+            let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("➕").on_hover_text("Add trace").clicked() || enter {
+                match self.recorder.add(&self.spec, self.axis) {
+                    Ok(()) => {
+                        self.spec.clear();
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            if ui
+                .add_enabled(!layouts.is_empty(), egui::Button::new("➕ layouts"))
+                .on_hover_text("Add every field of the field layouts as a trace")
+                .clicked()
+            {
+                for layout in layouts.layouts() {
+                    for field in &layout.fields {
+                        let source = SignalSource::Field {
+                            matcher: layout.matcher.clone(),
+                            field: field.clone(),
+                        };
+                        self.recorder.add_source(
+                            format!("{} (layout)", field.name),
+                            source,
+                            self.axis,
+                        );
+                    }
+                }
+            }
+        });
+        if let Some(e) = &self.error {
+            ui.colored_label(OZON_PINK, e);
+        }
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("🗑")
+                .on_hover_text("Clear samples, the traces are kept")
+                .clicked()
+            {
+                self.recorder.clear();
+            }
+            ui.add(TextEdit::singleline(&mut self.csv_path).desired_width(200.0));
+            if ui
+                .button("💾 CSV")
+                .on_hover_text("Export the samples of all traces")
+                .clicked()
+            {
+                let csv = self.recorder.to_csv();
+                let rows = csv.lines().count().saturating_sub(1);
+                self.csv_status = Some(
+                    fs::write(&self.csv_path, csv)
+                        .map(|()| format!("{rows} rows written to {}", self.csv_path))
+                        .map_err(|e| format!("{}: {e}", self.csv_path)),
+                );
+            }
+            match &self.csv_status {
+                Some(Ok(status)) => {
+                    ui.label(status);
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(OZON_PINK, e);
+                }
+                None => {}
+            }
+        });
+    }
+
+    fn show_traces(&mut self, ui: &mut egui::Ui) {
+        let [a, b] = self.cursors;
+        ui.horizontal(|ui| {
+            ui.label("click a lane to set cursor A, shift+click for cursor B");
+            if let (Some(a), Some(b)) = (a, b) {
+                ui.label(format!("Δt={:.6} s", b - a));
+            }
+            if (a.is_some() || b.is_some())
+                && ui.button("✖").on_hover_text("Remove cursors").clicked()
+            {
+                self.cursors = [None; 2];
+            }
+        });
+
+        let mut to_remove = None;
+        egui::Grid::new("signal_traces")
+            .striped(true)
+            .show(ui, |ui| {
+                for header in ["Trace", "Source", "Axis", "Samples", "A", "B", "B-A", ""] {
+                    ui.label(RichText::new(header).strong());
+                }
+                ui.end_row();
+
+                for (n, trace) in self.recorder.traces_mut().iter_mut().enumerate() {
+                    let at_a = a.and_then(|t| trace.value_at(t));
+                    let at_b = b.and_then(|t| trace.value_at(t));
+                    ui.label(trace.source.to_string());
+                    ui.label(&trace.spec);
+                    ui.add(DragValue::new(&mut trace.axis).range(1..=8));
+                    ui.label(trace.samples().len().to_string());
+                    ui.label(value_text(at_a));
+                    ui.label(value_text(at_b));
+                    ui.label(value_text(at_a.zip(at_b).map(|(a, b)| b - a)));
+                    if ui.button("🗑").on_hover_text("Remove trace").clicked() {
+                        to_remove = Some(n);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(n) = to_remove {
+            self.recorder.remove(n);
+        }
+    }
+
+    fn show_lanes(&mut self, ui: &mut egui::Ui) {
+        let axes: BTreeSet<usize> = self.recorder.traces().iter().map(|t| t.axis).collect();
+        let shift = ui.input(|i| i.modifiers.shift);
+        for axis in axes {
+            let response = Plot::new(("signal_lane", axis))
+                .height(150.0)
+                .legend(Legend::default())
+                .link_axis("signal_lanes", true, false)
+                .link_cursor("signal_lanes", true, false)
+                .y_axis_label(format!("axis {axis}"))
+                .show(ui, |plot_ui| {
+                    let bounds = plot_ui.plot_bounds();
+                    let range = if plot_ui.auto_bounds().x || !bounds.is_valid_x() {
+                        f64::NEG_INFINITY..=f64::INFINITY
+                    } else {
+                        bounds.range_x()
+                    };
+                    for trace in self.recorder.traces().iter().filter(|t| t.axis == axis) {
+                        let points = trace.points(range.clone(), MAX_POINTS);
+                        plot_ui.line(
+                            Line::new(PlotPoints::new(points)).name(trace.source.to_string()),
+                        );
+                    }
+                    for (cursor, name) in self.cursors.iter().zip(["A", "B"]) {
+                        if let Some(time) = cursor {
+                            plot_ui.vline(VLine::new(*time).color(OZON_BLUE).name(name));
+                        }
+                    }
+                    if plot_ui.response().clicked() {
+                        plot_ui.pointer_coordinate().map(|p| p.x)
+                    } else {
+                        None
+                    }
+                });
+            if let Some(time) = response.inner {
+                self.cursors[usize::from(shift)] = Some(time);
+            }
+        }
+    }
+}
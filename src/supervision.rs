@@ -0,0 +1,286 @@
+use crate::{
+    message_cached::{MessageCached, RxMessageAdditional},
+    sdo_session::{SdoResult, SdoTransfer},
+};
+use core::fmt;
+use oze_canopen::canopen::NodeId;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Producer heartbeat time in milliseconds.
+const PRODUCER_HEARTBEAT_TIME: u16 = 0x1017;
+/// A deadline is missed when nothing was received for this multiple of the expected period.
+const TOLERANCE: f64 = 1.5;
+const MAX_ALARMS: usize = 256;
+
+/// What a supervision rule expects to be received periodically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Supervised {
+    Heartbeat(NodeId),
+    /// Messages with this COB ID, or 29-bit identifier of extended frames.
+    Cob {
+        extended: bool,
+        id: u32,
+    },
+}
+
+impl fmt::Display for Supervised {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Supervised::Heartbeat(node_id) => write!(f, "heartbeat of node {node_id}"),
+            Supervised::Cob {
+                extended: false,
+                id,
+            } => write!(f, "COB ID {id:03X}"),
+            Supervised::Cob { extended: true, id } => write!(f, "ID {id:08X}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub period: Duration,
+    /// Heartbeat time read from object 0x1017 instead of entered by the user.
+    pub from_sdo: bool,
+    /// Last message, or the time supervision started.
+    pub last_seen: Instant,
+    pub received: bool,
+    /// Alarm raised for the current timeout.
+    alarm: Option<u64>,
+}
+
+impl Rule {
+    pub fn in_alarm(&self) -> bool {
+        self.alarm.is_some()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Alarm {
+    pub id: u64,
+    pub supervised: Supervised,
+    pub raised: Instant,
+    /// Time the supervised message was received again.
+    pub cleared: Option<Instant>,
+    pub acknowledged: bool,
+    pub message: String,
+}
+
+/// Raises alarms when heartbeats or periodic messages are not received in time.
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    rules: BTreeMap<Supervised, Rule>,
+    /// Alarm history, most recent first.
+    alarms: VecDeque<Alarm>,
+    next_id: u64,
+}
+
+impl Supervisor {
+    /// Adds or replaces a rule, supervision starts now.
+    pub fn set_rule(&mut self, supervised: Supervised, period: Duration) {
+        self.insert(supervised, period, false, Instant::now());
+    }
+
+    fn insert(&mut self, supervised: Supervised, period: Duration, from_sdo: bool, now: Instant) {
+        self.remove_rule(supervised);
+        self.rules.insert(
+            supervised,
+            Rule {
+                period,
+                from_sdo,
+                last_seen: now,
+                received: false,
+                alarm: None,
+            },
+        );
+    }
+
+    pub fn remove_rule(&mut self, supervised: Supervised) {
+        if let Some(id) = self.rules.remove(&supervised).and_then(|r| r.alarm) {
+            self.clear_alarm(id, Instant::now());
+        }
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = (&Supervised, &Rule)> {
+        self.rules.iter()
+    }
+
+    pub fn alarms(&self) -> impl Iterator<Item = &Alarm> {
+        self.alarms.iter()
+    }
+
+    /// Number of alarms which were not acknowledged yet.
+    pub fn unacknowledged(&self) -> usize {
+        self.alarms.iter().filter(|a| !a.acknowledged).count()
+    }
+
+    pub fn acknowledge(&mut self, id: u64) {
+        if let Some(alarm) = self.alarms.iter_mut().find(|a| a.id == id) {
+            alarm.acknowledged = true;
+        }
+    }
+
+    pub fn acknowledge_all(&mut self) {
+        for alarm in &mut self.alarms {
+            alarm.acknowledged = true;
+        }
+    }
+
+    /// Removes acknowledged alarms which are no longer active.
+    pub fn clear_history(&mut self) {
+        self.alarms
+            .retain(|a| !a.acknowledged || a.cleared.is_none());
+    }
+
+    fn clear_alarm(&mut self, id: u64, time: Instant) {
+        if let Some(alarm) = self.alarms.iter_mut().find(|a| a.id == id) {
+            alarm.cleared = Some(time);
+            log::info!("{} received again", alarm.supervised);
+        }
+    }
+
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        let time = msg.get_timestamp();
+        let mut seen = vec![Supervised::Cob {
+            extended: msg.extended,
+            id: msg.can_id,
+        }];
+        if let (RxMessageAdditional::Heartbeat(_), Some(node_id)) =
+            (&msg.additional, msg.msg.parsed_node_id)
+        {
+            seen.push(Supervised::Heartbeat(node_id));
+        }
+
+        for supervised in seen {
+            let Some(rule) = self.rules.get_mut(&supervised) else {
+                continue;
+            };
+            rule.last_seen = time;
+            rule.received = true;
+            if let Some(id) = rule.alarm.take() {
+                self.clear_alarm(id, time);
+            }
+        }
+    }
+
+    /// Supervises heartbeats with the producer heartbeat time written to or read from 0x1017.
+    pub fn push_transfer(&mut self, transfer: &SdoTransfer) {
+        if transfer.result != SdoResult::Success
+            || transfer.index != PRODUCER_HEARTBEAT_TIME
+            || transfer.subindex != 0
+        {
+            return;
+        }
+        let [lo, hi, ..] = transfer.data[..] else {
+            return;
+        };
+
+        let supervised = Supervised::Heartbeat(transfer.node_id);
+        if self.rules.get(&supervised).is_some_and(|r| !r.from_sdo) {
+            // rules entered by the user take precedence
+            return;
+        }
+        let period = Duration::from_millis(u64::from(u16::from_le_bytes([lo, hi])));
+        if period.is_zero() {
+            self.remove_rule(supervised);
+        } else if self.rules.get(&supervised).map(|r| r.period) != Some(period) {
+            let time = transfer.finished.unwrap_or(transfer.started);
+            self.insert(supervised, period, true, time);
+        }
+    }
+
+    /// Restarts every deadline after messages were not received, e.g. while stopped.
+    pub fn resume(&mut self, now: Instant) {
+        for rule in self.rules.values_mut() {
+            rule.last_seen = now;
+        }
+    }
+
+    /// Raises alarms for rules whose deadline passed.
+    pub fn check(&mut self, now: Instant) {
+        for (supervised, rule) in &mut self.rules {
+            let silence = now.saturating_duration_since(rule.last_seen);
+            if rule.alarm.is_some()
+                || silence.as_secs_f64() <= rule.period.as_secs_f64() * TOLERANCE
+            {
+                continue;
+            }
+
+            let message = if rule.received {
+                format!(
+                    "{supervised} missing for {} ms, expected every {} ms",
+                    silence.as_millis(),
+                    rule.period.as_millis()
+                )
+            } else {
+                format!(
+                    "{supervised} never received, expected every {} ms",
+                    rule.period.as_millis()
+                )
+            };
+            log::warn!("{message}");
+
+            let id = self.next_id;
+            self.next_id += 1;
+            rule.alarm = Some(id);
+            self.alarms.push_front(Alarm {
+                id,
+                supervised: *supervised,
+                raised: now,
+                cleared: None,
+                acknowledged: false,
+                message,
+            });
+        }
+        self.alarms.truncate(MAX_ALARMS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Supervised, Supervisor};
+    use crate::message_cached::test_message;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    const PDO: Supervised = Supervised::Cob {
+        extended: false,
+        id: 0x185,
+    };
+
+    #[test]
+    fn test_heartbeat_timeout() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut supervisor = Supervisor::default();
+        supervisor.set_rule(Supervised::Heartbeat(5), Duration::from_millis(100));
+        supervisor.set_rule(PDO, Duration::from_millis(10));
+
+        supervisor.push_data(&test_message(0x705, &[0x05], at(50)));
+        supervisor.push_data(&test_message(0x185, &[0x01], at(50)));
+        supervisor.check(at(150));
+        assert_eq!(supervisor.unacknowledged(), 1);
+        let alarm = supervisor.alarms().next().unwrap();
+        assert_eq!(alarm.supervised, PDO);
+        assert!(alarm.cleared.is_none());
+
+        supervisor.check(at(250));
+        assert_eq!(supervisor.unacknowledged(), 2);
+
+        supervisor.push_data(&test_message(0x705, &[0x05], at(260)));
+        supervisor.acknowledge_all();
+        supervisor.clear_history();
+        let alarms: Vec<_> = supervisor.alarms().collect();
+        assert_eq!(alarms.len(), 1);
+        assert_eq!(alarms[0].supervised, PDO);
+        assert!(supervisor.rules().all(|(s, r)| r.in_alarm() == (*s == PDO)));
+
+        // silence while stopped doesn't count
+        supervisor.resume(at(1000));
+        supervisor.check(at(1100));
+        assert_eq!(supervisor.unacknowledged(), 0);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    message_cached::MessageCached,
+    sdo_session::SdoTransfer,
+    supervision::{Supervised, Supervisor},
+    theme::OZON_PINK,
+};
+use egui::{RichText, TextEdit};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Edits supervision rules and shows the alarms they raised.
+#[derive(Debug)]
+pub struct SupervisionPanel {
+    pub supervisor: Supervisor,
+    pub start_time: Instant,
+    node_raw: String,
+    heartbeat_raw: String,
+    cob_raw: String,
+    period_raw: String,
+}
+
+impl Default for SupervisionPanel {
+    fn default() -> Self {
+        Self {
+            supervisor: Supervisor::default(),
+            start_time: Instant::now(),
+            node_raw: String::new(),
+            heartbeat_raw: String::new(),
+            cob_raw: String::new(),
+            period_raw: String::new(),
+        }
+    }
+}
+
+fn parse_period(raw: &str) -> Option<Duration> {
+    raw.trim()
+        .parse()
+        .ok()
+        .filter(|ms| *ms > 0)
+        .map(Duration::from_millis)
+}
+
+impl SupervisionPanel {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        self.supervisor.push_data(msg);
+    }
+
+    pub fn push_transfer(&mut self, transfer: &SdoTransfer) {
+        self.supervisor.push_transfer(transfer);
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui) {
+        self.show_rule_editor(ui);
+        ui.separator();
+        self.show_rules(ui);
+        ui.separator();
+        self.show_alarms(ui);
+    }
+
+    fn show_rule_editor(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut self.node_raw)
+                    .hint_text("nodeID")
+                    .desired_width(55.0),
+            );
+            ui.add(
+                TextEdit::singleline(&mut self.heartbeat_raw)
+                    .hint_text("ms")
+                    .desired_width(55.0),
+            );
+            let node_id = self.node_raw.trim().parse().ok();
+            let period = parse_period(&self.heartbeat_raw);
+            if ui
+                .add_enabled(
+                    node_id.is_some() && period.is_some(),
+                    egui::Button::new("➕ heartbeat"),
+                )
+                .on_hover_text(
+                    "Expected heartbeat time of the node. Heartbeat times written to or read \
+                     from object 0x1017 by SDO are supervised automatically.",
+                )
+                .clicked()
+            {
+                if let (Some(node_id), Some(period)) = (node_id, period) {
+                    self.supervisor
+                        .set_rule(Supervised::Heartbeat(node_id), period);
+                }
+            }
+
+            ui.separator();
+            ui.add(
+                TextEdit::singleline(&mut self.cob_raw)
+                    .hint_text("COB ID hex")
+                    .desired_width(80.0),
+            );
+            ui.add(
+                TextEdit::singleline(&mut self.period_raw)
+                    .hint_text("ms")
+                    .desired_width(55.0),
+            );
+            // 29-bit identifiers are written with 8 digits like in the COB ID column
+            let cob_raw = self.cob_raw.trim();
+            let cob = u32::from_str_radix(cob_raw, 16)
+                .ok()
+                .map(|id| Supervised::Cob {
+                    extended: cob_raw.len() > 3,
+                    id,
+                });
+            let period = parse_period(&self.period_raw);
+            if ui
+                .add_enabled(
+                    cob.is_some() && period.is_some(),
+                    egui::Button::new("➕ period"),
+                )
+                .on_hover_text("Expected period of a PDO or any other message")
+                .clicked()
+            {
+                if let (Some(cob), Some(period)) = (cob, period) {
+                    self.supervisor.set_rule(cob, period);
+                }
+            }
+        });
+    }
+
+    fn show_rules(&mut self, ui: &mut egui::Ui) {
+        let now = Instant::now();
+        let mut to_remove = None;
+        egui::Grid::new("supervision_rules")
+            .striped(true)
+            .show(ui, |ui| {
+                for header in [
+                    "Supervised",
+                    "Period",
+                    "Source",
+                    "Last received",
+                    "Status",
+                    "",
+                ] {
+                    ui.label(RichText::new(header).strong());
+                }
+                ui.end_row();
+
+                for (supervised, rule) in self.supervisor.rules() {
+                    ui.label(supervised.to_string());
+                    ui.label(format!("{} ms", rule.period.as_millis()));
+                    ui.label(if rule.from_sdo { "0x1017" } else { "manual" });
+                    if rule.received {
+                        let age = now.saturating_duration_since(rule.last_seen);
+                        ui.label(format!("{:.1} s ago", age.as_secs_f32()));
+                    } else {
+                        ui.label("never");
+                    }
+                    if rule.in_alarm() {
+                        ui.colored_label(OZON_PINK, "TIMEOUT");
+                    } else {
+                        ui.label("OK");
+                    }
+                    if ui.button("🗑").on_hover_text("Remove rule").clicked() {
+                        to_remove = Some(*supervised);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(supervised) = to_remove {
+            self.supervisor.remove_rule(supervised);
+        }
+    }
+
+    fn show_alarms(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "unacknowledged={}",
+                self.supervisor.unacknowledged()
+            ));
+            if ui
+                .button("✔ all")
+                .on_hover_text("Acknowledge all alarms")
+                .clicked()
+            {
+                self.supervisor.acknowledge_all();
+            }
+            if ui
+                .button("🗑")
+                .on_hover_text("Remove acknowledged alarms which are no longer active")
+                .clicked()
+            {
+                self.supervisor.clear_history();
+            }
+        });
+
+        let mut to_acknowledge = None;
+        egui::ScrollArea::vertical()
+            .id_salt("supervision_alarms")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("supervision_alarms_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in ["Raised", "Cleared", "Alarm", ""] {
+                            ui.label(RichText::new(header).strong());
+                        }
+                        ui.end_row();
+
+                        for alarm in self.supervisor.alarms() {
+                            let time = |t: Instant| {
+                                format!("{:.6}", t.duration_since(self.start_time).as_secs_f32())
+                            };
+                            ui.label(time(alarm.raised));
+                            ui.label(alarm.cleared.map_or_else(|| "active".to_owned(), time));
+                            if alarm.acknowledged {
+                                ui.label(&alarm.message);
+                            } else {
+                                ui.colored_label(OZON_PINK, &alarm.message);
+                            }
+                            if !alarm.acknowledged
+                                && ui.button("✔").on_hover_text("Acknowledge").clicked()
+                            {
+                                to_acknowledge = Some(alarm.id);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if let Some(id) = to_acknowledge {
+            self.supervisor.acknowledge(id);
+        }
+    }
+}
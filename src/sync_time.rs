@@ -0,0 +1,207 @@
+use crate::message_cached::{MessageCached, RxMessageAdditional};
+use chrono::{Days, NaiveDate, NaiveTime};
+use core::fmt;
+use oze_canopen::proto::CobId;
+
+/// Default COB-ID of the TIME object.
+pub const TIME_COB_ID: CobId = 0x100;
+/// Largest valid SYNC counter overflow value (object 0x1019).
+const MAX_SYNC_COUNTER: u8 = 240;
+const TIME_MS_MASK: u32 = 0x0FFF_FFFF;
+
+/// Result of checking a SYNC counter against the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncEvent {
+    /// Counter is the previous one plus one, or no previous SYNC was seen.
+    InSequence,
+    /// Counter wrapped to 1, the value is the overflow value it wrapped after.
+    Overflow(u8),
+    /// Counter skipped values, probably lost SYNC messages.
+    Gap { expected: u8 },
+    /// Counter outside of the valid 1-240 range.
+    Invalid,
+}
+
+/// SYNC message with its optional counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncInfo {
+    pub counter: Option<u8>,
+    pub event: SyncEvent,
+}
+
+/// `TIME_OF_DAY` from the TIME object: milliseconds after midnight and days since 1984-01-01.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeOfDay {
+    pub ms: u32,
+    pub days: u16,
+}
+
+/// Follows SYNC counters to detect lost SYNCs and learn the counter overflow value.
+#[derive(Debug, Default, Clone)]
+pub struct SyncTracker {
+    last: Option<u8>,
+    overflow: Option<u8>,
+}
+
+impl SyncInfo {
+    pub fn new(data: &[u8]) -> Self {
+        Self {
+            counter: data.first().copied(),
+            event: SyncEvent::InSequence,
+        }
+    }
+}
+
+impl fmt::Display for SyncInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(counter) = self.counter else {
+            return write!(f, "SYNC");
+        };
+
+        write!(f, "SYNC counter {counter:3}")?;
+        match self.event {
+            SyncEvent::InSequence => Ok(()),
+            SyncEvent::Overflow(overflow) => write!(f, " overflow after {overflow}"),
+            SyncEvent::Gap { expected } => write!(f, " GAP expected {expected}"),
+            SyncEvent::Invalid => write!(f, " INVALID"),
+        }
+    }
+}
+
+impl TimeOfDay {
+    /// Parses the 6 byte `TIME_OF_DAY` structure.
+    pub fn new(data: &[u8]) -> Option<Self> {
+        let [m0, m1, m2, m3, d0, d1] = data else {
+            return None;
+        };
+
+        Some(Self {
+            ms: u32::from_le_bytes([*m0, *m1, *m2, *m3]) & TIME_MS_MASK,
+            days: u16::from_le_bytes([*d0, *d1]),
+        })
+    }
+
+    pub fn date(&self) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(1984, 1, 1)?.checked_add_days(Days::new(u64::from(self.days)))
+    }
+
+    pub fn time(&self) -> Option<NaiveTime> {
+        NaiveTime::from_num_seconds_from_midnight_opt(self.ms / 1000, (self.ms % 1000) * 1_000_000)
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.date(), self.time()) {
+            (Some(date), Some(time)) => write!(f, "TIME {date} {time}"),
+            _ => write!(f, "TIME invalid ms={} days={}", self.ms, self.days),
+        }
+    }
+}
+
+impl SyncTracker {
+    /// Checks the counter of a SYNC message and stores the result in it.
+    pub fn process(&mut self, msg: &mut MessageCached) {
+        let RxMessageAdditional::Sync(sync) = &mut msg.additional else {
+            return;
+        };
+        let Some(counter) = sync.counter else {
+            self.last = None;
+            return;
+        };
+
+        sync.event = self.check(counter);
+        self.last = Some(counter);
+    }
+
+    fn check(&mut self, counter: u8) -> SyncEvent {
+        if counter == 0 || counter > MAX_SYNC_COUNTER {
+            return SyncEvent::Invalid;
+        }
+
+        let Some(last) = self.last else {
+            return SyncEvent::InSequence;
+        };
+
+        if counter == 1 {
+            // learn the overflow value from every wrap, an earlier one may have followed lost SYNCs
+            return match self.overflow.replace(last) {
+                Some(overflow) if last < overflow => SyncEvent::Gap { expected: last + 1 },
+                _ => SyncEvent::Overflow(last),
+            };
+        }
+
+        let wraps = self
+            .overflow
+            .map_or(last >= MAX_SYNC_COUNTER, |o| last >= o);
+        let expected = if wraps { 1 } else { last + 1 };
+        if counter == expected {
+            return SyncEvent::InSequence;
+        }
+
+        if counter == last + 1 {
+            // counted past the overflow value, it was learned after lost SYNCs
+            self.overflow = None;
+            return SyncEvent::InSequence;
+        }
+
+        SyncEvent::Gap { expected }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SyncEvent, SyncTracker, TimeOfDay};
+    use crate::message_cached::{test_message, RxMessageAdditional};
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_sync_counter() {
+        fn check(tracker: &mut SyncTracker, counter: u8) -> Option<SyncEvent> {
+            let mut msg = test_message(0x080, &[counter], Instant::now());
+            tracker.process(&mut msg);
+            match msg.additional {
+                RxMessageAdditional::Sync(sync) => Some(sync.event),
+                _ => None,
+            }
+        }
+
+        let tracker = &mut SyncTracker::default();
+        assert_eq!(check(tracker, 3), Some(SyncEvent::InSequence));
+        assert_eq!(check(tracker, 4), Some(SyncEvent::InSequence));
+        assert_eq!(check(tracker, 5), Some(SyncEvent::InSequence));
+        assert_eq!(check(tracker, 1), Some(SyncEvent::Overflow(5)));
+        assert_eq!(check(tracker, 2), Some(SyncEvent::InSequence));
+        assert_eq!(check(tracker, 4), Some(SyncEvent::Gap { expected: 3 }));
+        assert_eq!(check(tracker, 5), Some(SyncEvent::InSequence));
+        assert_eq!(check(tracker, 1), Some(SyncEvent::Overflow(5)));
+        assert_eq!(check(tracker, 0), Some(SyncEvent::Invalid));
+
+        // SYNC 6 was lost before the first wrap, the overflow is learned again
+        let tracker = &mut SyncTracker::default();
+        for counter in [4, 5, 1, 2, 3, 4, 5] {
+            check(tracker, counter);
+        }
+        assert_eq!(check(tracker, 6), Some(SyncEvent::InSequence));
+        assert_eq!(check(tracker, 1), Some(SyncEvent::Overflow(6)));
+        for counter in 2..=6 {
+            assert_eq!(check(tracker, counter), Some(SyncEvent::InSequence));
+        }
+        assert_eq!(check(tracker, 1), Some(SyncEvent::Overflow(6)));
+        // lost SYNCs before a wrap are a gap
+        for counter in 2..=4 {
+            check(tracker, counter);
+        }
+        assert_eq!(check(tracker, 1), Some(SyncEvent::Gap { expected: 5 }));
+    }
+
+    #[test]
+    fn test_time_of_day() {
+        // 2025-03-16 is day 15050 since 1984-01-01, 12:34:56.789 is 45296789 ms
+        let ms = 45_296_789u32.to_le_bytes();
+        let days = 15050u16.to_le_bytes();
+        let time = TimeOfDay::new(&[ms[0], ms[1], ms[2], ms[3], days[0], days[1]]).unwrap();
+        assert_eq!(time.to_string(), "TIME 2025-03-16 12:34:56.789");
+        assert!(TimeOfDay::new(&[0; 4]).is_none());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::message_cached::{MessageCached, RxMessageAdditional};
+use oze_canopen::canopen::{NodeId, RxMessageType};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use tokio::time::Instant;
+
+/// Samples kept for statistics and histograms, older ones are dropped.
+const MAX_SAMPLES: usize = 1024;
+/// Samples needed before outliers are flagged.
+const MIN_SAMPLES_FOR_OUTLIERS: usize = 16;
+/// Samples further than this many standard deviations from the mean are outliers.
+const OUTLIER_SIGMA: f64 = 3.0;
+/// Smaller deviations are never outliers, e.g. when all previous samples were equal.
+const MIN_OUTLIER_DEVIATION: f64 = 1e-6;
+const FUNCTION_CODE_MASK: u16 = 0x780;
+/// Function codes of transmit PDOs 1-4, sent by the node in response to SYNC.
+const TPDO_FUNCTION_CODES: [u16; 4] = [0x180, 0x280, 0x380, 0x480];
+
+/// Summary of timing samples in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimingSummary {
+    pub count: usize,
+    pub mean: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// Recent timing samples with outlier detection.
+#[derive(Debug, Default, Clone)]
+pub struct Samples {
+    values: VecDeque<f64>,
+    pub outliers: u64,
+    mean: f64,
+    /// Sum of squared differences from the mean (Welford's algorithm).
+    m2: f64,
+}
+
+impl Samples {
+    #[allow(clippy::cast_precision_loss)]
+    fn mean_stddev(&self) -> (f64, f64) {
+        let variance = self.m2.max(0.0) / self.values.len() as f64;
+        (self.mean, variance.sqrt())
+    }
+
+    /// Adds a sample, flagging it as an outlier against the previous samples.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn push(&mut self, value: f64) {
+        if self.values.len() >= MIN_SAMPLES_FOR_OUTLIERS {
+            let (mean, stddev) = self.mean_stddev();
+            if (value - mean).abs() > (OUTLIER_SIGMA * stddev).max(MIN_OUTLIER_DEVIATION) {
+                self.outliers += 1;
+            }
+        }
+
+        if self.values.len() >= MAX_SAMPLES {
+            if let Some(oldest) = self.values.pop_front() {
+                // reverse of the update below
+                let delta = oldest - self.mean;
+                self.mean -= delta / self.values.len() as f64;
+                self.m2 -= delta * (oldest - self.mean);
+            }
+        }
+        self.values.push_back(value);
+        let delta = value - self.mean;
+        self.mean += delta / self.values.len() as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn summary(&self) -> Option<TimingSummary> {
+        if self.values.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f64> = self.values.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        // nearest rank
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        let (mean, stddev) = self.mean_stddev();
+        Some(TimingSummary {
+            count: sorted.len(),
+            mean,
+            stddev,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+        })
+    }
+
+    /// Histogram of the samples as (bin start, bin width, count).
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn histogram(&self, bins: usize) -> Vec<(f64, f64, usize)> {
+        let Some(summary) = self.summary() else {
+            return Vec::new();
+        };
+        let range = summary.max - summary.min;
+        if range <= 0.0 {
+            return vec![(summary.min, 0.0, summary.count)];
+        }
+
+        let width = range / bins as f64;
+        let mut counts = vec![0; bins];
+        for v in &self.values {
+            let bin = (((v - summary.min) / width) as usize).min(bins - 1);
+            counts[bin] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| (summary.min + i as f64 * width, width, count))
+            .collect()
+    }
+}
+
+/// Inter-arrival times of one COB ID.
+#[derive(Debug, Clone)]
+pub struct CobTiming {
+    pub cob_str: String,
+    pub parsed_type: RxMessageType,
+    pub periods: Samples,
+    last: Instant,
+}
+
+/// Timing analysis of all COB IDs and SYNC to PDO latencies.
+#[derive(Debug, Default)]
+pub struct TimingAnalysis {
+    /// By [`MessageCached::frame_id`].
+    cobs: BTreeMap<(bool, u32), CobTiming>,
+    /// Time from SYNC to the first TPDO of the node.
+    latencies: BTreeMap<NodeId, Samples>,
+    last_sync: Option<Instant>,
+    /// Nodes which already answered the last SYNC.
+    responded: BTreeSet<NodeId>,
+}
+
+impl TimingAnalysis {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        let time = msg.get_timestamp();
+        let id = msg.frame_id();
+        match self.cobs.get_mut(&id) {
+            Some(cob) => {
+                let period = time.saturating_duration_since(cob.last).as_secs_f64();
+                cob.periods.push(period);
+                cob.last = time;
+            }
+            None => {
+                self.cobs.insert(
+                    id,
+                    CobTiming {
+                        cob_str: msg.cob_str.clone(),
+                        parsed_type: msg.msg.parsed_type,
+                        periods: Samples::default(),
+                        last: time,
+                    },
+                );
+            }
+        }
+
+        if matches!(msg.additional, RxMessageAdditional::Sync(_)) {
+            self.last_sync = Some(time);
+            self.responded.clear();
+            return;
+        }
+
+        let function_code = msg.msg.msg.cob_id & FUNCTION_CODE_MASK;
+        if msg.msg.parsed_type != RxMessageType::Pdo
+            || !TPDO_FUNCTION_CODES.contains(&function_code)
+        {
+            return;
+        }
+        let (Some(sync), Some(node_id)) = (self.last_sync, msg.msg.parsed_node_id) else {
+            return;
+        };
+        if self.responded.insert(node_id) {
+            let latency = time.saturating_duration_since(sync).as_secs_f64();
+            self.latencies.entry(node_id).or_default().push(latency);
+        }
+    }
+
+    pub fn cobs(&self) -> impl Iterator<Item = (&(bool, u32), &CobTiming)> {
+        self.cobs.iter()
+    }
+
+    pub fn cob(&self, id: (bool, u32)) -> Option<&CobTiming> {
+        self.cobs.get(&id)
+    }
+
+    pub fn latencies(&self) -> impl Iterator<Item = (&NodeId, &Samples)> {
+        self.latencies.iter()
+    }
+
+    pub fn latency(&self, node_id: NodeId) -> Option<&Samples> {
+        self.latencies.get(&node_id)
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Samples, TimingAnalysis, MAX_SAMPLES};
+    use crate::message_cached::test_message;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_timing_analysis() {
+        let start = Instant::now();
+        let at = |us| start + Duration::from_micros(us);
+        let mut timing = TimingAnalysis::default();
+        for cycle in 0..100 {
+            let sync = cycle * 10_000;
+            // one late cycle
+            let sync = if cycle == 50 { sync + 3_000 } else { sync };
+            timing.push_data(&test_message(0x080, &[], at(sync)));
+            timing.push_data(&test_message(0x185, &[1], at(sync + 200)));
+            timing.push_data(&test_message(0x285, &[2], at(sync + 400)));
+        }
+
+        let sync = timing.cob((false, 0x080)).unwrap();
+        assert_eq!(sync.periods.len(), 99);
+        assert_eq!(sync.periods.outliers, 2);
+        let summary = sync.periods.summary().unwrap();
+        assert!((summary.p50 - 0.010).abs() < 1e-9);
+        assert!((summary.max - 0.013).abs() < 1e-9);
+        assert!((summary.min - 0.007).abs() < 1e-9);
+
+        let latency = timing.latency(5).unwrap().summary().unwrap();
+        assert_eq!(latency.count, 100);
+        assert!((latency.mean - 0.0002).abs() < 1e-9);
+
+        let histogram = sync.periods.histogram(6);
+        assert_eq!(histogram.iter().map(|b| b.2).sum::<usize>(), 99);
+        assert_eq!(histogram[0].2, 1);
+
+        // statistics follow the samples kept when the oldest are dropped
+        let value = |i: usize| f64::from(u8::try_from(i % 7).unwrap());
+        let mut samples = Samples::default();
+        let mut kept = Samples::default();
+        for i in 0..MAX_SAMPLES + 100 {
+            samples.push(value(i));
+            if i >= 100 {
+                kept.push(value(i));
+            }
+        }
+        let (summary, expected) = (samples.summary().unwrap(), kept.summary().unwrap());
+        assert_eq!(summary.count, MAX_SAMPLES);
+        assert!((summary.mean - expected.mean).abs() < 1e-9);
+        assert!((summary.stddev - expected.stddev).abs() < 1e-9);
+    }
+}
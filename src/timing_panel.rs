@@ -0,0 +1,158 @@
+use crate::{
+    message_cached::MessageCached,
+    theme::{OZON_BLUE, OZON_PINK},
+    timing::{Samples, TimingAnalysis, TimingSummary},
+};
+use egui::RichText;
+use egui_plot::{Bar, BarChart, Plot};
+use oze_canopen::canopen::NodeId;
+
+const HISTOGRAM_BINS: usize = 40;
+
+/// Samples shown in the histogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selected {
+    Cob((bool, u32)),
+    Latency(NodeId),
+}
+
+/// Shows inter-arrival statistics per COB ID and SYNC to PDO latency per node.
+#[derive(Debug, Default)]
+pub struct TimingPanel {
+    timing: TimingAnalysis,
+    selected: Option<Selected>,
+}
+
+fn ms(seconds: f64) -> String {
+    format!("{:.3}", seconds * 1000.0)
+}
+
+const HEADERS: [&str; 9] = [
+    "samples", "mean ms", "σ ms", "min ms", "p50 ms", "p95 ms", "p99 ms", "max ms", "outliers",
+];
+
+fn summary_cells(ui: &mut egui::Ui, summary: &TimingSummary, samples: &Samples) {
+    ui.label(summary.count.to_string());
+    ui.label(ms(summary.mean));
+    ui.label(ms(summary.stddev));
+    ui.label(ms(summary.min));
+    ui.label(ms(summary.p50));
+    ui.label(ms(summary.p95));
+    ui.label(ms(summary.p99));
+    ui.label(ms(summary.max));
+    if samples.outliers > 0 {
+        ui.colored_label(OZON_PINK, samples.outliers.to_string())
+            .on_hover_text("Samples further than 3σ from the mean");
+    } else {
+        ui.label("0");
+    }
+}
+
+impl TimingPanel {
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        self.timing.push_data(msg);
+    }
+
+    pub fn update(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .button("🗑")
+            .on_hover_text("Clear timing statistics")
+            .clicked()
+        {
+            self.timing.clear();
+            self.selected = None;
+        }
+
+        ui.label("Inter-arrival time per COB ID, click a row to show its histogram");
+        egui::ScrollArea::vertical()
+            .id_salt("timing_cobs")
+            .max_height(250.0)
+            .show(ui, |ui| {
+                egui::Grid::new("timing_cobs_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in ["COB ID", "Packet type"].iter().chain(&HEADERS) {
+                            ui.label(RichText::new(*header).strong());
+                        }
+                        ui.end_row();
+
+                        for (id, cob) in self.timing.cobs() {
+                            let Some(summary) = cob.periods.summary() else {
+                                continue;
+                            };
+                            let selected = self.selected == Some(Selected::Cob(*id));
+                            if ui.selectable_label(selected, &cob.cob_str).clicked() {
+                                self.selected = Some(Selected::Cob(*id));
+                            }
+                            ui.label(cob.parsed_type.to_string());
+                            summary_cells(ui, &summary, &cob.periods);
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        ui.separator();
+        ui.label("Latency from SYNC to the first TPDO of each node");
+        egui::Grid::new("timing_latency_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for header in ["Node ID"].iter().chain(&HEADERS) {
+                    ui.label(RichText::new(*header).strong());
+                }
+                ui.end_row();
+
+                for (node_id, samples) in self.timing.latencies() {
+                    let Some(summary) = samples.summary() else {
+                        continue;
+                    };
+                    let selected = self.selected == Some(Selected::Latency(*node_id));
+                    if ui
+                        .selectable_label(selected, format!("{node_id:3}"))
+                        .clicked()
+                    {
+                        self.selected = Some(Selected::Latency(*node_id));
+                    }
+                    summary_cells(ui, &summary, samples);
+                    ui.end_row();
+                }
+            });
+
+        self.show_histogram(ui);
+    }
+
+    fn show_histogram(&self, ui: &mut egui::Ui) {
+        let (name, samples) = match self.selected {
+            Some(Selected::Cob(id)) => match self.timing.cob(id) {
+                Some(cob) => (format!("COB ID {} period", cob.cob_str), &cob.periods),
+                None => return,
+            },
+            Some(Selected::Latency(node_id)) => match self.timing.latency(node_id) {
+                Some(samples) => (format!("node {node_id} SYNC latency"), samples),
+                None => return,
+            },
+            None => return,
+        };
+
+        #[allow(clippy::cast_precision_loss)]
+        let bars = samples
+            .histogram(HISTOGRAM_BINS)
+            .into_iter()
+            .map(|(start, width, count)| {
+                let width = width * 1000.0;
+                Bar::new(start * 1000.0 + width / 2.0, count as f64)
+                    .width(width.max(0.001))
+                    .fill(OZON_BLUE)
+            })
+            .collect();
+
+        ui.separator();
+        ui.label(format!("{name} histogram, ms"));
+        Plot::new("timing_histogram")
+            .height(150.0)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .show(ui, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new(bars).name(name));
+            });
+    }
+}
@@ -0,0 +1,266 @@
+use core::fmt;
+
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+const POSITIVE_RESPONSE_OFFSET: u8 = 0x40;
+const SUPPRESS_POSITIVE_RESPONSE: u8 = 0x80;
+
+const DIAGNOSTIC_SESSION_CONTROL: u8 = 0x10;
+const ECU_RESET: u8 = 0x11;
+const READ_DATA_BY_IDENTIFIER: u8 = 0x22;
+const SECURITY_ACCESS: u8 = 0x27;
+const WRITE_DATA_BY_IDENTIFIER: u8 = 0x2E;
+const ROUTINE_CONTROL: u8 = 0x31;
+const TRANSFER_DATA: u8 = 0x36;
+const TESTER_PRESENT: u8 = 0x3E;
+
+/// Longer payloads are shortened when displayed.
+const MAX_SHOWN_BYTES: usize = 16;
+
+/// UDS (ISO 14229) request or response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UdsMessage {
+    Request { sid: u8, data: Vec<u8> },
+    Response { sid: u8, data: Vec<u8> },
+    Negative { sid: u8, nrc: u8 },
+}
+
+pub fn service_name(sid: u8) -> Option<&'static str> {
+    Some(match sid {
+        DIAGNOSTIC_SESSION_CONTROL => "DiagnosticSessionControl",
+        ECU_RESET => "ECUReset",
+        0x14 => "ClearDiagnosticInformation",
+        0x19 => "ReadDTCInformation",
+        READ_DATA_BY_IDENTIFIER => "ReadDataByIdentifier",
+        0x23 => "ReadMemoryByAddress",
+        SECURITY_ACCESS => "SecurityAccess",
+        0x28 => "CommunicationControl",
+        WRITE_DATA_BY_IDENTIFIER => "WriteDataByIdentifier",
+        0x2F => "InputOutputControlByIdentifier",
+        ROUTINE_CONTROL => "RoutineControl",
+        0x34 => "RequestDownload",
+        0x35 => "RequestUpload",
+        TRANSFER_DATA => "TransferData",
+        0x37 => "RequestTransferExit",
+        0x3D => "WriteMemoryByAddress",
+        TESTER_PRESENT => "TesterPresent",
+        0x85 => "ControlDTCSetting",
+        _ => return None,
+    })
+}
+
+pub fn nrc_name(nrc: u8) -> &'static str {
+    match nrc {
+        0x10 => "generalReject",
+        0x11 => "serviceNotSupported",
+        0x12 => "subFunctionNotSupported",
+        0x13 => "incorrectMessageLengthOrInvalidFormat",
+        0x14 => "responseTooLong",
+        0x21 => "busyRepeatRequest",
+        0x22 => "conditionsNotCorrect",
+        0x24 => "requestSequenceError",
+        0x25 => "noResponseFromSubnetComponent",
+        0x26 => "failurePreventsExecutionOfRequestedAction",
+        0x31 => "requestOutOfRange",
+        0x33 => "securityAccessDenied",
+        0x35 => "invalidKey",
+        0x36 => "exceedNumberOfAttempts",
+        0x37 => "requiredTimeDelayNotExpired",
+        0x70 => "uploadDownloadNotAccepted",
+        0x71 => "transferDataSuspended",
+        0x72 => "generalProgrammingFailure",
+        0x73 => "wrongBlockSequenceCounter",
+        0x78 => "requestCorrectlyReceived-ResponsePending",
+        0x7E => "subFunctionNotSupportedInActiveSession",
+        0x7F => "serviceNotSupportedInActiveSession",
+        _ => "unknown",
+    }
+}
+
+fn session_name(session: u8) -> &'static str {
+    match session {
+        0x01 => "default",
+        0x02 => "programming",
+        0x03 => "extended",
+        0x04 => "safety",
+        _ => "other",
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    let s = data
+        .iter()
+        .take(MAX_SHOWN_BYTES)
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if data.len() > MAX_SHOWN_BYTES {
+        format!("{s} ... ({} bytes)", data.len())
+    } else {
+        s
+    }
+}
+
+impl UdsMessage {
+    /// Parses a complete ISO-TP payload; requests and responses differ only by the service ID.
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let (&sid, data) = payload.split_first()?;
+        if sid == NEGATIVE_RESPONSE {
+            let [sid, nrc, ..] = data else {
+                return None;
+            };
+            return Some(UdsMessage::Negative {
+                sid: *sid,
+                nrc: *nrc,
+            });
+        }
+
+        let data = data.to_vec();
+        if sid >= POSITIVE_RESPONSE_OFFSET && service_name(sid - POSITIVE_RESPONSE_OFFSET).is_some()
+        {
+            Some(UdsMessage::Response {
+                sid: sid - POSITIVE_RESPONSE_OFFSET,
+                data,
+            })
+        } else {
+            Some(UdsMessage::Request { sid, data })
+        }
+    }
+
+    fn sid(&self) -> u8 {
+        match self {
+            UdsMessage::Request { sid, .. }
+            | UdsMessage::Response { sid, .. }
+            | UdsMessage::Negative { sid, .. } => *sid,
+        }
+    }
+}
+
+/// Formats the service specific parameters.
+fn write_parameters(f: &mut fmt::Formatter, sid: u8, response: bool, data: &[u8]) -> fmt::Result {
+    match (sid, data) {
+        (DIAGNOSTIC_SESSION_CONTROL, [session, ..]) => {
+            let session = session & !SUPPRESS_POSITIVE_RESPONSE;
+            write!(f, " {} session", session_name(session))
+        }
+        (SECURITY_ACCESS, [level, rest @ ..]) => {
+            let level = level & !SUPPRESS_POSITIVE_RESPONSE;
+            let what = match (level % 2 == 1, response) {
+                (true, false) => "requestSeed",
+                (true, true) => "seed",
+                (false, _) => "sendKey",
+            };
+            write!(f, " level {level:02X} {what}")?;
+            if !rest.is_empty() {
+                write!(f, " [{}]", hex(rest))?;
+            }
+            Ok(())
+        }
+        (READ_DATA_BY_IDENTIFIER, [hi, lo, rest @ ..]) if response => {
+            let value = if rest.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+                format!("\"{}\"", String::from_utf8_lossy(rest))
+            } else {
+                hex(rest)
+            };
+            write!(f, " DID {hi:02X}{lo:02X} = {value}")
+        }
+        (READ_DATA_BY_IDENTIFIER, dids) => {
+            for did in dids.chunks(2) {
+                write!(f, " DID {}", hex(did).replace(' ', ""))?;
+            }
+            Ok(())
+        }
+        (WRITE_DATA_BY_IDENTIFIER, [hi, lo, rest @ ..]) => {
+            write!(f, " DID {hi:02X}{lo:02X}")?;
+            if !rest.is_empty() {
+                write!(f, " = {}", hex(rest))?;
+            }
+            Ok(())
+        }
+        (ROUTINE_CONTROL, [control, hi, lo, rest @ ..]) => {
+            let control = match control & !SUPPRESS_POSITIVE_RESPONSE {
+                0x01 => "start",
+                0x02 => "stop",
+                0x03 => "results",
+                _ => "other",
+            };
+            write!(f, " {control} routine {hi:02X}{lo:02X}")?;
+            if !rest.is_empty() {
+                write!(f, " [{}]", hex(rest))?;
+            }
+            Ok(())
+        }
+        (TRANSFER_DATA, [counter, rest @ ..]) => {
+            write!(f, " block {counter}")?;
+            if !rest.is_empty() {
+                write!(f, " {} bytes", rest.len())?;
+            }
+            Ok(())
+        }
+        (ECU_RESET | TESTER_PRESENT, [sub, ..]) => {
+            write!(f, " sub {:02X}", sub & !SUPPRESS_POSITIVE_RESPONSE)
+        }
+        (_, []) => Ok(()),
+        (_, data) => write!(f, " [{}]", hex(data)),
+    }
+}
+
+impl fmt::Display for UdsMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sid = self.sid();
+        let name = service_name(sid).map_or_else(|| format!("SID {sid:02X}"), ToOwned::to_owned);
+        match self {
+            UdsMessage::Request { data, .. } => {
+                write!(f, "UDS → {name}")?;
+                write_parameters(f, sid, false, data)
+            }
+            UdsMessage::Response { data, .. } => {
+                write!(f, "UDS ← {name}")?;
+                write_parameters(f, sid, true, data)
+            }
+            UdsMessage::Negative { nrc, .. } => {
+                write!(f, "UDS ← {name} NEGATIVE {nrc:02X} {}", nrc_name(*nrc))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UdsMessage;
+
+    fn decode(payload: &[u8]) -> String {
+        UdsMessage::parse(payload).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_uds_decoding() {
+        assert_eq!(
+            decode(&[0x10, 0x02]),
+            "UDS → DiagnosticSessionControl programming session"
+        );
+        assert_eq!(
+            decode(&[0x50, 0x02, 0x00, 0x32, 0x01, 0xF4]),
+            "UDS ← DiagnosticSessionControl programming session"
+        );
+        assert_eq!(
+            decode(&[0x67, 0x01, 0x12, 0x34]),
+            "UDS ← SecurityAccess level 01 seed [12 34]"
+        );
+        assert_eq!(
+            decode(&[0x7F, 0x27, 0x35]),
+            "UDS ← SecurityAccess NEGATIVE 35 invalidKey"
+        );
+        assert_eq!(
+            decode(&[0x22, 0xF1, 0x90]),
+            "UDS → ReadDataByIdentifier DID F190"
+        );
+        assert_eq!(
+            decode(&[0x62, 0xF1, 0x90, b'W', b'0', b'L']),
+            "UDS ← ReadDataByIdentifier DID F190 = \"W0L\""
+        );
+        assert_eq!(
+            decode(&[0x2E, 0xF1, 0x99, 0x01, 0x02]),
+            "UDS → WriteDataByIdentifier DID F199 = 01 02"
+        );
+    }
+}
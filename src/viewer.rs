@@ -1,68 +1,108 @@
-use crate::{filter::GlobalFilter, message_cached::MessageCached, message_row::MessageRow};
-use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+use crate::{
+    filter::GlobalFilter, fixed_trace::FixedTrace, history::History, message_cached::MessageCached,
+    message_row::MessageRow,
+};
+use std::{cell::RefCell, rc::Rc};
+use tokio::time::Instant;
+
+/// How the viewer shows the messages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    /// Chronological list of messages, newest first.
+    #[default]
+    List,
+    /// One row per COB ID updated in place.
+    Fixed,
+}
 
 #[derive(Debug)]
 pub struct Viewer {
     global_filter: Rc<RefCell<GlobalFilter>>,
     pub message_row: MessageRow,
+    pub mode: ViewMode,
+    fixed_trace: FixedTrace,
+    /// Show only messages received in this range, selected in the chart.
+    pub time_range: Option<(Instant, Instant)>,
+    /// Scroll to the first highlighted message on the next update.
+    scroll_to_highlight: bool,
 }
 
 impl Viewer {
     pub fn new(global_filter: Rc<RefCell<GlobalFilter>>) -> Self {
         Self {
             message_row: MessageRow::default(),
+            mode: ViewMode::default(),
+            fixed_trace: FixedTrace::default(),
+            time_range: None,
+            scroll_to_highlight: false,
             global_filter,
         }
     }
 
-    pub fn update(&mut self, ui: &mut egui::Ui, data: &VecDeque<MessageCached>) {
-        let filt = self.global_filter.borrow();
-        let data: VecDeque<&MessageCached> = data.iter().filter(|i| !filt.filter(i)).collect();
+    /// Collects statistics of every message, the filter is applied when they are shown.
+    pub fn push_data(&mut self, msg: &MessageCached) {
+        self.fixed_trace.push_data(msg);
+    }
 
-        // let sessions: Vec<(u8, u8)> = Vec::new();
-        // for i in &data {
-        //     match &i.additional {
-        //         RxMessageAdditional::SdoTx(d) => (),
-        //         RxMessageAdditional::SdoRx(d) => (),
+    /// Highlights the messages received in the range and scrolls to them.
+    pub fn highlight(&mut self, range: Option<(Instant, Instant)>) {
+        self.message_row.highlight = range;
+        self.scroll_to_highlight = range.is_some();
+    }
 
-        //         _ => (),
-        //     }
-        // }
+    /// Shows the messages passing the global filter, newest first.
+    pub fn update(&mut self, ui: &mut egui::Ui, history: &History) {
+        if self.mode == ViewMode::Fixed {
+            let filt = self.global_filter.borrow();
+            self.fixed_trace.update(ui, &self.message_row, &filt);
+            return;
+        }
+        let range = history.filtered_range(self.time_range);
+        let row_message = |row: usize| history.filtered(range.end - 1 - row);
 
         let row_spacing = 4.0;
         let column_spacing = 20.0;
         let text_style = egui::TextStyle::Body;
         let text_height = ui.text_style_height(&text_style);
         let height = text_height + row_spacing;
-        egui::ScrollArea::vertical().animated(true).show_rows(
-            ui,
-            height,
-            data.len() + 1,
-            |ui, row_range| {
-                egui::Grid::new("viewer_grid")
-                    .start_row(row_range.start)
-                    .spacing([column_spacing, row_spacing])
-                    .striped(true)
-                    .min_row_height(height)
-                    .show(ui, |ui| {
-                        let data_range = if row_range.start == 0 {
-                            self.message_row.header(ui);
-                            ui.end_row();
-                            0..(row_range.end - 1)
-                        } else {
-                            (row_range.start - 1)..(row_range.end - 1)
-                        };
-
-                        for d in data.range(data_range) {
-                            self.message_row.message(ui, d);
-                            ui.end_row();
-                        }
-
-                        // Костыль нужный, чтобы выровнять ширину столбца
+        let mut scroll_area = egui::ScrollArea::vertical().animated(true);
+        if std::mem::take(&mut self.scroll_to_highlight) {
+            // newest messages first, the oldest highlighted one is the first received at the cursor
+            if let Some((from, to)) = self.message_row.highlight {
+                let position = history.filtered_position(from).max(range.start);
+                if position < range.end && history.filtered(position).get_timestamp() <= to {
+                    // the header is the first row
+                    let row = range.end - position;
+                    #[allow(clippy::cast_precision_loss)]
+                    let offset = row as f32 * (height + ui.spacing().item_spacing.y);
+                    scroll_area = scroll_area.vertical_scroll_offset(offset);
+                }
+            }
+        }
+        scroll_area.show_rows(ui, height, range.len() + 1, |ui, row_range| {
+            egui::Grid::new("viewer_grid")
+                .start_row(row_range.start)
+                .spacing([column_spacing, row_spacing])
+                .striped(true)
+                .min_row_height(height)
+                .show(ui, |ui| {
+                    let data_range = if row_range.start == 0 {
                         self.message_row.header(ui);
                         ui.end_row();
-                    });
-            },
-        );
+                        0..(row_range.end - 1)
+                    } else {
+                        (row_range.start - 1)..(row_range.end - 1)
+                    };
+
+                    for row in data_range {
+                        self.message_row.message(ui, row_message(row));
+                        ui.end_row();
+                    }
+
+                    // Костыль нужный, чтобы выровнять ширину столбца
+                    self.message_row.header(ui);
+                    ui.end_row();
+                });
+        });
     }
 }
@@ -7,7 +7,7 @@ mod tests {
         interface::Connection,
         proto::nmt::{NmtCommand, NmtCommandSpecifier},
     };
-    use oze_canopen_viewer::driver::{self, Control};
+    use oze_canopen_viewer::driver::{self, Control, Protocol};
     use tokio::{sync::watch, time::sleep};
 
     async fn send_test_messages() {
@@ -31,6 +31,7 @@ mod tests {
                 can_name: "vcan0".to_owned(),
                 bitrate: Some(100_000),
             },
+            protocol: Protocol::CanOpen,
         };
 
         let (state_snd, state_rcv) = watch::channel(driver::State::default());